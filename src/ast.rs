@@ -1,80 +1,310 @@
-use crate::lexer::Token;
+use std::cell::Cell;
 
-#[derive(Debug, Clone)]
+use serde::{Deserialize, Serialize};
+
+use crate::diagnostic::Span;
+use crate::formatter;
+
+// `位置` 字段记录出错时要指向的记号：变量名、运算符、函数名或方法名
+
+/// 名字解析得到的绑定位置：从使用处的作用域沿上级走 `depth` 层，取其中第 `index` 个绑定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Slot {
+    pub depth: usize,
+    pub index: usize,
+}
+
+/// 类型标注中可以写的类型，名字与运行时错误信息中的类型名称一致
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Type {
+    数字,
+    字符串,
+    布尔值,
+    数组,
+    字典,
+    空,
+}
+
+impl Type {
+    pub const ALL: [Type; 6] = [Type::数字, Type::字符串, Type::布尔值, Type::数组, Type::字典, Type::空];
+    
+    pub fn name(self) -> &'static str {
+        match self {
+            Type::数字 => "数字",
+            Type::字符串 => "字符串",
+            Type::布尔值 => "布尔值",
+            Type::数组 => "数组",
+            Type::字典 => "字典",
+            Type::空 => "空",
+        }
+    }
+    
+    pub fn from_name(name: &str) -> Option<Type> {
+        Type::ALL.into_iter().find(|ty| ty.name() == name)
+    }
+}
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Expression {
     数字字面量(f64),
     字符串字面量(String),
-    变量(String),
+    布尔字面量(bool),
+    /// `槽位` 由静态检查中的名字解析填写，解释器用它代替按名字查找
+    变量 {
+        名字: String,
+        位置: Span,
+        #[serde(skip)]
+        槽位: Cell<Option<Slot>>,
+    },
+    /// 方法中的接收者
+    自己 {
+        位置: Span,
+    },
     二元运算 {
         左: Box<Expression>,
         运算符: String,
         右: Box<Expression>,
+        位置: Span,
     },
     一元运算 {
         运算符: String,
         操作数: Box<Expression>,
+        位置: Span,
     },
+    /// 调用任意表达式的值；被调用的是变量时 `位置` 指向变量名，否则指向左括号
     函数调用 {
-        函数名: String,
+        函数: Box<Expression>,
         参数: Vec<Expression>,
+        位置: Span,
+    },
+    /// `函数(参数) { ... }`，`位置` 指向 `函数` 关键字；类型标注与函数声明相同
+    匿名函数 {
+        参数: Vec<String>,
+        #[serde(default)]
+        参数类型: Vec<Option<Type>>,
+        #[serde(default)]
+        返回类型: Option<Type>,
+        体: Vec<Statement>,
+        位置: Span,
     },
     赋值 {
         变量名: String,
         值: Box<Expression>,
+        位置: Span,
+        #[serde(skip)]
+        槽位: Cell<Option<Slot>>,
     },
-    数组字面量(Vec<Expression>),
-    数组索引 {
+    /// `数组[索引] = 值`，`数组` 本身可以是索引表达式，`位置` 指向左中括号
+    索引赋值 {
         数组: Box<Expression>,
         索引: Box<Expression>,
+        值: Box<Expression>,
+        位置: Span,
     },
-    数组长度(Box<Expression>),
-    数组添加 {
-        数组: Box<Expression>,
-        元素: Box<Expression>,
+    数组字面量(Vec<Expression>),
+    /// `起..止`，与 `范围(起, 止)` 相同，不包含 `止`；`位置` 指向 `..`
+    范围 {
+        起: Box<Expression>,
+        止: Box<Expression>,
+        位置: Span,
     },
-    数组删除 {
+    /// 数组按下标取值，字典按键取值
+    数组索引 {
         数组: Box<Expression>,
         索引: Box<Expression>,
+        位置: Span,
+    },
+    /// `{键: 值, ...}`，`位置` 指向左大括号
+    字典字面量 {
+        条目: Vec<(Expression, Expression)>,
+        位置: Span,
+    },
+    /// `对象.成员`，`位置` 指向成员名
+    成员访问 {
+        对象: Box<Expression>,
+        成员: String,
+        位置: Span,
+    },
+    /// `对象.成员 = 值`
+    成员赋值 {
+        对象: Box<Expression>,
+        成员: String,
+        值: Box<Expression>,
+        位置: Span,
+    },
+    /// `对象.方法(参数)`，数组和字典的内置方法与结构的方法都在运行时按对象的类型查找
+    方法调用 {
+        对象: Box<Expression>,
+        方法: String,
+        参数: Vec<Expression>,
+        位置: Span,
     },
 }
 
-#[derive(Debug, Clone)]
+impl Expression {
+    /// 表达式在源代码中的位置，字面量没有记录位置
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Expression::数字字面量(_)
+            | Expression::字符串字面量(_)
+            | Expression::布尔字面量(_)
+            | Expression::数组字面量(_) => None,
+            Expression::变量 { 位置, .. }
+            | Expression::自己 { 位置 }
+            | Expression::二元运算 { 位置, .. }
+            | Expression::一元运算 { 位置, .. }
+            | Expression::函数调用 { 位置, .. }
+            | Expression::匿名函数 { 位置, .. }
+            | Expression::赋值 { 位置, .. }
+            | Expression::索引赋值 { 位置, .. }
+            | Expression::数组索引 { 位置, .. }
+            | Expression::范围 { 位置, .. }
+            | Expression::字典字面量 { 位置, .. }
+            | Expression::成员访问 { 位置, .. }
+            | Expression::成员赋值 { 位置, .. }
+            | Expression::方法调用 { 位置, .. } => Some(*位置),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Statement {
     表达式语句(Expression),
+    /// `让 名字: 类型 = 初始值`，类型可以省略
     变量声明 {
         名字: String,
+        #[serde(default)]
+        类型: Option<Type>,
         初始值: Option<Expression>,
         是常量: bool,
+        /// 带 `公开` 的顶层声明可以被导入它所在文件的模块访问
+        是公开: bool,
+        位置: Span,
     },
+    /// `函数 名字(a: 类型, b) -> 类型 { ... }`；`参数类型` 与 `参数` 一一对应，没有标注的是 None
     函数声明 {
         名字: String,
         参数: Vec<String>,
+        #[serde(default)]
+        参数类型: Vec<Option<Type>>,
+        #[serde(default)]
+        返回类型: Option<Type>,
         体: Vec<Statement>,
+        是公开: bool,
+        位置: Span,
+    },
+    /// `结构 名字 { 字段, ... 函数 方法(...) { ... } }`，`方法` 中都是函数声明
+    结构声明 {
+        名字: String,
+        字段: Vec<String>,
+        方法: Vec<Statement>,
+        是公开: bool,
+        位置: Span,
+    },
+    /// `导入 "路径"` 或 `导入 名字`，可以用 `作为 别名` 换一个绑定的名字；
+    /// `路径` 保存源代码中写的路径，名字形式保存为名字本身
+    导入语句 {
+        路径: String,
+        别名: Option<String>,
+        位置: Span,
     },
+    /// `否则 如果` 解析为只含一条 如果 语句的 `假分支`；`位置` 指向 `如果` 关键字
     如果语句 {
         条件: Expression,
         真分支: Vec<Statement>,
         假分支: Vec<Statement>,
+        位置: Span,
     },
+    /// `循环 条件 { ... }` 或 `循环 { ... }`，没有条件时一直循环
     循环语句 {
         条件: Option<Expression>,
         体: Vec<Statement>,
+        位置: Span,
     },
+    /// `当 条件 { ... }`，与 `循环 条件 { ... }` 相同
     当语句 {
         条件: Expression,
         体: Vec<Statement>,
+        位置: Span,
     },
+    /// `对于 x 在 ...` 或 `对于 (键, 值) 在 字典`，后者的第二个变量存在 `值变量` 中
     对于语句 {
         变量: String,
+        值变量: Option<String>,
         可迭代: Expression,
         体: Vec<Statement>,
+        位置: Span,
+    },
+    /// `尝试 { ... } 捕获 (错误) { ... } 最终 { ... }`，捕获和最终至少有一个
+    尝试语句 {
+        体: Vec<Statement>,
+        /// 绑定捕获到的错误的变量名和它的位置，`捕获 { ... }` 不绑定变量
+        捕获变量: Option<(String, Span)>,
+        捕获: Option<Vec<Statement>>,
+        最终: Option<Vec<Statement>>,
+        位置: Span,
+    },
+    /// `位置` 指向 `抛出` 关键字，也是抛出的错误的位置
+    抛出语句 {
+        值: Expression,
+        位置: Span,
+    },
+    返回语句 {
+        值: Option<Expression>,
+        位置: Span,
+    },
+    跳出语句 {
+        位置: Span,
     },
-    返回语句(Option<Expression>),
-    跳出语句,
-    继续语句,
+    继续语句 {
+        位置: Span,
+    },
+    /// 语法错误的占位，只出现在带错误恢复解析出的程序中
+    错误 {
+        位置: Span,
+    },
+    /// 注释，只出现在保留注释解析出的程序中；`行尾` 表示同一行中注释前面有代码
+    注释 {
+        内容: String,
+        行尾: bool,
+        位置: Span,
+    },
+    /// 源代码中的空行，与 `注释` 一样只用于格式化
+    空行,
+}
+
+impl Statement {
+    /// 语句在源代码中的位置，通常是关键字或声明的名字；只含字面量的表达式语句和空行没有位置
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Statement::表达式语句(expr) => expr.span(),
+            Statement::空行 => None,
+            Statement::变量声明 { 位置, .. }
+            | Statement::函数声明 { 位置, .. }
+            | Statement::结构声明 { 位置, .. }
+            | Statement::导入语句 { 位置, .. }
+            | Statement::如果语句 { 位置, .. }
+            | Statement::循环语句 { 位置, .. }
+            | Statement::当语句 { 位置, .. }
+            | Statement::对于语句 { 位置, .. }
+            | Statement::尝试语句 { 位置, .. }
+            | Statement::抛出语句 { 位置, .. }
+            | Statement::返回语句 { 位置, .. }
+            | Statement::跳出语句 { 位置 }
+            | Statement::继续语句 { 位置 }
+            | Statement::错误 { 位置 }
+            | Statement::注释 { 位置, .. } => Some(*位置),
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Program {
     pub 语句: Vec<Statement>,
 }
@@ -91,27 +321,51 @@ impl Program {
     }
 }
 
+// 显示语法树的结构，用于 `cnlang ast`：每个二元和一元运算都加上括号，能直接看出结合顺序。
+// 格式化后的源代码见 formatter 模块
+
 impl std::fmt::Display for Expression {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Expression::数字字面量(n) => write!(f, "{}", n),
             Expression::字符串字面量(s) => write!(f, "\"{}\"", s),
-            Expression::变量(name) => write!(f, "{}", name),
-            Expression::二元运算 { 左, 运算符, 右 } => write!(f, "({} {} {})", 左, 运算符, 右),
-            Expression::一元运算 { 运算符, 操作数 } => write!(f, "{}{}", 运算符, 操作数),
-            Expression::函数调用 { 函数名, 参数 } => {
+            Expression::布尔字面量(b) => write!(f, "{}", if *b { "真" } else { "假" }),
+            Expression::变量 { 名字, .. } => write!(f, "{}", 名字),
+            Expression::自己 { .. } => write!(f, "自己"),
+            Expression::二元运算 { 左, 运算符, 右, .. } => {
+                let operator = match 运算符.as_str() {
+                    "&&" => "且",
+                    "||" => "或",
+                    other => other,
+                };
+                write!(f, "({} {} {})", 左, operator, 右)
+            }
+            Expression::一元运算 { 操作数, .. } => write!(f, "(非 {})", 操作数),
+            Expression::函数调用 { 函数, 参数, .. } => {
                 let args: Vec<String> = 参数.iter().map(|arg| arg.to_string()).collect();
-                write!(f, "{}({})", 函数名, args.join(", "))
+                write!(f, "{}({})", 函数, args.join(", "))
             }
-            Expression::赋值 { 变量名, 值 } => write!(f, "{} = {}", 变量名, 值),
+            Expression::匿名函数 { 参数, 参数类型, 返回类型, 体, .. } => {
+                write!(f, "函数{} {}", formatter::parameters(参数, 参数类型, *返回类型), block(体))
+            }
+            Expression::赋值 { 变量名, 值, .. } => write!(f, "{} = {}", 变量名, 值),
+            Expression::索引赋值 { 数组, 索引, 值, .. } => write!(f, "{}[{}] = {}", 数组, 索引, 值),
             Expression::数组字面量(元素) => {
                 let elements: Vec<String> = 元素.iter().map(|elem| elem.to_string()).collect();
                 write!(f, "[{}]", elements.join(", "))
             }
-            Expression::数组索引 { 数组, 索引 } => write!(f, "{}[{}]", 数组, 索引),
-            Expression::数组长度(数组) => write!(f, "{}.长度", 数组),
-            Expression::数组添加 { 数组, 元素 } => write!(f, "{}.添加({})", 数组, 元素),
-            Expression::数组删除 { 数组, 索引 } => write!(f, "{}.删除({})", 数组, 索引),
+            Expression::数组索引 { 数组, 索引, .. } => write!(f, "{}[{}]", 数组, 索引),
+            Expression::范围 { 起, 止, .. } => write!(f, "({}..{})", 起, 止),
+            Expression::字典字面量 { 条目, .. } => {
+                let entries: Vec<String> = 条目.iter().map(|(key, value)| format!("{}: {}", key, value)).collect();
+                write!(f, "{{{}}}", entries.join(", "))
+            }
+            Expression::成员访问 { 对象, 成员, .. } => write!(f, "{}.{}", 对象, 成员),
+            Expression::成员赋值 { 对象, 成员, 值, .. } => write!(f, "{}.{} = {}", 对象, 成员, 值),
+            Expression::方法调用 { 对象, 方法, 参数, .. } => {
+                let args: Vec<String> = 参数.iter().map(|arg| arg.to_string()).collect();
+                write!(f, "{}.{}({})", 对象, 方法, args.join(", "))
+            }
         }
     }
 }
@@ -120,53 +374,112 @@ impl std::fmt::Display for Statement {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Statement::表达式语句(expr) => write!(f, "{}", expr),
-            Statement::变量声明 { 名字, 初始值, 是常量 } => {
+            Statement::变量声明 { 名字, 类型, 初始值, 是常量, 是公开, .. } => {
                 let const_str = if *是常量 { "常量" } else { "让" };
+                write!(f, "{}{} {}", public_prefix(*是公开), const_str, formatter::annotated(名字, *类型))?;
                 match 初始值 {
-                    Some(value) => write!(f, "{} {} = {}", const_str, 名字, value),
-                    None => write!(f, "{} {}", const_str, 名字),
+                    Some(value) => write!(f, " = {}", value),
+                    None => Ok(()),
                 }
             }
-            Statement::函数声明 { 名字, 参数, 体 } => {
-                let args = 参数.join(", ");
-                let body: Vec<String> = 体.iter().map(|stmt| stmt.to_string()).collect();
-                write!(f, "函数 {}({}) {{\n  {}\n}}", 名字, args, body.join("\n  "))
+            Statement::函数声明 { 名字, 参数, 参数类型, 返回类型, 体, 是公开, .. } => {
+                let signature = formatter::parameters(参数, 参数类型, *返回类型);
+                write!(f, "{}函数 {}{} {}", public_prefix(*是公开), 名字, signature, block(体))
             }
-            Statement::如果语句 { 条件, 真分支, 假分支 } => {
-                let true_body: Vec<String> = 真分支.iter().map(|stmt| stmt.to_string()).collect();
-                let false_body: Vec<String> = 假分支.iter().map(|stmt| stmt.to_string()).collect();
-                write!(f, "如果 {} {{\n  {}\n}} 否则 {{\n  {}\n}}", 条件, true_body.join("\n  "), false_body.join("\n  "))
+            Statement::结构声明 { 名字, 字段, 方法, 是公开, .. } => {
+                let members = std::iter::once(字段.join(", ")).chain(方法.iter().map(Statement::to_string));
+                write!(f, "{}结构 {} {}", public_prefix(*是公开), 名字, indented(members))
             }
-            Statement::循环语句 { 条件, 体 } => {
-                let body: Vec<String> = 体.iter().map(|stmt| stmt.to_string()).collect();
-                match 条件 {
-                    Some(cond) => write!(f, "循环 {} {{\n  {}\n}}", cond, body.join("\n  ")),
-                    None => write!(f, "循环 {{\n  {}\n}}", body.join("\n  ")),
+            Statement::导入语句 { 路径, 别名, .. } => {
+                write!(f, "导入 \"{}\"", 路径)?;
+                match 别名 {
+                    Some(alias) => write!(f, " 作为 {}", alias),
+                    None => Ok(()),
                 }
             }
-            Statement::当语句 { 条件, 体 } => {
-                let body: Vec<String> = 体.iter().map(|stmt| stmt.to_string()).collect();
-                write!(f, "当 {} {{\n  {}\n}}", 条件, body.join("\n  "))
+            Statement::如果语句 { 条件, 真分支, 假分支, .. } => {
+                write!(f, "如果 {} {}", condition(条件), block(真分支))?;
+                if 假分支.is_empty() {
+                    Ok(())
+                } else {
+                    write!(f, " 否则 {}", block(假分支))
+                }
             }
-            Statement::对于语句 { 变量, 可迭代, 体 } => {
-                let body: Vec<String> = 体.iter().map(|stmt| stmt.to_string()).collect();
-                write!(f, "对于 {} 在 {} {{\n  {}\n}}", 变量, 可迭代, body.join("\n  "))
+            Statement::循环语句 { 条件, 体, .. } => match 条件 {
+                Some(cond) => write!(f, "循环 {} {}", condition(cond), block(体)),
+                None => write!(f, "循环 {}", block(体)),
+            },
+            Statement::当语句 { 条件, 体, .. } => write!(f, "当 {} {}", condition(条件), block(体)),
+            Statement::对于语句 { 变量, 值变量, 可迭代, 体, .. } => {
+                let variables = match 值变量 {
+                    Some(second) => format!("({}, {})", 变量, second),
+                    None => 变量.clone(),
+                };
+                write!(f, "对于 {} 在 {} {}", variables, condition(可迭代), block(体))
             }
-            Statement::返回语句(值) => {
-                match 值 {
-                    Some(expr) => write!(f, "返回 {}", expr),
-                    None => write!(f, "返回"),
+            Statement::尝试语句 { 体, 捕获变量, 捕获, 最终, .. } => {
+                write!(f, "尝试 {}", block(体))?;
+                if let Some(handler) = 捕获 {
+                    match 捕获变量 {
+                        Some((name, _)) => write!(f, " 捕获 ({}) {}", name, block(handler))?,
+                        None => write!(f, " 捕获 {}", block(handler))?,
+                    }
                 }
+                if let Some(finally) = 最终 {
+                    write!(f, " 最终 {}", block(finally))?;
+                }
+                Ok(())
             }
-            Statement::跳出语句 => write!(f, "跳出"),
-            Statement::继续语句 => write!(f, "继续"),
+            Statement::抛出语句 { 值, .. } => write!(f, "抛出 {}", 值),
+            Statement::返回语句 { 值, .. } => match 值 {
+                Some(expr) => write!(f, "返回 {}", expr),
+                None => write!(f, "返回"),
+            },
+            Statement::跳出语句 { .. } => write!(f, "跳出"),
+            Statement::继续语句 { .. } => write!(f, "继续"),
+            Statement::错误 { .. } => write!(f, "<语法错误>"),
+            Statement::注释 { 内容, .. } => write!(f, "//{}", 内容),
+            Statement::空行 => Ok(()),
         }
     }
 }
 
+/// 条件中不在括号里的字典字面量会被当成代码块，这时给整个条件加上括号；运算本身已经带着括号
+fn condition(expr: &Expression) -> String {
+    match expr {
+        Expression::二元运算 { .. } | Expression::一元运算 { .. } | Expression::范围 { .. } => expr.to_string(),
+        _ if formatter::has_bare_dictionary(expr) => format!("({})", expr),
+        _ => expr.to_string(),
+    }
+}
+
+fn block(statements: &[Statement]) -> String {
+    indented(statements.iter().map(Statement::to_string))
+}
+
+/// 大括号中的内容各占一行，每层缩进两个空格
+fn indented(items: impl Iterator<Item = String>) -> String {
+    let mut text = "{\n".to_string();
+    for item in items {
+        for line in item.lines() {
+            text.push_str(&format!("  {}\n", line));
+        }
+    }
+    text.push('}');
+    text
+}
+
+fn public_prefix(is_public: bool) -> &'static str {
+    if is_public {
+        "公开 "
+    } else {
+        ""
+    }
+}
+
 impl std::fmt::Display for Program {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let statements: Vec<String> = self.语句.iter().map(|stmt| stmt.to_string()).collect();
         write!(f, "{}", statements.join("\n"))
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,237 @@
+//! 生成 C 代码之前的检查：找出 C 后端不支持的语言功能
+//!
+//! C 后端把具名函数生成为 C 函数，函数不是值：不支持匿名函数和闭包，
+//! 只支持文件最外层的函数声明，也只能直接调用具名函数。
+//! C 后端也不支持结构，只有数组和字典的内置方法可以调用。
+//! 文件最外层的变量生成为 C 全局变量，每个名字只有一个 C 类型，不能用不同的类型标注重新声明。
+//! 这些用法都报告错误 E0301，指向源代码中的位置，而不是让 C 编译器报错。
+
+use std::collections::HashMap;
+
+use crate::ast::{Expression, Program, Statement};
+use crate::diagnostic::{Diagnostic, Span};
+use crate::module::import_name;
+
+/// 名字绑定的是什么
+#[derive(Clone, Copy, PartialEq)]
+enum Binding {
+    /// 文件最外层的函数声明
+    函数,
+    /// 变量、常量、参数和循环变量
+    值,
+    /// 导入的模块，`模块.成员(...)` 调用模块中的函数
+    模块,
+}
+
+/// 数组和字典的内置方法，C 后端只能调用这些方法
+const BUILTIN_METHODS: [&str; 6] = ["长度", "添加", "删除", "键", "值", "包含"];
+
+/// 检查整个程序，返回 C 后端不支持的用法
+pub fn check(program: &Program) -> Vec<Diagnostic> {
+    let mut functions = HashMap::new();
+    for statement in &program.语句 {
+        if let Statement::函数声明 { 名字, .. } = statement {
+            functions.insert(名字.clone(), Binding::函数);
+        }
+    }
+    let mut checker = Checker {
+        scopes: vec![functions],
+        diagnostics: Vec::new(),
+    };
+    let mut annotations = HashMap::new();
+    for statement in &program.语句 {
+        if let Statement::变量声明 { 名字, 类型, 位置, .. } = statement {
+            if *annotations.entry(名字).or_insert(*类型) != *类型 {
+                checker.unsupported(format!("C 后端不支持用不同的类型标注重新声明全局变量 {}", 名字), *位置);
+            }
+        }
+        match statement {
+            Statement::函数声明 { 参数, 体, .. } => checker.check_function(参数, 体),
+            _ => checker.check_statement(statement),
+        }
+    }
+    checker.diagnostics
+}
+
+struct Checker {
+    scopes: Vec<HashMap<String, Binding>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker {
+    fn check_block(&mut self, statements: &[Statement]) {
+        self.scopes.push(HashMap::new());
+        self.check_statements(statements);
+        self.scopes.pop();
+    }
+    
+    fn check_statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.check_statement(statement);
+        }
+    }
+    
+    /// 最外层的函数声明已经在 `check` 中处理，这里遇到的都是嵌套的
+    fn check_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::表达式语句(expr) => self.check_expression(expr),
+            Statement::变量声明 { 名字, 初始值, .. } => {
+                if let Some(expr) = 初始值 {
+                    self.check_expression(expr);
+                }
+                self.declare(名字, Binding::值);
+            }
+            Statement::函数声明 { 名字, 位置, .. } => {
+                self.unsupported(format!("C 后端只支持文件最外层的函数声明，不支持嵌套的函数 {}", 名字), *位置);
+            }
+            Statement::结构声明 { 名字, 位置, .. } => {
+                self.unsupported(format!("C 后端不支持结构，不能声明结构 {}", 名字), *位置);
+            }
+            Statement::如果语句 { 条件, 真分支, 假分支, .. } => {
+                self.check_expression(条件);
+                self.check_block(真分支);
+                self.check_block(假分支);
+            }
+            Statement::循环语句 { 条件, 体, .. } => {
+                if let Some(condition) = 条件 {
+                    self.check_expression(condition);
+                }
+                self.check_block(体);
+            }
+            Statement::当语句 { 条件, 体, .. } => {
+                self.check_expression(条件);
+                self.check_block(体);
+            }
+            Statement::对于语句 { 变量, 值变量, 可迭代, 体, .. } => {
+                self.check_expression(可迭代);
+                self.scopes.push(HashMap::new());
+                self.declare(变量, Binding::值);
+                if let Some(name) = 值变量 {
+                    self.declare(name, Binding::值);
+                }
+                self.check_statements(体);
+                self.scopes.pop();
+            }
+            Statement::尝试语句 { 体, 捕获变量, 捕获, 最终, .. } => {
+                self.check_block(体);
+                if let Some(handler) = 捕获 {
+                    self.scopes.push(HashMap::new());
+                    if let Some((name, _)) = 捕获变量 {
+                        self.declare(name, Binding::值);
+                    }
+                    self.check_statements(handler);
+                    self.scopes.pop();
+                }
+                if let Some(finally) = 最终 {
+                    self.check_block(finally);
+                }
+            }
+            Statement::抛出语句 { 值, .. } => self.check_expression(值),
+            Statement::返回语句 { 值, .. } => {
+                if let Some(expr) = 值 {
+                    self.check_expression(expr);
+                }
+            }
+            Statement::导入语句 { 路径, 别名, .. } => self.declare(&import_name(路径, 别名.as_deref()), Binding::模块),
+            Statement::跳出语句 { .. }
+            | Statement::继续语句 { .. }
+            | Statement::错误 { .. }
+            | Statement::注释 { .. }
+            | Statement::空行 => {}
+        }
+    }
+    
+    fn check_expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::数字字面量(_)
+            | Expression::字符串字面量(_)
+            | Expression::布尔字面量(_)
+            | Expression::自己 { .. } => {}
+            Expression::变量 { 名字, 位置, .. } => {
+                if self.lookup(名字) == Some(Binding::函数) {
+                    self.unsupported(format!("C 后端中函数不是值，{} 只能直接调用", 名字), *位置);
+                }
+            }
+            Expression::二元运算 { 左, 右, .. } => {
+                self.check_expression(左);
+                self.check_expression(右);
+            }
+            Expression::一元运算 { 操作数, .. } => self.check_expression(操作数),
+            Expression::函数调用 { 函数, 参数, 位置 } => {
+                match 函数.as_ref() {
+                    Expression::变量 { 名字, .. } if self.lookup(名字) == Some(Binding::值) => {
+                        self.unsupported(format!("C 后端只能调用具名函数，{} 是一个变量", 名字), *位置);
+                    }
+                    Expression::变量 { .. } => {}
+                    callee => {
+                        self.unsupported("C 后端只能调用具名函数".to_string(), *位置);
+                        self.check_expression(callee);
+                    }
+                }
+                for arg in 参数 {
+                    self.check_expression(arg);
+                }
+            }
+            Expression::匿名函数 { 位置, .. } => self.unsupported("C 后端不支持匿名函数".to_string(), *位置),
+            Expression::赋值 { 值, .. } => self.check_expression(值),
+            Expression::索引赋值 { 数组, 索引, 值, .. } => {
+                self.check_expression(数组);
+                self.check_expression(索引);
+                self.check_expression(值);
+            }
+            Expression::数组字面量(元素) => {
+                for element in 元素 {
+                    self.check_expression(element);
+                }
+            }
+            Expression::数组索引 { 数组, 索引, .. } => {
+                self.check_expression(数组);
+                self.check_expression(索引);
+            }
+            Expression::范围 { 起, 止, .. } => {
+                self.check_expression(起);
+                self.check_expression(止);
+            }
+            Expression::成员访问 { 对象, .. } => self.check_expression(对象),
+            Expression::成员赋值 { 成员, 位置, .. } => {
+                self.unsupported(format!("C 后端不支持结构，不能给成员 {} 赋值", 成员), *位置);
+            }
+            Expression::方法调用 { 对象, 方法, 参数, 位置 } => {
+                let is_module = matches!(对象.as_ref(), Expression::变量 { 名字, .. } if self.lookup(名字) == Some(Binding::模块));
+                if !is_module && !BUILTIN_METHODS.contains(&方法.as_str()) {
+                    self.unsupported(format!("C 后端不支持结构，只能调用数组和字典的内置方法，不能调用 {}", 方法), *位置);
+                }
+                self.check_expression(对象);
+                for arg in 参数 {
+                    self.check_expression(arg);
+                }
+            }
+            Expression::字典字面量 { 条目, .. } => {
+                for (key, value) in 条目 {
+                    self.check_expression(key);
+                    self.check_expression(value);
+                }
+            }
+        }
+    }
+    
+    /// 参数和函数体共用一个作用域
+    fn check_function(&mut self, parameters: &[String], body: &[Statement]) {
+        self.scopes.push(parameters.iter().map(|param| (param.clone(), Binding::值)).collect());
+        self.check_statements(body);
+        self.scopes.pop();
+    }
+    
+    fn declare(&mut self, name: &str, binding: Binding) {
+        self.scopes.last_mut().expect("至少有全局作用域").insert(name.to_string(), binding);
+    }
+    
+    /// 找不到的名字是内置函数或导入的模块
+    fn lookup(&self, name: &str) -> Option<Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name).copied())
+    }
+    
+    fn unsupported(&mut self, message: String, span: Span) {
+        self.diagnostics.push(Diagnostic::error("E0301", message).with_span(span));
+    }
+}
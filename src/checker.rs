@@ -0,0 +1,247 @@
+//! 执行前的静态检查
+//!
+//! 目前检查常量：给常量赋值、在同一作用域中重复声明常量；
+//! 以及只能出现在文件最外层的 `导入` 和 `公开`。
+//! 作用域的划分与解释器一致：每个函数和每个 `{}` 代码块各有一个作用域，
+//! 报告的错误代码也与运行时相同。
+
+use std::collections::HashMap;
+
+use crate::ast::{Expression, Program, Statement};
+use crate::diagnostic::{Diagnostic, Span};
+use crate::module::import_name;
+
+struct Declaration {
+    constant: bool,
+    span: Span,
+}
+
+/// 检查整个程序，返回发现的错误和警告
+pub fn check(program: &Program) -> Vec<Diagnostic> {
+    let mut checker = Checker {
+        scopes: vec![HashMap::new()],
+        diagnostics: Vec::new(),
+    };
+    checker.check_statements(&program.语句);
+    checker.diagnostics
+}
+
+struct Checker {
+    scopes: Vec<HashMap<String, Declaration>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker {
+    /// 在新的块作用域中检查一组语句
+    fn check_block(&mut self, statements: &[Statement]) {
+        self.scopes.push(HashMap::new());
+        self.check_statements(statements);
+        self.scopes.pop();
+    }
+    
+    fn check_statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.check_statement(statement);
+        }
+    }
+    
+    fn check_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::表达式语句(expr) => self.check_expression(expr),
+            Statement::变量声明 { 名字, 初始值, 是常量, 是公开, 位置, .. } => {
+                self.check_public(*是公开, 名字, *位置);
+                match 初始值 {
+                    Some(expr) => self.check_expression(expr),
+                    None if *是常量 => {
+                        self.diagnostics.push(
+                            Diagnostic::warning("W0101", format!("常量 {} 没有初始值，它的值将永远是 空", 名字))
+                                .with_span(*位置),
+                        );
+                    }
+                    None => {}
+                }
+                self.declare(名字, *是常量, *位置);
+            }
+            Statement::函数声明 { 名字, 参数, 体, 是公开, 位置, .. } => {
+                self.check_public(*是公开, 名字, *位置);
+                self.declare(名字, false, *位置);
+                self.check_function(参数, 体, *位置);
+            }
+            Statement::结构声明 { 名字, 方法, 是公开, 位置, .. } => {
+                self.check_public(*是公开, 名字, *位置);
+                self.declare(名字, false, *位置);
+                for method in 方法 {
+                    if let Statement::函数声明 { 参数, 体, 位置, .. } = method {
+                        self.check_function(参数, 体, *位置);
+                    }
+                }
+            }
+            Statement::如果语句 { 条件, 真分支, 假分支, .. } => {
+                self.check_expression(条件);
+                self.check_block(真分支);
+                self.check_block(假分支);
+            }
+            Statement::循环语句 { 条件, 体, .. } => {
+                if let Some(condition) = 条件 {
+                    self.check_expression(condition);
+                }
+                self.check_block(体);
+            }
+            Statement::当语句 { 条件, 体, .. } => {
+                self.check_expression(条件);
+                self.check_block(体);
+            }
+            Statement::对于语句 { 变量, 值变量, 可迭代, 体, 位置 } => {
+                self.check_expression(可迭代);
+                self.scopes.push(HashMap::new());
+                self.declare(变量, false, *位置);
+                if let Some(name) = 值变量 {
+                    self.declare(name, false, *位置);
+                }
+                self.check_statements(体);
+                self.scopes.pop();
+            }
+            Statement::尝试语句 { 体, 捕获变量, 捕获, 最终, .. } => {
+                self.check_block(体);
+                if let Some(handler) = 捕获 {
+                    self.scopes.push(HashMap::new());
+                    if let Some((name, span)) = 捕获变量 {
+                        self.declare(name, false, *span);
+                    }
+                    self.check_statements(handler);
+                    self.scopes.pop();
+                }
+                if let Some(finally) = 最终 {
+                    self.check_block(finally);
+                }
+            }
+            Statement::抛出语句 { 值, .. } => self.check_expression(值),
+            // 导入的模块和常量一样不能重新赋值
+            Statement::导入语句 { 路径, 别名, 位置 } => {
+                if !self.at_top_level() {
+                    self.diagnostics.push(Diagnostic::error("E0216", "导入 只能出现在文件的最外层").with_span(*位置));
+                }
+                self.declare(&import_name(路径, 别名.as_deref()), true, *位置);
+            }
+            Statement::返回语句 { 值, .. } => {
+                if let Some(expr) = 值 {
+                    self.check_expression(expr);
+                }
+            }
+            Statement::跳出语句 { .. }
+            | Statement::继续语句 { .. }
+            | Statement::错误 { .. }
+            | Statement::注释 { .. }
+            | Statement::空行 => {}
+        }
+    }
+    
+    fn check_expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::数字字面量(_)
+            | Expression::字符串字面量(_)
+            | Expression::布尔字面量(_)
+            | Expression::变量 { .. }
+            | Expression::自己 { .. } => {}
+            Expression::二元运算 { 左, 右, .. } => {
+                self.check_expression(左);
+                self.check_expression(右);
+            }
+            Expression::一元运算 { 操作数, .. } => self.check_expression(操作数),
+            Expression::函数调用 { 函数, 参数, .. } => {
+                self.check_expression(函数);
+                for arg in 参数 {
+                    self.check_expression(arg);
+                }
+            }
+            Expression::匿名函数 { 参数, 体, 位置, .. } => self.check_function(参数, 体, *位置),
+            Expression::赋值 { 变量名, 值, 位置, .. } => {
+                self.check_expression(值);
+                if let Some(declaration) = self.lookup(变量名) {
+                    if declaration.constant {
+                        let error = Diagnostic::error("E0210", format!("不能给常量 {} 赋值", 变量名))
+                            .with_span(*位置)
+                            .with_label(declaration.span, "常量在这里声明");
+                        self.diagnostics.push(error);
+                    }
+                }
+            }
+            Expression::索引赋值 { 数组, 索引, 值, .. } => {
+                self.check_expression(数组);
+                self.check_expression(索引);
+                self.check_expression(值);
+            }
+            Expression::数组字面量(元素) => {
+                for element in 元素 {
+                    self.check_expression(element);
+                }
+            }
+            Expression::数组索引 { 数组, 索引, .. } => {
+                self.check_expression(数组);
+                self.check_expression(索引);
+            }
+            Expression::范围 { 起, 止, .. } => {
+                self.check_expression(起);
+                self.check_expression(止);
+            }
+            Expression::成员访问 { 对象, .. } => self.check_expression(对象),
+            Expression::成员赋值 { 对象, 值, .. } => {
+                self.check_expression(对象);
+                self.check_expression(值);
+            }
+            Expression::方法调用 { 对象, 参数, .. } => {
+                self.check_expression(对象);
+                for arg in 参数 {
+                    self.check_expression(arg);
+                }
+            }
+            Expression::字典字面量 { 条目, .. } => {
+                for (key, value) in 条目 {
+                    self.check_expression(key);
+                    self.check_expression(value);
+                }
+            }
+        }
+    }
+    
+    fn at_top_level(&self) -> bool {
+        self.scopes.len() == 1
+    }
+    
+    fn check_public(&mut self, is_public: bool, name: &str, span: Span) {
+        if is_public && !self.at_top_level() {
+            let error = Diagnostic::error("E0216", format!("公开 只能用于文件最外层的声明，{} 不在最外层", name))
+                .with_span(span);
+            self.diagnostics.push(error);
+        }
+    }
+    
+    /// 参数和函数体共用一个作用域
+    fn check_function(&mut self, parameters: &[String], body: &[Statement], span: Span) {
+        let mut scope = HashMap::new();
+        for param in parameters {
+            scope.insert(param.clone(), Declaration { constant: false, span });
+        }
+        self.scopes.push(scope);
+        self.check_statements(body);
+        self.scopes.pop();
+    }
+    
+    fn declare(&mut self, name: &str, constant: bool, span: Span) {
+        let scope = self.scopes.last_mut().expect("至少有全局作用域");
+        if let Some(existing) = scope.get(name) {
+            if existing.constant {
+                let error = Diagnostic::error("E0211", format!("常量 {} 不能重复声明", name))
+                    .with_span(span)
+                    .with_label(existing.span, "常量在这里声明");
+                self.diagnostics.push(error);
+                return;
+            }
+        }
+        scope.insert(name.to_string(), Declaration { constant, span });
+    }
+    
+    fn lookup(&self, name: &str) -> Option<&Declaration> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+}
@@ -0,0 +1,137 @@
+//! 命令行参数
+//!
+//! 不带参数时进入交互模式，`cnlang 文件.cn` 与 `cnlang run 文件.cn` 相同。
+//! 以前的 `cnlang --repl` 仍然可以使用，它不显示在帮助中。
+
+use std::env;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use crate::code_generator::OutputType;
+
+#[derive(Parser)]
+#[command(
+    name = "cnlang",
+    version,
+    about = "中文编程语言 / A programming language with Chinese keywords",
+    args_conflicts_with_subcommands = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// 要运行的源文件，等同于 run 子命令 / Source file to run, same as the run subcommand
+    pub file: Option<String>,
+    /// 旧的 `cnlang --repl` 写法，与 repl 子命令相同
+    #[arg(long, hide = true, conflicts_with = "file")]
+    pub repl: bool,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// 用解释器运行程序 / Run a program with the interpreter
+    #[command(visible_alias = "运行")]
+    Run {
+        /// 源文件 / Source file
+        file: String,
+        /// 文件是 `ast --json` 输出的语法树 / The file is a syntax tree written by `ast --json`
+        #[arg(long)]
+        json: bool,
+    },
+    /// 用 C 后端编译程序 / Compile a program through the C backend
+    #[command(visible_alias = "构建")]
+    Build(BuildArgs),
+    /// 只做语法分析和静态检查 / Parse and run the static checks only
+    #[command(visible_alias = "检查")]
+    Check {
+        /// 源文件 / Source file
+        file: String,
+    },
+    /// 格式化源文件 / Format source files
+    #[command(visible_alias = "格式化")]
+    Fmt {
+        /// 源文件 / Source files
+        #[arg(required = true)]
+        files: Vec<String>,
+        /// 只检查不修改，有文件需要格式化时以非零退出码结束 / Check only; fail if any file needs formatting
+        #[arg(long)]
+        check: bool,
+    },
+    /// 输出语法树 / Print the syntax tree
+    #[command(visible_alias = "语法树")]
+    Ast {
+        /// 源文件 / Source file
+        file: String,
+        /// 以 JSON 格式输出，带位置信息 / Print as JSON, including source positions
+        #[arg(long)]
+        json: bool,
+    },
+    /// 输出词法分析得到的记号 / Print the tokens produced by the lexer
+    #[command(visible_alias = "词法")]
+    Tokens {
+        /// 源文件 / Source file
+        file: String,
+        /// 以 JSON 格式输出 / Print as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// 进入交互模式 / Start the interactive mode
+    #[command(visible_alias = "交互")]
+    Repl,
+    /// 启动语言服务器，通过标准输入输出通信 / Start the language server on stdio
+    #[command(visible_alias = "语言服务")]
+    Lsp,
+}
+
+#[derive(Args)]
+pub struct BuildArgs {
+    /// 源文件 / Source file
+    pub file: String,
+    /// 输出类型 / Output kind
+    #[arg(long, value_enum, default_value_t = Target::Exe)]
+    pub target: Target,
+    /// 输出文件名，默认与源文件同名 / Output file, named after the source file by default
+    #[arg(short, long)]
+    pub output: Option<String>,
+    /// C 编译器，默认使用 CC 环境变量或 PATH 中的 gcc、clang、tcc / C compiler, defaults to CC or gcc, clang, tcc in PATH
+    #[arg(long)]
+    pub cc: Option<String>,
+    /// C 编译器的优化级别 / Optimization level passed to the C compiler
+    #[arg(long, default_value = "2")]
+    pub opt_level: String,
+    /// 生成调试信息 / Emit debug information
+    #[arg(short = 'g', long)]
+    pub debug: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Target {
+    /// 可执行文件 / Executable
+    Exe,
+    /// 动态库 / Shared library
+    So,
+    /// 目标文件 / Object file
+    Obj,
+    /// C 源代码 / C source
+    C,
+}
+
+impl Target {
+    pub fn output_type(self) -> OutputType {
+        match self {
+            Target::Exe => OutputType::Exe,
+            Target::So => OutputType::Dll,
+            Target::Obj => OutputType::Object,
+            Target::C => OutputType::CSource,
+        }
+    }
+    
+    /// 默认输出文件名的后缀，随平台而定
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Target::Exe => env::consts::EXE_SUFFIX,
+            Target::So => env::consts::DLL_SUFFIX,
+            Target::Obj => ".o",
+            Target::C => ".c",
+        }
+    }
+}
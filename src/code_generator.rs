@@ -1,11 +1,76 @@
-use crate::ast::{Program, Statement, Expression};
+use crate::ast::{Program, Statement, Expression, Type};
+use crate::module::{import_name, Module, ModuleLoader};
+use crate::toolchain::Toolchain;
+use crate::types::{always_exits, mismatch};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::process::Command;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
 pub struct CodeGenerator {
     output_type: OutputType,
+    /// 编译 C 代码用的编译器
+    toolchain: Option<Toolchain>,
+    /// 入口文件导入的所有模块，和入口文件一起生成到同一个 C 文件中
+    modules: ModuleLoader,
+    /// 入口文件所在的目录
+    directory: PathBuf,
+    /// 正在生成的文件中名字的映射
+    scope: RefCell<ModuleScope>,
+    /// 正在生成的语句所在的函数、循环和尝试语句，由内到外排在末尾
+    frames: RefCell<Vec<Frame>>,
+    /// 为每个尝试语句生成不重复的变量名
+    next_try_id: Cell<usize>,
+    /// 正在生成的函数或 main 中可见的局部变量和它们的类型标注，由外到内每个代码块一层
+    locals: RefCell<Vec<HashMap<String, Option<Type>>>>,
+    /// 正在生成的函数或 main 中有尝试语句：局部变量和参数声明为 volatile，
+    /// 否则开启优化后，longjmp 回到跳转点时读不到在尝试代码块中修改的值
+    volatile_locals: Cell<bool>,
+    /// 模块全局变量的类型标注，按 C 中的名字
+    global_types: RefCell<HashMap<String, Option<Type>>>,
+    /// 已经在声明中初始化的 const 全局变量，按 C 中的名字
+    constant_globals: RefCell<HashSet<String>>,
+    /// 函数的参数和返回类型，按 C 中的名字
+    signatures: RefCell<HashMap<String, Signature>>,
 }
 
+/// 函数的参数名、参数类型和返回类型，调用时按它们转换参数和返回值
+struct Signature {
+    parameters: Vec<(String, Option<Type>)>,
+    returns: Option<Type>,
+}
+
+/// 跳转语句离开尝试语句时，需要弹出错误处理器并执行最终代码块
+enum Frame {
+    /// 带函数的返回类型，`返回` 按它转换返回值
+    函数(Option<Type>),
+    循环,
+    尝试 {
+        /// 这一层在 try_stack 中压入的处理器数量
+        handlers: usize,
+        /// 已经生成的最终代码块
+        finally: Option<String>,
+    },
+}
+
+/// 生成一个文件时名字的映射
+///
+/// 模块最外层的变量和函数生成为带前缀的 C 全局变量和函数，以免不同模块中的同名声明冲突；
+/// 入口文件最外层的变量也是全局变量，函数中可以使用，前缀是 `ENTRY_PREFIX`，函数保留原来的名字。
+#[derive(Default)]
+struct ModuleScope {
+    prefix: String,
+    /// 需要加前缀的名字
+    globals: HashSet<String>,
+    /// 导入绑定的名字对应的模块
+    imports: HashMap<String, Rc<Module>>,
+}
+
+/// 入口文件全局变量的前缀，避免与运行时和 C 标准库中的名字冲突；
+/// 模块的前缀带有加载顺序，不会与它相同
+const ENTRY_PREFIX: &str = "global_";
+
 #[derive(Debug, Clone)]
 pub enum OutputType {
     Exe,
@@ -18,102 +83,57 @@ impl CodeGenerator {
     pub fn new(output_type: OutputType) -> Self {
         CodeGenerator {
             output_type,
+            toolchain: None,
+            modules: ModuleLoader::default(),
+            directory: PathBuf::from("."),
+            scope: RefCell::new(ModuleScope::default()),
+            frames: RefCell::new(Vec::new()),
+            next_try_id: Cell::new(0),
+            locals: RefCell::new(Vec::new()),
+            volatile_locals: Cell::new(false),
+            global_types: RefCell::new(HashMap::new()),
+            constant_globals: RefCell::new(HashSet::new()),
+            signatures: RefCell::new(HashMap::new()),
         }
     }
     
-    pub fn generate(&self, program: &Program, output_path: &str) -> Result<(), String> {
+    /// 使用已经加载好的模块，`directory` 是入口文件所在的目录
+    pub fn with_modules(mut self, modules: ModuleLoader, directory: PathBuf) -> Self {
+        self.modules = modules;
+        self.directory = directory;
+        self
+    }
+    
+    pub fn with_toolchain(mut self, toolchain: Toolchain) -> Self {
+        self.toolchain = Some(toolchain);
+        self
+    }
+    
+    /// 生成 C 源代码，或者再用 C 编译器编译；没有用 `with_toolchain` 指定编译器时自动查找
+    pub fn generate(&self, program: &Program, output_path: &Path) -> Result<(), String> {
+        let c_code = self.generate_c_code(program)?;
         match self.output_type {
-            OutputType::CSource => {
-                let c_code = self.generate_c_code(program);
-                fs::write(output_path, c_code).map_err(|e| format!("写入文件失败: {}", e))?;
-                Ok(())
-            }
-            OutputType::Exe => {
-                let c_code = self.generate_c_code(program);
-                let c_file = "temp_output.c";
-                println!("C代码长度: {}", c_code.len());
-                fs::write(c_file, c_code).map_err(|e| format!("写入临时文件失败: {}", e))?;
-                println!("临时文件写入完成");
-                
-                // 使用mingw64中的gcc编译C代码为exe
-                let gcc_path = "c:\\users\\administrator\\desktop\\rust中文版\\mingw64\\bin\\gcc.exe";
-                println!("编译命令: {} -o {} {}", gcc_path, output_path, c_file);
-                let output = Command::new(gcc_path)
-                    .args(&["-o", output_path, c_file])
-                    .output()
-                    .map_err(|e| format!("编译失败: {}", e))?;
-                println!("编译退出码: {}", output.status.code().unwrap_or(-1));
-                    
-                if !output.status.success() {
-                    let stderr = String::from_utf8_lossy(&output.stderr);
-                    let stdout = String::from_utf8_lossy(&output.stdout);
-                    println!("STDERR: {}", stderr);
-                    println!("STDOUT: {}", stdout);
-                    let error = if stderr.is_empty() { stdout } else { stderr };
-                    return Err(format!("编译错误: {}", error));
-                }
-                
-                // 清理临时文件
-                let _ = fs::remove_file(c_file);
-                Ok(())
-            }
-            OutputType::Dll => {
-                let c_code = self.generate_c_code(program);
-                let c_file = "temp_output.c";
-                fs::write(c_file, c_code).map_err(|e| format!("写入临时文件失败: {}", e))?;
-                
-                // 使用mingw64中的gcc编译C代码为dll
-                let gcc_path = "c:\\users\\administrator\\desktop\\rust中文版\\mingw64\\bin\\gcc.exe";
-                let output = Command::new(gcc_path)
-                    .args(&["-shared", "-o", output_path, c_file])
-                    .output()
-                    .map_err(|e| format!("编译失败: {}", e))?;
-                    
-                if !output.status.success() {
-                    let stderr = String::from_utf8_lossy(&output.stderr);
-                    let stdout = String::from_utf8_lossy(&output.stdout);
-                    let error = if stderr.is_empty() { stdout } else { stderr };
-                    return Err(format!("编译错误: {}", error));
-                }
-                
-                // 清理临时文件
-                let _ = fs::remove_file(c_file);
-                Ok(())
-            }
-            OutputType::Object => {
-                let c_code = self.generate_c_code(program);
-                let c_file = "temp_output.c";
-                fs::write(c_file, c_code).map_err(|e| format!("写入临时文件失败: {}", e))?;
-                
-                // 使用mingw64中的gcc编译C代码为目标文件
-                let gcc_path = "c:\\users\\administrator\\desktop\\rust中文版\\mingw64\\bin\\gcc.exe";
-                let output = Command::new(gcc_path)
-                    .args(&["-c", "-o", output_path, c_file])
-                    .output()
-                    .map_err(|e| format!("编译失败: {}", e))?;
-                    
-                if !output.status.success() {
-                    let stderr = String::from_utf8_lossy(&output.stderr);
-                    let stdout = String::from_utf8_lossy(&output.stdout);
-                    let error = if stderr.is_empty() { stdout } else { stderr };
-                    return Err(format!("编译错误: {}", error));
-                }
-                
-                // 清理临时文件
-                let _ = fs::remove_file(c_file);
-                Ok(())
+            OutputType::CSource => fs::write(output_path, c_code).map_err(|e| format!("写入文件失败: {}", e)),
+            _ => {
+                let toolchain = match &self.toolchain {
+                    Some(toolchain) => toolchain.clone(),
+                    None => Toolchain::find(None)?,
+                };
+                toolchain.compile(&c_code, &self.output_type, output_path)
             }
         }
     }
     
-    fn generate_c_code(&self, program: &Program) -> String {
+    fn generate_c_code(&self, program: &Program) -> Result<String, String> {
         let mut c_code = String::new();
         
         // 添加C头文件
         c_code.push_str("#include <stdio.h>\n");
         c_code.push_str("#include <stdlib.h>\n");
         c_code.push_str("#include <string.h>\n");
-        c_code.push_str("#include <stdbool.h>\n\n");
+        c_code.push_str("#include <stdbool.h>\n");
+        c_code.push_str("#include <setjmp.h>\n");
+        c_code.push_str("#include <math.h>\n\n");
         
         // 添加值类型的定义
         c_code.push_str("typedef enum {\n");
@@ -121,48 +141,132 @@ impl CodeGenerator {
         c_code.push_str("    VALUE_STRING,\n");
         c_code.push_str("    VALUE_BOOLEAN,\n");
         c_code.push_str("    VALUE_ARRAY,\n");
+        c_code.push_str("    VALUE_MAP,\n");
+        c_code.push_str("    VALUE_ERROR,\n");
         c_code.push_str("    VALUE_NULL\n");
         c_code.push_str("} ValueType;\n\n");
         
         // 添加前向声明
         c_code.push_str("// 前向声明\n");
-        c_code.push_str("typedef struct Value Value;\n\n");
+        c_code.push_str("typedef struct Value Value;\n");
+        c_code.push_str("typedef struct Array Array;\n");
+        c_code.push_str("typedef struct Map Map;\n");
+        c_code.push_str("typedef struct ErrorObject ErrorObject;\n\n");
         
+        // 数组和字典与解释器一样按引用共享，Value 中只保存指针
         c_code.push_str("struct Value {\n");
         c_code.push_str("    ValueType type;\n");
         c_code.push_str("    union {\n");
         c_code.push_str("        double number;\n");
         c_code.push_str("        char* string;\n");
         c_code.push_str("        bool boolean;\n");
-        c_code.push_str("        struct {\n");
-        c_code.push_str("            struct Value* values;\n");
-        c_code.push_str("            int count;\n");
-        c_code.push_str("            int capacity;\n");
-        c_code.push_str("        } array;\n");
+        c_code.push_str("        Array* array;\n");
+        c_code.push_str("        Map* map;\n");
+        c_code.push_str("        ErrorObject* error;\n");
         c_code.push_str("    } as;\n");
         c_code.push_str("};\n\n");
         
+        c_code.push_str("struct Array {\n");
+        c_code.push_str("    Value* values;\n");
+        c_code.push_str("    int count;\n");
+        c_code.push_str("    int capacity;\n");
+        c_code.push_str("};\n\n");
+        
+        // 捕获到的错误，字段与解释器中的错误值相同；运行时函数产生的错误没有位置，行列为 0
+        c_code.push_str("struct ErrorObject {\n");
+        c_code.push_str("    const char* kind;\n");
+        c_code.push_str("    char* message;\n");
+        c_code.push_str("    int line;\n");
+        c_code.push_str("    int column;\n");
+        c_code.push_str("    Value value;\n");
+        c_code.push_str("};\n\n");
+        
+        // 每个尝试语句在 try_stack 中压入一个跳转点，抛出时跳回最近的一个
+        c_code.push_str("#define MAX_TRY_DEPTH 256\n");
+        c_code.push_str("jmp_buf try_stack[MAX_TRY_DEPTH];\n");
+        c_code.push_str("int try_depth = 0;\n");
+        c_code.push_str("Value current_error;\n\n");
+        
+        // 字典按插入顺序保存键值对，查找是线性的
+        c_code.push_str("struct Map {\n");
+        c_code.push_str("    char** keys;\n");
+        c_code.push_str("    Value* values;\n");
+        c_code.push_str("    int count;\n");
+        c_code.push_str("    int capacity;\n");
+        c_code.push_str("};\n\n");
+        
         // 添加内置函数声明
         c_code.push_str("void builtin_print(Value value);\n");
+        c_code.push_str("char* display_text(Value value);\n");
+        c_code.push_str("char* value_to_text(Value value);\n");
         c_code.push_str("Value builtin_input();\n");
         c_code.push_str("Value create_array();\n");
-        c_code.push_str("void array_push(Value* array, Value value);\n");
+        c_code.push_str("Value array_push(Value array, Value value);\n");
         c_code.push_str("Value array_get(Value array, int index);\n");
+        c_code.push_str("Value array_set(Value array, int index, Value value);\n");
         c_code.push_str("int array_length(Value array);\n");
-        c_code.push_str("void array_remove(Value* array, int index);\n");
+        c_code.push_str("Value array_remove(Value array, int index);\n");
+        c_code.push_str("Value create_map();\n");
+        c_code.push_str("Value map_get(Value map, Value key);\n");
+        c_code.push_str("Value map_set(Value map, Value key, Value value);\n");
+        c_code.push_str("bool map_has(Value map, Value key);\n");
+        c_code.push_str("Value map_remove(Value map, Value key);\n");
+        c_code.push_str("Value map_keys(Value map);\n");
+        c_code.push_str("Value map_values(Value map);\n");
+        c_code.push_str("Value index_get(Value container, Value key);\n");
+        c_code.push_str("Value index_set(Value container, Value key, Value value);\n");
+        c_code.push_str("int value_length(Value container);\n");
+        c_code.push_str("Value remove_item(Value container, Value key);\n");
+        c_code.push_str("Value expect_method(Value object, const char* method);\n");
+        c_code.push_str("Value make_range(Value start, Value end, Value step);\n");
+        c_code.push_str("Value iteration_items(Value iterable);\n");
+        c_code.push_str("Value iteration_keys(Value iterable);\n");
+        c_code.push_str("Value iteration_values(Value iterable);\n");
+        c_code.push_str("void raise_error(Value error);\n");
+        c_code.push_str("void throw_value(Value value, int line, int column);\n");
+        c_code.push_str("void runtime_error(const char* kind, const char* message);\n");
+        c_code.push_str("Value member_get(Value object, const char* name);\n");
         
         // 添加辅助函数声明
         c_code.push_str("bool is_truthy(Value value);\n");
         c_code.push_str("int compare_values(Value a, Value b);\n");
+        c_code.push_str("bool values_equal(Value a, Value b);\n");
         c_code.push_str("Value add_values(Value a, Value b);\n");
         c_code.push_str("Value subtract_values(Value a, Value b);\n");
         c_code.push_str("Value multiply_values(Value a, Value b);\n");
         c_code.push_str("Value divide_values(Value a, Value b);\n");
+        c_code.push_str("double divide_numbers(double a, double b);\n");
+        c_code.push_str("Value expect_type(Value value, ValueType type, const char* expected, const char* what);\n");
         c_code.push_str("Value negate_value(Value value);\n\n");
         
         // 添加内置函数实现
         c_code.push_str(&self.generate_builtin_functions());
         
+        // 导入的模块排在入口文件之前，被导入的模块又排在导入它的模块之前
+        let mut module_inits = String::new();
+        for module in self.modules.modules() {
+            c_code.push_str(&self.generate_module(module).map_err(|e| format!("{}: {}", module.source.name, e))?);
+            module_inits.push_str(&format!("    {}init();\n", self.prefix_of(module)));
+        }
+        let globals = program
+            .语句
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::变量声明 { 名字, 类型, .. } => {
+                    self.global_types.borrow_mut().entry(format!("{}{}", ENTRY_PREFIX, 名字)).or_insert(*类型);
+                    Some(名字.clone())
+                }
+                _ => None,
+            })
+            .collect();
+        *self.scope.borrow_mut() = ModuleScope {
+            prefix: ENTRY_PREFIX.to_string(),
+            globals,
+            imports: self.imports_of(program, &self.directory),
+        };
+        c_code.push_str(&self.global_declarations(&program.语句, ENTRY_PREFIX));
+        c_code.push('\n');
+        
         // 分离函数声明和其他语句
         let mut function_declarations = Vec::new();
         let mut other_statements = Vec::new();
@@ -178,39 +282,28 @@ impl CodeGenerator {
             }
         }
         
+        // 先记下所有函数的类型，函数体中可以调用后面的函数
+        for func_decl in &function_declarations {
+            if let Statement::函数声明 { 名字, 参数, 参数类型, 返回类型, .. } = func_decl {
+                self.declare_function(名字, 参数, 参数类型, *返回类型);
+            }
+        }
+        
         // 添加函数定义到main函数外部
         for func_decl in &function_declarations {
-            if let Statement::函数声明 { 名字, 参数, 体 } = func_decl {
-                let args: Vec<String> = 参数.iter().enumerate().map(|(i, _)| format!("Value arg{}", i)).collect();
-                let args_str = args.join(", ");
-                
-                // 生成函数体，需要将参数名映射到arg0, arg1等
-                let mut body_code = String::new();
-                for stmt in 体 {
-                    let stmt_code = self.generate_statement_c_code(stmt);
-                    // 替换参数名
-                    let mut replaced_code = stmt_code;
-                    for (i, param_name) in 参数.iter().enumerate() {
-                        replaced_code = replaced_code.replace(param_name, &format!("arg{}", i));
-                    }
-                    body_code.push_str(&replaced_code);
-                    body_code.push_str("\n");
-                }
-                
-                // 生成完整的函数定义
-                c_code.push_str(&format!("Value {}({}) {{\n", 名字, args_str));
-                for line in body_code.lines() {
-                    c_code.push_str(&format!("    {}\n", line));
-                }
-                c_code.push_str("}\n\n");
+            if let Statement::函数声明 { 名字, 体, .. } = func_decl {
+                c_code.push_str(&self.generate_function(名字, 体)?);
             }
         }
         
         // 重新定义main函数，包含其他语句
+        *self.locals.borrow_mut() = vec![HashMap::new()];
+        self.volatile_locals.set(contains_try(other_statements.iter().copied()));
         c_code.push_str("int main() {\n");
         c_code.push_str("    // Program entry point\n");
+        c_code.push_str(&module_inits);
         for statement in &other_statements {
-            let stmt_code = self.generate_statement_c_code(statement);
+            let stmt_code = self.generate_statement_c_code(statement)?;
             // 为每行代码添加缩进
             for line in stmt_code.lines() {
                 c_code.push_str(&format!("    {}\n", line));
@@ -219,147 +312,513 @@ impl CodeGenerator {
         c_code.push_str("    return 0;\n");
         c_code.push_str("}\n");
         
-        c_code
+        Ok(c_code)
+    }
+    
+    /// 记下函数的参数和返回类型，`c_name` 是它在 C 中的名字
+    fn declare_function(&self, c_name: &str, parameters: &[String], types: &[Option<Type>], returns: Option<Type>) {
+        let parameters = parameters
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.clone(), types.get(i).copied().flatten()))
+            .collect();
+        self.signatures.borrow_mut().insert(c_name.to_string(), Signature { parameters, returns });
+    }
+    
+    /// 函数在 C 中的返回类型和参数列表，例如 `double 加(double a, Value b)`
+    fn function_head(&self, c_name: &str) -> String {
+        let signatures = self.signatures.borrow();
+        let signature = &signatures[c_name];
+        let args: Vec<String> = signature
+            .parameters
+            .iter()
+            .map(|(name, ty)| self.local_declaration(*ty, name))
+            .collect();
+        format!("{} {}({})", c_type(signature.returns), c_name, args.join(", "))
+    }
+    
+    /// 局部变量或参数的 C 声明，例如 `double x`；函数中有尝试语句时是 `double volatile x`
+    fn local_declaration(&self, ty: Option<Type>, name: &str) -> String {
+        let qualifier = if self.volatile_locals.get() { " volatile" } else { "" };
+        format!("{}{} {}", c_type(ty), qualifier, name)
+    }
+    
+    /// 生成一个 C 函数，`c_name` 是它在 C 中的名字，类型已经用 `declare_function` 记下
+    fn generate_function(&self, c_name: &str, body: &[Statement]) -> Result<String, String> {
+        let outer_volatile = self.volatile_locals.replace(contains_try(body));
+        let (head, returns, scope) = {
+            let signatures = self.signatures.borrow();
+            let signature = &signatures[c_name];
+            (self.function_head(c_name), signature.returns, signature.parameters.iter().cloned().collect())
+        };
+        
+        // 参数与局部变量一样直接用原来的名字；函数中看不到 main 的局部变量
+        let outer = self.locals.replace(vec![scope]);
+        let body_code = self.in_frame(Frame::函数(returns), || self.generate_block(body));
+        *self.locals.borrow_mut() = outer;
+        self.volatile_locals.set(outer_volatile);
+        let mut body_code = body_code?;
+        
+        // 执行到结尾时返回 空，返回类型不是 空 时是错误
+        if !always_exits(body) {
+            match returns {
+                Some(ty) if ty != Type::空 => {
+                    let message = mismatch("返回值", ty, Type::空.name()).message;
+                    body_code.push_str(&format!("runtime_error(\"E0217\", {});\n", c_string_literal(&message)));
+                    body_code.push_str(&format!("return {};\n", default_value(returns)));
+                }
+                _ => body_code.push_str("return (Value){VALUE_NULL};\n"),
+            }
+        }
+        
+        // 生成完整的函数定义
+        let mut code = format!("{} {{\n", head);
+        for line in body_code.lines() {
+            code.push_str(&format!("    {}\n", line));
+        }
+        code.push_str("}\n\n");
+        Ok(code)
     }
     
-    fn generate_statement_c_code(&self, statement: &Statement) -> String {
-        match statement {
+    /// 生成一个导入的模块：全局变量、函数，以及执行模块最外层语句的初始化函数
+    fn generate_module(&self, module: &Module) -> Result<String, String> {
+        let prefix = self.prefix_of(module);
+        let statements = &module.program.语句;
+        let globals = statements
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::变量声明 { 名字, .. } | Statement::函数声明 { 名字, .. } => Some(名字.clone()),
+                _ => None,
+            })
+            .collect();
+        for statement in statements {
+            match statement {
+                Statement::变量声明 { 名字, 类型, .. } => {
+                    self.global_types.borrow_mut().entry(format!("{}{}", prefix, 名字)).or_insert(*类型);
+                }
+                Statement::函数声明 { 名字, 参数, 参数类型, 返回类型, .. } => {
+                    self.declare_function(&format!("{}{}", prefix, 名字), 参数, 参数类型, *返回类型);
+                }
+                _ => {}
+            }
+        }
+        *self.scope.borrow_mut() = ModuleScope {
+            prefix: prefix.clone(),
+            globals,
+            imports: self.imports_of(&module.program, module.directory()),
+        };
+        
+        let mut code = format!("// 模块 {}\n", module.source.name);
+        code.push_str(&self.global_declarations(statements, &prefix));
+        code.push('\n');
+        for statement in statements {
+            if let Statement::函数声明 { 名字, 体, .. } = statement {
+                code.push_str(&self.generate_function(&format!("{}{}", prefix, 名字), 体)?);
+            }
+        }
+        
+        *self.locals.borrow_mut() = vec![HashMap::new()];
+        self.volatile_locals.set(contains_try(statements));
+        code.push_str(&format!("void {}init(void) {{\n", prefix));
+        for statement in statements {
+            if !matches!(statement, Statement::函数声明 { .. }) {
+                for line in self.generate_statement_c_code(statement)?.lines() {
+                    code.push_str(&format!("    {}\n", line));
+                }
+            }
+        }
+        code.push_str("}\n\n");
+        Ok(code)
+    }
+    
+    /// 文件最外层的变量对应的 C 全局变量，同名的变量只声明一次；c_support 已经拒绝了类型标注不同的重新声明。
+    /// 只声明一次、初始值是字面量的标量常量声明为 const，直接在声明中初始化
+    fn global_declarations(&self, statements: &[Statement], prefix: &str) -> String {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for statement in statements {
+            if let Statement::变量声明 { 名字, .. } = statement {
+                *counts.entry(名字).or_default() += 1;
+            }
+        }
+        let mut code = String::new();
+        let mut declared = HashSet::new();
+        for statement in statements {
+            let Statement::变量声明 { 名字, 类型, 初始值, 是常量, .. } = statement else {
+                continue;
+            };
+            if !declared.insert(名字) {
+                continue;
+            }
+            let c_name = format!("{}{}", prefix, 名字);
+            match constant_initializer(初始值.as_ref(), *类型) {
+                Some(value) if *是常量 && counts[名字.as_str()] == 1 => {
+                    code.push_str(&format!("const {} {} = {};\n", c_type(*类型), c_name, value));
+                    self.constant_globals.borrow_mut().insert(c_name);
+                }
+                _ => code.push_str(&format!("{} {} = {};\n", c_type(*类型), c_name, global_default(*类型))),
+            }
+        }
+        code
+    }
+    
+    /// 模块的名字加上它在加载顺序中的位置，不同目录中的同名模块也不会冲突
+    fn prefix_of(&self, module: &Module) -> String {
+        let index = self
+            .modules
+            .modules()
+            .iter()
+            .position(|loaded| loaded.path == module.path)
+            .unwrap_or_default();
+        let name: String = module
+            .name
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == '_' { c } else { '_' })
+            .collect();
+        format!("{}_{}_", name, index)
+    }
+    
+    fn imports_of(&self, program: &Program, directory: &Path) -> HashMap<String, Rc<Module>> {
+        program
+            .语句
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::导入语句 { 路径, 别名, .. } => {
+                    let module = self.modules.get(directory, 路径)?;
+                    Some((import_name(路径, 别名.as_deref()), module))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+    
+    /// 名字在 C 代码中的写法，模块的全局变量和函数带前缀
+    fn c_name(&self, name: &str) -> String {
+        let scope = self.scope.borrow();
+        if scope.globals.contains(name) {
+            format!("{}{}", scope.prefix, name)
+        } else {
+            name.to_string()
+        }
+    }
+    
+    /// `模块.成员` 在 C 代码中的名字；`object` 不是导入的模块时返回 None
+    fn module_member(&self, object: &Expression, member: &str) -> Option<Result<String, String>> {
+        let Expression::变量 { 名字, .. } = object else {
+            return None;
+        };
+        let module = self.scope.borrow().imports.get(名字)?.clone();
+        if module.exports().iter().any(|export| export == member) {
+            Some(Ok(format!("{}{}", self.prefix_of(&module), member)))
+        } else {
+            Some(Err(format!("模块 {} 没有公开的成员 {}", module.name, member)))
+        }
+    }
+    
+    fn generate_statement_c_code(&self, statement: &Statement) -> Result<String, String> {
+        let code = match statement {
             Statement::表达式语句(expr) => {
-                format!("{};", self.generate_expression_c_code(expr))
+                // 结果不会被使用，不需要装箱
+                format!("{};", self.generate_typed(expr)?.0)
             }
-            Statement::变量声明 { 名字, 初始值, 是常量 } => {
-                let const_str = if *是常量 { "const " } else { "" };
+            Statement::变量声明 { 名字, 类型, 初始值, 是常量, .. } => {
+                // 数组常量仍然需要通过 array_push 填充元素，只有标量常量声明为 const；
+                // 字符串常量不声明为 const，否则不能传给参数是 char* 的函数
+                let const_str = if *是常量 && *类型 != Some(Type::字符串) { "const " } else { "" };
+                // 文件最外层的变量已经声明为全局变量，这里只是赋初始值；代码块中的同名变量是局部变量
+                let is_global = self.frames.borrow().is_empty()
+                    && self.locals.borrow().len() == 1
+                    && self.scope.borrow().globals.contains(名字);
+                let c_name = self.c_name(名字);
+                if is_global && self.constant_globals.borrow().contains(&c_name) {
+                    return Ok(String::new());
+                }
+                let declaration = if is_global { c_name.clone() } else { self.local_declaration(*类型, &c_name) };
+                let what = format!("{} {}", if *是常量 { "常量" } else { "变量" }, 名字);
+                if !is_global {
+                    self.declare_local(名字, *类型);
+                }
                 match 初始值 {
+                    Some(expr) if 类型.is_some_and(is_unboxed) => {
+                        let (code, ty) = self.generate_typed(expr)?;
+                        format!("{}{} = {};", const_str, declaration, convert(code, ty, *类型, &what))
+                    }
                     Some(expr) => {
-                        if let Expression::数组字面量(元素) = expr {
-                            if 元素.is_empty() {
-                                format!("Value {}{} = create_array();", const_str, 名字)
-                            } else {
-                                let mut code = format!("Value {}{} = create_array();", const_str, 名字);
-                                for element in 元素 {
-                                    let element_code = self.generate_expression_c_code(element);
-                                    code.push_str(&format!("\n    array_push(&{}, {});", 名字, element_code));
-                                }
-                                code
+                        if let (Expression::数组字面量(元素), None | Some(Type::数组)) = (expr, 类型) {
+                            let mut code = format!("{} = create_array();", declaration);
+                            for element in 元素 {
+                                let element_code = self.generate_expression_c_code(element)?;
+                                code.push_str(&format!("\n    array_push({}, {});", c_name, element_code));
                             }
+                            code
                         } else {
-                            let expr_code = self.generate_expression_c_code(expr);
-                            format!("Value {}{} = {};", const_str, 名字, expr_code)
+                            let (code, ty) = self.generate_typed(expr)?;
+                            let expr_code = convert(code, ty, *类型, &what);
+                            if is_global {
+                                format!("{} = {};", declaration, expr_code)
+                            } else {
+                                format!("{}{} = {};", const_str, declaration, expr_code)
+                            }
                         }
                     }
+                    None if is_global && 类型.is_none() => String::new(),
+                    None if 类型.is_none() => {
+                        format!("{}{} = (Value){{VALUE_NULL}};", const_str, declaration)
+                    }
+                    // 没有初始值的变量的值是 空，与标注不符时在运行时报错
                     None => {
-                        format!("Value {}{} = create_null();", const_str, 名字)
+                        let value = convert("(Value){VALUE_NULL}".to_string(), Some(Type::空), *类型, &what);
+                        format!("{}{} = {};", if is_global { "" } else { const_str }, declaration, value)
                     }
                 }
             }
-            Statement::函数声明 { 名字, 参数, 体 } => {
-                let args: Vec<String> = 参数.iter().map(|_| "Value".to_string()).collect();
-                let args_str = args.join(", ");
+            Statement::结构声明 { .. } => {
+                return Err("C 后端暂不支持结构".to_string());
+            }
+            Statement::函数声明 { 名字, 参数, 参数类型, 返回类型, .. } => {
+                self.declare_function(名字, 参数, 参数类型, *返回类型);
                 // 在C语言中，函数声明应该放在main函数外部
-                format!("Value {}({});", 名字, args_str)
-            }
-            Statement::如果语句 { 条件, 真分支, 假分支 } => {
-                let condition_code = self.generate_expression_c_code(条件);
-                let mut true_body = String::new();
-                for stmt in 真分支 {
-                    true_body.push_str(&self.generate_statement_c_code(stmt));
-                    true_body.push_str("\n");
-                }
-                let mut false_body = String::new();
-                for stmt in 假分支 {
-                    false_body.push_str(&self.generate_statement_c_code(stmt));
-                    false_body.push_str("\n");
-                }
+                format!("{};", self.function_head(名字))
+            }
+            Statement::如果语句 { 条件, 真分支, 假分支, .. } => {
+                let condition_code = self.generate_condition(条件)?;
+                let true_body = self.generate_block(真分支)?;
+                let false_body = self.generate_block(假分支)?;
                 format!("if ({}) {{\n{}\n}} else {{\n{}\n}}", condition_code, true_body, false_body)
             }
-            Statement::循环语句 { 条件, 体 } => {
-                let mut body_code = String::new();
-                for stmt in 体 {
-                    body_code.push_str(&self.generate_statement_c_code(stmt));
-                    body_code.push_str("\n");
-                }
-                match 条件 {
-                    Some(cond) => {
-                        let condition_code = self.generate_expression_c_code(cond);
-                        format!("while ({}) {{\n{}\n}}", condition_code, body_code)
-                    }
-                    None => {
-                        format!("while (1) {{\n{}\n}}", body_code)
+            Statement::循环语句 { 条件, 体, .. } => self.generate_loop(条件.as_ref(), 体)?,
+            Statement::当语句 { 条件, 体, .. } => self.generate_loop(Some(条件), 体)?,
+            Statement::对于语句 { 变量, 值变量, 可迭代, 体, .. } => {
+                let iterable_code = self.generate_expression_c_code(可迭代)?;
+                let body_code = self.in_scope(|| {
+                    self.declare_local(变量, None);
+                    if let Some(value_name) = 值变量 {
+                        self.declare_local(value_name, None);
                     }
+                    self.in_frame(Frame::循环, || self.generate_block(体))
+                })?;
+                // (下标, 值) 和 (键, 值) 形式分别取出键和值
+                let items = if 值变量.is_some() { "iteration_keys" } else { "iteration_items" };
+                let mut code = format!("{{\nValue {}_source = {};\nValue {}_items = {}({}_source);\n", 变量, iterable_code, 变量, items, 变量);
+                let mut bindings = format!("    {} = {}_items.as.array->values[{}_i];\n", self.local_declaration(None, 变量), 变量, 变量);
+                if let Some(value_name) = 值变量 {
+                    code.push_str(&format!("Value {}_values = iteration_values({}_source);\n", 变量, 变量));
+                    bindings.push_str(&format!("    {} = {}_values.as.array->values[{}_i];\n", self.local_declaration(None, value_name), 变量, 变量));
                 }
+                code.push_str(&format!("for (int {}_i = 0; {}_i < {}_items.as.array->count; {}_i++) {{\n{}{}\n}}\n}}",
+                       变量, 变量, 变量, 变量, bindings, body_code));
+                code
             }
-            Statement::当语句 { 条件, 体 } => {
-                let condition_code = self.generate_expression_c_code(条件);
-                let mut body_code = String::new();
-                for stmt in 体 {
-                    body_code.push_str(&self.generate_statement_c_code(stmt));
-                    body_code.push_str("\n");
-                }
-                format!("do {{\n{}\n}} while ({});", body_code, condition_code)
-            }
-            Statement::对于语句 { 变量, 可迭代, 体 } => {
-                let iterable_code = self.generate_expression_c_code(可迭代);
-                let mut body_code = String::new();
-                for stmt in 体 {
-                    body_code.push_str(&self.generate_statement_c_code(stmt));
-                    body_code.push_str("\n");
+            Statement::尝试语句 { 体, 捕获变量, 捕获, 最终, .. } => {
+                self.generate_try_statement(体, 捕获变量.as_ref().map(|(name, _)| name.as_str()), 捕获.as_deref(), 最终.as_deref())?
+            }
+            // 导入的模块在 main 开始时已经初始化
+            Statement::导入语句 { 路径, .. } => format!("/* 导入 {} */", 路径),
+            Statement::抛出语句 { 值, 位置 } => {
+                let value_code = self.generate_expression_c_code(值)?;
+                format!("throw_value({}, {}, {});", value_code, 位置.line, 位置.column)
+            }
+            Statement::返回语句 { 值, .. } => {
+                let (code, ty) = match 值 {
+                    Some(expr) => self.generate_typed(expr)?,
+                    None => ("(Value){VALUE_NULL}".to_string(), Some(Type::空)),
+                };
+                let returns = self.frames.borrow().iter().rev().find_map(|frame| match frame {
+                    Frame::函数(returns) => Some(*returns),
+                    _ => None,
+                });
+                let returns = returns.flatten();
+                let value_code = convert(code, ty, returns, "返回值");
+                // 先计算返回值，再离开外层的尝试语句
+                let cleanup = self.leave_frames(|frame| matches!(frame, Frame::函数(_)));
+                if cleanup.is_empty() {
+                    format!("return {};", value_code)
+                } else {
+                    format!("{{\n{} return_value = {};\n{}return return_value;\n}}", c_type(returns), value_code, cleanup)
                 }
-                format!("for (int {}_i = 0; {}_i < {}.as.array.count; {}_i++) {{\n    Value {} = {}.as.array.values[{}_i];\n{}\n}}", 
-                       变量, 变量, iterable_code, 变量, 变量, iterable_code, 变量, body_code)
             }
-            Statement::返回语句(值) => {
-                match 值 {
-                    Some(expr) => {
-                        format!("return {};", self.generate_expression_c_code(expr))
-                    }
-                    None => {
-                        "return;".to_string()
-                    }
+            Statement::跳出语句 { .. } => {
+                format!("{}break;", self.leave_frames(|frame| matches!(frame, Frame::循环 | Frame::函数(_))))
+            }
+            Statement::继续语句 { .. } => {
+                format!("{}continue;", self.leave_frames(|frame| matches!(frame, Frame::循环 | Frame::函数(_))))
+            }
+            Statement::错误 { .. } => "/* 语法错误 */".to_string(),
+            Statement::注释 { .. } | Statement::空行 => String::new(),
+        };
+        Ok(code)
+    }
+    
+    /// 生成一个代码块中的语句，其中声明的变量只在这个代码块中可见
+    fn generate_block(&self, statements: &[Statement]) -> Result<String, String> {
+        self.in_scope(|| {
+            let mut code = String::new();
+            for stmt in statements {
+                code.push_str(&self.generate_statement_c_code(stmt)?);
+                code.push('\n');
+            }
+            Ok(code)
+        })
+    }
+    
+    /// 在新的一层局部变量中生成代码
+    fn in_scope<T>(&self, generate: impl FnOnce() -> T) -> T {
+        self.locals.borrow_mut().push(HashMap::new());
+        let result = generate();
+        self.locals.borrow_mut().pop();
+        result
+    }
+    
+    fn declare_local(&self, name: &str, ty: Option<Type>) {
+        if let Some(scope) = self.locals.borrow_mut().last_mut() {
+            scope.insert(name.to_string(), ty);
+        }
+    }
+    
+    /// 名字的类型标注：先找局部变量，再找模块的全局变量
+    fn variable_type(&self, name: &str) -> Option<Type> {
+        if let Some(ty) = self.locals.borrow().iter().rev().find_map(|scope| scope.get(name)) {
+            return *ty;
+        }
+        self.global_types.borrow().get(&self.c_name(name)).copied().flatten()
+    }
+    
+    /// 与解释器的 `execute_loop` 相同：每次执行循环体之前检查条件，`继续` 之后也一样
+    fn generate_loop(&self, condition: Option<&Expression>, body: &[Statement]) -> Result<String, String> {
+        let condition_code = match condition {
+            Some(condition) => self.generate_condition(condition)?,
+            None => "1".to_string(),
+        };
+        let body_code = self.in_frame(Frame::循环, || self.generate_block(body))?;
+        Ok(format!("while ({}) {{\n{}\n}}", condition_code, body_code))
+    }
+    
+    /// 在 `frame` 之内生成代码
+    fn in_frame<T>(&self, frame: Frame, generate: impl FnOnce() -> T) -> T {
+        self.frames.borrow_mut().push(frame);
+        let result = generate();
+        self.frames.borrow_mut().pop();
+        result
+    }
+    
+    /// 跳转到 `until` 匹配的那一层之前，弹出途经的尝试语句的处理器，并执行它们的最终代码块
+    fn leave_frames(&self, until: impl Fn(&Frame) -> bool) -> String {
+        let mut code = String::new();
+        for frame in self.frames.borrow().iter().rev() {
+            if until(frame) {
+                break;
+            }
+            if let Frame::尝试 { handlers, finally } = frame {
+                if *handlers > 0 {
+                    code.push_str(&format!("try_depth -= {};\n", handlers));
+                }
+                if let Some(finally) = finally {
+                    code.push_str(finally);
                 }
             }
-            Statement::跳出语句 => "break;".to_string(),
-            Statement::继续语句 => "continue;".to_string(),
         }
+        code
     }
     
-    fn generate_expression_c_code(&self, expr: &Expression) -> String {
-        match expr {
-            Expression::数字字面量(n) => {
-                format!("(Value){{VALUE_NUMBER, .as.number = {}}}", n)
-            }
-            Expression::字符串字面量(s) => {
-                format!("(Value){{VALUE_STRING, .as.string = \"{}\"}}", s)
-            }
-            Expression::变量(name) => name.clone(),
-            Expression::二元运算 { 左, 运算符, 右 } => {
-                let left_code = self.generate_expression_c_code(左);
-                let right_code = self.generate_expression_c_code(右);
-                match 运算符.as_str() {
-                    "+" => format!("add_values({}, {})", left_code, right_code),
-                    "-" => format!("subtract_values({}, {})", left_code, right_code),
-                    "*" => format!("multiply_values({}, {})", left_code, right_code),
-                    "/" => format!("divide_values({}, {})", left_code, right_code),
-                    "==" => format!("(Value){{VALUE_BOOLEAN, .as.boolean = compare_values({}, {}) == 0}}", left_code, right_code),
-                    "!=" => format!("(Value){{VALUE_BOOLEAN, .as.boolean = compare_values({}, {}) != 0}}", left_code, right_code),
-                    "<" => format!("(Value){{VALUE_BOOLEAN, .as.boolean = compare_values({}, {}) < 0}}", left_code, right_code),
-                    ">" => format!("(Value){{VALUE_BOOLEAN, .as.boolean = compare_values({}, {}) > 0}}", left_code, right_code),
-                    "<=" => format!("(Value){{VALUE_BOOLEAN, .as.boolean = compare_values({}, {}) <= 0}}", left_code, right_code),
-                    ">=" => format!("(Value){{VALUE_BOOLEAN, .as.boolean = compare_values({}, {}) >= 0}}", left_code, right_code),
-                    "&&" => format!("(Value){{VALUE_BOOLEAN, .as.boolean = is_truthy({}) && is_truthy({})}}", left_code, right_code),
-                    "||" => format!("(Value){{VALUE_BOOLEAN, .as.boolean = is_truthy({}) || is_truthy({})}}", left_code, right_code),
-                    _ => format!("/* 未知运算符 {} */ (Value){{VALUE_NULL}}", 运算符),
+    /// 尝试语句用 setjmp 设置跳转点，throw_value 用 longjmp 回到这里；
+    /// 有最终代码块时，捕获代码块也在一个跳转点之内运行，这样其中的错误会在执行最终代码块之后继续传播
+    fn generate_try_statement(
+        &self,
+        body: &[Statement],
+        catch_variable: Option<&str>,
+        handler: Option<&[Statement]>,
+        finally: Option<&[Statement]>,
+    ) -> Result<String, String> {
+        let id = self.next_try_id.get();
+        self.next_try_id.set(id + 1);
+        
+        let finally_code = finally.map(|finally| self.generate_block(finally)).transpose()?;
+        let body_code = self.in_frame(
+            Frame::尝试 { handlers: 1, finally: finally_code.clone() },
+            || self.generate_block(body),
+        )?;
+        let catch_binding = catch_variable.map_or(String::new(), |name| format!("{} = current_error;\n", self.local_declaration(None, name)));
+        // 捕获的错误绑定到的变量只在捕获代码块中可见
+        let generate_handler = |handler: &[Statement]| {
+            self.in_scope(|| {
+                if let Some(name) = catch_variable {
+                    self.declare_local(name, None);
                 }
+                self.generate_block(handler)
+            })
+        };
+        
+        let Some(finally_code) = finally_code else {
+            // 只有捕获：出错时跳转点已经弹出，捕获代码块中的错误直接向外传播
+            let handler_code = generate_handler(handler.unwrap_or(&[]))?;
+            return Ok(format!(
+                "if (setjmp(try_stack[try_depth++]) == 0) {{\n{}try_depth--;\n}} else {{\n{}{}}}",
+                body_code, catch_binding, handler_code
+            ));
+        };
+        
+        let failed = format!("try_{}_failed", id);
+        let error = format!("try_{}_error", id);
+        let on_error = match handler {
+            Some(handler) => {
+                let handler_code = self.in_frame(
+                    Frame::尝试 { handlers: 1, finally: Some(finally_code.clone()) },
+                    || generate_handler(handler),
+                )?;
+                format!(
+                    "if (setjmp(try_stack[try_depth++]) == 0) {{\n{}{}try_depth--;\n}} else {{\n{} = 1;\n{} = current_error;\n}}\n",
+                    catch_binding, handler_code, failed, error
+                )
             }
-            Expression::一元运算 { 运算符, 操作数 } => {
-                let operand_code = self.generate_expression_c_code(操作数);
+            None => format!("{} = 1;\n{} = current_error;\n", failed, error),
+        };
+        Ok(format!(
+            "{{\nvolatile int {failed} = 0;\nValue {error};\nif (setjmp(try_stack[try_depth++]) == 0) {{\n{body}try_depth--;\n}} else {{\n{on_error}}}\n{finally}if ({failed}) raise_error({error});\n}}",
+            failed = failed,
+            error = error,
+            body = body_code,
+            on_error = on_error,
+            finally = finally_code,
+        ))
+    }
+    
+    /// 生成表达式，结果装箱为 Value
+    fn generate_expression_c_code(&self, expr: &Expression) -> Result<String, String> {
+        let (code, ty) = self.generate_typed(expr)?;
+        Ok(box_value(code, ty))
+    }
+    
+    /// 生成条件表达式，结果是 C 的真假
+    fn generate_condition(&self, expr: &Expression) -> Result<String, String> {
+        let (code, ty) = self.generate_typed(expr)?;
+        Ok(truthy(code, ty))
+    }
+    
+    /// 生成表达式和它确定的类型：数字、字符串和布尔值直接用 double、char* 和 bool 表示，
+    /// 其余的值和类型不确定的值用 Value 表示
+    fn generate_typed(&self, expr: &Expression) -> Result<(String, Option<Type>), String> {
+        let code = match expr {
+            // 数字总是写成带小数点或指数的形式，C 中不会按整数计算
+            Expression::数字字面量(n) => return Ok((format!("{:?}", n), Some(Type::数字))),
+            Expression::字符串字面量(s) => return Ok((c_string_literal(s), Some(Type::字符串))),
+            Expression::布尔字面量(b) => return Ok((b.to_string(), Some(Type::布尔值))),
+            Expression::变量 { 名字, .. } if self.scope.borrow().imports.contains_key(名字) => {
+                return Err(format!("C 后端只能通过 {}.成员 使用导入的模块", 名字));
+            }
+            Expression::变量 { 名字, .. } => return Ok((self.c_name(名字), self.variable_type(名字))),
+            Expression::二元运算 { 左, 运算符, 右, .. } => return self.generate_binary(左, 运算符, 右),
+            Expression::一元运算 { 运算符, 操作数, .. } => {
+                let (operand_code, ty) = self.generate_typed(操作数)?;
                 match 运算符.as_str() {
-                    "!" => format!("(Value){{VALUE_BOOLEAN, .as.boolean = !is_truthy({})}}", operand_code),
-                    "-" => format!("negate_value({})", operand_code),
+                    "!" => return Ok((format!("(!{})", truthy(operand_code, ty)), Some(Type::布尔值))),
+                    "-" if ty == Some(Type::数字) => return Ok((format!("(-{})", operand_code), ty)),
+                    "-" => format!("negate_value({})", box_value(operand_code, ty)),
                     _ => format!("/* 未知一元运算符 {} */ (Value){{VALUE_NULL}}", 运算符),
                 }
             }
-            Expression::函数调用 { 函数名, 参数 } => {
-                let args: Vec<String> = 参数.iter().map(|arg| self.generate_expression_c_code(arg)).collect();
+            Expression::函数调用 { 函数, 参数, .. } => {
+                let Expression::变量 { 名字: 函数名, .. } = 函数.as_ref() else {
+                    return Err(format!("C 后端只能调用具名函数: {}", 函数));
+                };
                 // 映射内置函数名
                 let c_function_name = match 函数名.as_str() {
                     "打印" => "builtin_print",
@@ -369,13 +828,36 @@ impl CodeGenerator {
                     "数组添加" => "array_push",
                     "数组删除" => "array_remove",
                     "数组索引" => "array_get",
-                    _ => 函数名.as_str(),
+                    "范围" => "make_range",
+                    _ => return self.generate_call(&self.c_name(函数名), 参数),
                 };
+                let args = 参数
+                    .iter()
+                    .map(|arg| self.generate_expression_c_code(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                // 省略的起点和步长分别是 0 和 1
+                if 函数名 == "范围" {
+                    let zero = "(Value){VALUE_NUMBER, .as.number = 0}".to_string();
+                    let one = "(Value){VALUE_NUMBER, .as.number = 1}".to_string();
+                    let (start, end, step) = match args.as_slice() {
+                        [end] => (zero, end.clone(), one),
+                        [start, end] => (start.clone(), end.clone(), one),
+                        [start, end, step] => (start.clone(), end.clone(), step.clone()),
+                        _ => return Err("范围函数需要 1 到 3 个参数".to_string()),
+                    };
+                    return Ok((format!("make_range({}, {}, {})", start, end, step), None));
+                }
                 format!("{}({})", c_function_name, args.join(", "))
             }
-            Expression::赋值 { 变量名, 值 } => {
-                let value_code = self.generate_expression_c_code(值);
-                format!("{} = {}", 变量名, value_code)
+            Expression::匿名函数 { .. } => {
+                return Err("C 后端暂不支持匿名函数".to_string());
+            }
+            // 赋值的结果是赋给变量的值，类型与变量相同
+            Expression::赋值 { 变量名, 值, .. } => {
+                let target = self.variable_type(变量名);
+                let (code, ty) = self.generate_typed(值)?;
+                let value_code = convert(code, ty, target, &format!("变量 {}", 变量名));
+                return Ok((format!("({} = {})", self.c_name(变量名), value_code), target));
             }
             Expression::数组字面量(元素) => {
                 if 元素.is_empty() {
@@ -385,64 +867,274 @@ impl CodeGenerator {
                     let mut code = "({ ".to_string();
                     code.push_str("Value temp_array = create_array(); ");
                     for element in 元素 {
-                        let element_code = self.generate_expression_c_code(element);
-                        code.push_str(&format!("array_push(&temp_array, {}); ", element_code));
+                        let element_code = self.generate_expression_c_code(element)?;
+                        code.push_str(&format!("array_push(temp_array, {}); ", element_code));
                     }
                     code.push_str("temp_array; })");
                     code
                 }
             }
-            Expression::数组索引 { 数组, 索引 } => {
-                let array_code = self.generate_expression_c_code(数组);
-                let index_code = self.generate_expression_c_code(索引);
-                format!("array_get({}, (int){}.as.number)", array_code, index_code)
+            // 下标、长度和删除在运行时按数组或字典分派
+            Expression::范围 { 起, 止, .. } => {
+                let start_code = self.generate_expression_c_code(起)?;
+                let end_code = self.generate_expression_c_code(止)?;
+                format!("make_range({}, {}, (Value){{VALUE_NUMBER, .as.number = 1}})", start_code, end_code)
+            }
+            Expression::数组索引 { 数组, 索引, .. } => {
+                let array_code = self.generate_expression_c_code(数组)?;
+                let index_code = self.generate_expression_c_code(索引)?;
+                format!("index_get({}, {})", array_code, index_code)
+            }
+            Expression::索引赋值 { 数组, 索引, 值, .. } => {
+                let array_code = self.generate_expression_c_code(数组)?;
+                let index_code = self.generate_expression_c_code(索引)?;
+                let value_code = self.generate_expression_c_code(值)?;
+                format!("index_set({}, {}, {})", array_code, index_code, value_code)
+            }
+            Expression::字典字面量 { 条目, .. } => {
+                let mut code = "({ Value temp_map = create_map(); ".to_string();
+                for (key, value) in 条目 {
+                    let key_code = self.generate_expression_c_code(key)?;
+                    let value_code = self.generate_expression_c_code(value)?;
+                    code.push_str(&format!("map_set(temp_map, {}, {}); ", key_code, value_code));
+                }
+                code.push_str("temp_map; })");
+                code
+            }
+            Expression::方法调用 { 对象, 方法, 参数, .. } => {
+                if let Some(function) = self.module_member(对象, 方法) {
+                    return self.generate_call(&function?, 参数);
+                }
+                let args = 参数
+                    .iter()
+                    .map(|arg| self.generate_expression_c_code(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                
+                // 只支持数组和字典的内置方法，参数数量与解释器的要求一致；对象的类型在运行时检查
+                let object_code = format!("expect_method({}, \"{}\")", self.generate_expression_c_code(对象)?, 方法);
+                match (方法.as_str(), args.as_slice()) {
+                    ("长度", []) => format!("(Value){{VALUE_NUMBER, .as.number = value_length({})}}", object_code),
+                    ("添加", [element]) => format!("array_push({}, {})", object_code, element),
+                    ("删除", [key]) => format!("remove_item({}, {})", object_code, key),
+                    ("键", []) => format!("map_keys({})", object_code),
+                    ("值", []) => format!("map_values({})", object_code),
+                    ("包含", [key]) => format!("(Value){{VALUE_BOOLEAN, .as.boolean = map_has({}, {})}}", object_code, key),
+                    _ => return Err(format!("C 后端暂不支持方法调用: {}", expr)),
+                }
+            }
+            // C 后端只有错误值有字段
+            Expression::成员访问 { 对象, 成员, .. } => {
+                if let Some(member) = self.module_member(对象, 成员) {
+                    let member = member?;
+                    let ty = self.global_types.borrow().get(&member).copied().flatten();
+                    return Ok((member, ty));
+                }
+                format!("member_get({}, \"{}\")", self.generate_expression_c_code(对象)?, 成员)
+            }
+            Expression::成员赋值 { .. } | Expression::自己 { .. } => {
+                return Err("C 后端暂不支持结构".to_string());
+            }
+        };
+        Ok((code, None))
+    }
+    
+    /// 两边都是数字时直接用 C 的运算，否则交给运行时按值的类型计算；比较的结果总是 C 的真假
+    fn generate_binary(&self, left: &Expression, operator: &str, right: &Expression) -> Result<(String, Option<Type>), String> {
+        let (left_code, left_type) = self.generate_typed(left)?;
+        let (right_code, right_type) = self.generate_typed(right)?;
+        let numbers = left_type == Some(Type::数字) && right_type == Some(Type::数字);
+        let (code, ty) = match operator {
+            "&&" | "||" => {
+                let code = format!("({} {} {})", truthy(left_code, left_type), operator, truthy(right_code, right_type));
+                (code, Some(Type::布尔值))
+            }
+            "==" | "!=" => {
+                let code = match (left_type, right_type) {
+                    (Some(Type::字符串), Some(Type::字符串)) => {
+                        format!("(strcmp({}, {}) {} 0)", left_code, right_code, operator)
+                    }
+                    (Some(a), Some(b)) if a == b && is_unboxed(a) => {
+                        format!("({} {} {})", left_code, operator, right_code)
+                    }
+                    _ => {
+                        let negate = if operator == "!=" { "!" } else { "" };
+                        let (a, b) = (box_value(left_code, left_type), box_value(right_code, right_type));
+                        format!("{}values_equal({}, {})", negate, a, b)
+                    }
+                };
+                (code, Some(Type::布尔值))
             }
-            Expression::数组长度(数组) => {
-                let array_code = self.generate_expression_c_code(数组);
-                format!("(Value){{VALUE_NUMBER, .as.number = array_length({})}}", array_code)
+            "<" | ">" | "<=" | ">=" if numbers => (format!("({} {} {})", left_code, operator, right_code), Some(Type::布尔值)),
+            "<" | ">" | "<=" | ">=" => {
+                let (a, b) = (box_value(left_code, left_type), box_value(right_code, right_type));
+                (format!("(compare_values({}, {}) {} 0)", a, b, operator), Some(Type::布尔值))
             }
-            Expression::数组添加 { 数组, 元素 } => {
-                let array_code = self.generate_expression_c_code(数组);
-                let element_code = self.generate_expression_c_code(元素);
-                format!("array_push(&{}, {})", array_code, element_code)
+            "+" | "-" | "*" if numbers => (format!("({} {} {})", left_code, operator, right_code), Some(Type::数字)),
+            "/" if numbers => (format!("divide_numbers({}, {})", left_code, right_code), Some(Type::数字)),
+            // 字符串与数字、布尔值相加时按显示的文本拼接
+            "+" if (left_type == Some(Type::字符串) || right_type == Some(Type::字符串))
+                && left_type.is_some_and(is_unboxed)
+                && right_type.is_some_and(is_unboxed) =>
+            {
+                let (a, b) = (text(left_code, left_type), text(right_code, right_type));
+                (format!("concat_strings({}, {})", a, b), Some(Type::字符串))
             }
-            Expression::数组删除 { 数组, 索引 } => {
-                let array_code = self.generate_expression_c_code(数组);
-                let index_code = self.generate_expression_c_code(索引);
-                format!("array_remove(&{}, (int){}.as.number)", array_code, index_code)
+            "+" | "-" | "*" | "/" => {
+                let function = match operator {
+                    "+" => "add_values",
+                    "-" => "subtract_values",
+                    "*" => "multiply_values",
+                    _ => "divide_values",
+                };
+                let (a, b) = (box_value(left_code, left_type), box_value(right_code, right_type));
+                (format!("{}({}, {})", function, a, b), None)
             }
+            _ => (format!("/* 未知运算符 {} */ (Value){{VALUE_NULL}}", operator), None),
+        };
+        Ok((code, ty))
+    }
+    
+    /// 调用 C 中名为 `c_name` 的函数，参数按函数的参数类型转换，结果的类型是函数的返回类型
+    fn generate_call(&self, c_name: &str, arguments: &[Expression]) -> Result<(String, Option<Type>), String> {
+        let mut args = Vec::new();
+        for (i, arg) in arguments.iter().enumerate() {
+            let (code, ty) = self.generate_typed(arg)?;
+            let parameter = self
+                .signatures
+                .borrow()
+                .get(c_name)
+                .and_then(|signature| signature.parameters.get(i).cloned());
+            args.push(match parameter {
+                Some((name, expected)) => convert(code, ty, expected, &format!("参数 {}", name)),
+                None => box_value(code, ty),
+            });
         }
+        let returns = self.signatures.borrow().get(c_name).and_then(|signature| signature.returns);
+        Ok((format!("{}({})", c_name, args.join(", ")), returns))
     }
     
     fn generate_builtin_functions(&self) -> String {
         let mut code = String::new();
         
         code.push_str("\n// 内置函数实现\n");
-        code.push_str("void builtin_print(Value value) {\n");
+        code.push_str("const char* boolean_text(bool value) {\n");
+        code.push_str("    return value ? \"真\" : \"假\";\n");
+        code.push_str("}\n\n");
+        
+        // 数字按 Rust 的 f64 显示：能还原出原值的最短写法，不用科学计数法
+        code.push_str("void format_number(double n, char* buffer) {\n");
+        code.push_str("    if (isnan(n)) { strcpy(buffer, \"NaN\"); return; }\n");
+        code.push_str("    if (isinf(n)) { strcpy(buffer, n > 0 ? \"inf\" : \"-inf\"); return; }\n");
+        code.push_str("    char digits[32];\n");
+        code.push_str("    for (int precision = 0; precision < 17; precision++) {\n");
+        code.push_str("        snprintf(digits, sizeof(digits), \"%.*e\", precision, n);\n");
+        code.push_str("        if (strtod(digits, NULL) == n) break;\n");
+        code.push_str("    }\n");
+        code.push_str("    char* p = digits;\n");
+        code.push_str("    char* out = buffer;\n");
+        code.push_str("    if (*p == '-') *out++ = *p++;\n");
+        code.push_str("    char mantissa[20];\n");
+        code.push_str("    int count = 0;\n");
+        code.push_str("    for (; *p != 'e'; p++) if (*p != '.') mantissa[count++] = *p;\n");
+        code.push_str("    int exponent = atoi(p + 1);\n");
+        code.push_str("    while (count > 1 && mantissa[count - 1] == '0') count--;\n");
+        code.push_str("    if (exponent < 0) {\n");
+        code.push_str("        *out++ = '0';\n");
+        code.push_str("        *out++ = '.';\n");
+        code.push_str("        for (int i = 0; i < -exponent - 1; i++) *out++ = '0';\n");
+        code.push_str("        for (int i = 0; i < count; i++) *out++ = mantissa[i];\n");
+        code.push_str("    } else {\n");
+        code.push_str("        for (int i = 0; i <= exponent || i < count; i++) {\n");
+        code.push_str("            if (i == exponent + 1) *out++ = '.';\n");
+        code.push_str("            *out++ = i < count ? mantissa[i] : '0';\n");
+        code.push_str("        }\n");
+        code.push_str("    }\n");
+        code.push_str("    *out = '\\0';\n");
+        code.push_str("}\n\n");
+        
+        code.push_str("typedef struct {\n");
+        code.push_str("    char* data;\n");
+        code.push_str("    int length;\n");
+        code.push_str("    int capacity;\n");
+        code.push_str("} Text;\n\n");
+        
+        code.push_str("void text_append(Text* text, const char* s) {\n");
+        code.push_str("    int n = strlen(s);\n");
+        code.push_str("    while (text->length + n + 1 > text->capacity) {\n");
+        code.push_str("        text->capacity = text->capacity ? text->capacity * 2 : 64;\n");
+        code.push_str("        text->data = realloc(text->data, text->capacity);\n");
+        code.push_str("    }\n");
+        code.push_str("    memcpy(text->data + text->length, s, n + 1);\n");
+        code.push_str("    text->length += n;\n");
+        code.push_str("}\n\n");
+        
+        // 与解释器的 Display 一致；open 中是正在显示的数组和字典，引用自身时显示为 [...]
+        code.push_str("void display_value(Text* text, Value value, const void** open, int depth) {\n");
+        code.push_str("    char buffer[512];\n");
         code.push_str("    switch (value.type) {\n");
         code.push_str("        case VALUE_NUMBER:\n");
-        code.push_str("            printf(\"%f\\n\", value.as.number);\n");
+        code.push_str("            format_number(value.as.number, buffer);\n");
+        code.push_str("            text_append(text, buffer);\n");
         code.push_str("            break;\n");
         code.push_str("        case VALUE_STRING:\n");
-        code.push_str("            printf(\"%s\\n\", value.as.string);\n");
+        code.push_str("            text_append(text, \"\\\"\");\n");
+        code.push_str("            text_append(text, value.as.string);\n");
+        code.push_str("            text_append(text, \"\\\"\");\n");
         code.push_str("            break;\n");
         code.push_str("        case VALUE_BOOLEAN:\n");
-        code.push_str("            printf(\"%s\\n\", value.as.boolean ? \"true\" : \"false\");\n");
+        code.push_str("            text_append(text, boolean_text(value.as.boolean));\n");
         code.push_str("            break;\n");
         code.push_str("        case VALUE_ARRAY:\n");
-        code.push_str("            printf(\"[\");\n");
-        code.push_str("            for (int i = 0; i < value.as.array.count; i++) {\n");
-        code.push_str("                if (i > 0) printf(\", \");\n");
-        code.push_str("                builtin_print(value.as.array.values[i]);\n");
+        code.push_str("            for (int i = 0; i < depth; i++) {\n");
+        code.push_str("                if (open[i] == value.as.array) { text_append(text, \"[...]\"); return; }\n");
         code.push_str("            }\n");
-        code.push_str("            printf(\"]\\n\");\n");
+        code.push_str("            if (depth == 64) { text_append(text, \"[...]\"); return; }\n");
+        code.push_str("            open[depth] = value.as.array;\n");
+        code.push_str("            text_append(text, \"[\");\n");
+        code.push_str("            for (int i = 0; i < value.as.array->count; i++) {\n");
+        code.push_str("                if (i > 0) text_append(text, \", \");\n");
+        code.push_str("                display_value(text, value.as.array->values[i], open, depth + 1);\n");
+        code.push_str("            }\n");
+        code.push_str("            text_append(text, \"]\");\n");
+        code.push_str("            break;\n");
+        code.push_str("        case VALUE_MAP:\n");
+        code.push_str("            for (int i = 0; i < depth; i++) {\n");
+        code.push_str("                if (open[i] == value.as.map) { text_append(text, \"{...}\"); return; }\n");
+        code.push_str("            }\n");
+        code.push_str("            if (depth == 64) { text_append(text, \"{...}\"); return; }\n");
+        code.push_str("            open[depth] = value.as.map;\n");
+        code.push_str("            text_append(text, \"{\");\n");
+        code.push_str("            for (int i = 0; i < value.as.map->count; i++) {\n");
+        code.push_str("                if (i > 0) text_append(text, \", \");\n");
+        code.push_str("                text_append(text, \"\\\"\");\n");
+        code.push_str("                text_append(text, value.as.map->keys[i]);\n");
+        code.push_str("                text_append(text, \"\\\": \");\n");
+        code.push_str("                display_value(text, value.as.map->values[i], open, depth + 1);\n");
+        code.push_str("            }\n");
+        code.push_str("            text_append(text, \"}\");\n");
+        code.push_str("            break;\n");
+        code.push_str("        case VALUE_ERROR:\n");
+        code.push_str("            snprintf(buffer, sizeof(buffer), \"错误[%s]: \", value.as.error->kind);\n");
+        code.push_str("            text_append(text, buffer);\n");
+        code.push_str("            text_append(text, value.as.error->message);\n");
         code.push_str("            break;\n");
         code.push_str("        case VALUE_NULL:\n");
-        code.push_str("            printf(\"null\\n\");\n");
+        code.push_str("            text_append(text, \"空\");\n");
         code.push_str("            break;\n");
         code.push_str("    }\n");
         code.push_str("}\n\n");
         
+        code.push_str("char* display_text(Value value) {\n");
+        code.push_str("    Text text = {NULL, 0, 0};\n");
+        code.push_str("    const void* open[64];\n");
+        code.push_str("    display_value(&text, value, open, 0);\n");
+        code.push_str("    return text.data;\n");
+        code.push_str("}\n\n");
+        
+        code.push_str("void builtin_print(Value value) {\n");
+        code.push_str("    printf(\"%s\\n\", display_text(value));\n");
+        code.push_str("}\n\n");
+        
         code.push_str("Value builtin_input() {\n");
         code.push_str("    char buffer[1024];\n");
         code.push_str("    if (fgets(buffer, sizeof(buffer), stdin)) {\n");
@@ -453,39 +1145,281 @@ impl CodeGenerator {
         code.push_str("}\n\n");
         
         code.push_str("Value create_array() {\n");
-        code.push_str("    Value array = {VALUE_ARRAY};\n");
-        code.push_str("    array.as.array.values = malloc(sizeof(Value) * 8);\n");
-        code.push_str("    array.as.array.count = 0;\n");
-        code.push_str("    array.as.array.capacity = 8;\n");
-        code.push_str("    return array;\n");
+        code.push_str("    Array* array = malloc(sizeof(Array));\n");
+        code.push_str("    array->values = malloc(sizeof(Value) * 8);\n");
+        code.push_str("    array->count = 0;\n");
+        code.push_str("    array->capacity = 8;\n");
+        code.push_str("    return (Value){VALUE_ARRAY, .as.array = array};\n");
         code.push_str("}\n\n");
         
-        code.push_str("void array_push(Value* array, Value value) {\n");
-        code.push_str("    if (array->as.array.count >= array->as.array.capacity) {\n");
-        code.push_str("        array->as.array.capacity *= 2;\n");
-        code.push_str("        array->as.array.values = realloc(array->as.array.values, sizeof(Value) * array->as.array.capacity);\n");
+        // 添加原地修改数组并返回数组本身，删除返回被删除的元素，与解释器一致
+        code.push_str("Value array_push(Value array, Value value) {\n");
+        code.push_str("    Array* a = array.as.array;\n");
+        code.push_str("    if (a->count >= a->capacity) {\n");
+        code.push_str("        a->capacity *= 2;\n");
+        code.push_str("        a->values = realloc(a->values, sizeof(Value) * a->capacity);\n");
         code.push_str("    }\n");
-        code.push_str("    array->as.array.values[array->as.array.count++] = value;\n");
+        code.push_str("    a->values[a->count++] = value;\n");
+        code.push_str("    return array;\n");
         code.push_str("}\n\n");
         
         code.push_str("Value array_get(Value array, int index) {\n");
-        code.push_str("    if (index < 0 || index >= array.as.array.count) {\n");
-        code.push_str("        return (Value){VALUE_NULL};\n");
+        code.push_str("    if (index < 0 || index >= array.as.array->count) {\n");
+        code.push_str("        runtime_error(\"E0206\", \"数组索引越界\");\n");
+        code.push_str("    }\n");
+        code.push_str("    return array.as.array->values[index];\n");
+        code.push_str("}\n\n");
+        
+        code.push_str("Value array_set(Value array, int index, Value value) {\n");
+        code.push_str("    if (index >= 0 && index < array.as.array->count) {\n");
+        code.push_str("        array.as.array->values[index] = value;\n");
         code.push_str("    }\n");
-        code.push_str("    return array.as.array.values[index];\n");
+        code.push_str("    return value;\n");
         code.push_str("}\n\n");
         
         code.push_str("int array_length(Value array) {\n");
-        code.push_str("    return array.as.array.count;\n");
+        code.push_str("    return array.as.array->count;\n");
+        code.push_str("}\n\n");
+        
+        code.push_str("Value array_remove(Value array, int index) {\n");
+        code.push_str("    Array* a = array.as.array;\n");
+        code.push_str("    if (index < 0 || index >= a->count) return (Value){VALUE_NULL};\n");
+        code.push_str("    Value removed = a->values[index];\n");
+        code.push_str("    for (int i = index; i < a->count - 1; i++) {\n");
+        code.push_str("        a->values[i] = a->values[i + 1];\n");
+        code.push_str("    }\n");
+        code.push_str("    a->count--;\n");
+        code.push_str("    return removed;\n");
+        code.push_str("}\n\n");
+        
+        code.push_str("Value create_map() {\n");
+        code.push_str("    Map* map = malloc(sizeof(Map));\n");
+        code.push_str("    map->keys = malloc(sizeof(char*) * 8);\n");
+        code.push_str("    map->values = malloc(sizeof(Value) * 8);\n");
+        code.push_str("    map->count = 0;\n");
+        code.push_str("    map->capacity = 8;\n");
+        code.push_str("    return (Value){VALUE_MAP, .as.map = map};\n");
+        code.push_str("}\n\n");
+        
+        code.push_str("int map_find(Map* m, Value key) {\n");
+        code.push_str("    if (key.type != VALUE_STRING) return -1;\n");
+        code.push_str("    for (int i = 0; i < m->count; i++) {\n");
+        code.push_str("        if (strcmp(m->keys[i], key.as.string) == 0) return i;\n");
+        code.push_str("    }\n");
+        code.push_str("    return -1;\n");
+        code.push_str("}\n\n");
+        
+        code.push_str("Value map_get(Value map, Value key) {\n");
+        code.push_str("    int i = map_find(map.as.map, key);\n");
+        code.push_str("    if (i < 0) runtime_error(\"E0208\", \"字典中没有这个键\");\n");
+        code.push_str("    return map.as.map->values[i];\n");
         code.push_str("}\n\n");
-        code.push_str("\n");
         
-        code.push_str("void array_remove(Value* array, int index) {\n");
-        code.push_str("    if (index < 0 || index >= array->as.array.count) return;\n");
-        code.push_str("    for (int i = index; i < array->as.array.count - 1; i++) {\n");
-        code.push_str("        array->as.array.values[i] = array->as.array.values[i + 1];\n");
+        // 已有的键原地更新，不改变顺序
+        code.push_str("Value map_set(Value map, Value key, Value value) {\n");
+        code.push_str("    Map* m = map.as.map;\n");
+        code.push_str("    if (key.type != VALUE_STRING) return value;\n");
+        code.push_str("    int i = map_find(m, key);\n");
+        code.push_str("    if (i >= 0) {\n");
+        code.push_str("        m->values[i] = value;\n");
+        code.push_str("        return value;\n");
+        code.push_str("    }\n");
+        code.push_str("    if (m->count >= m->capacity) {\n");
+        code.push_str("        m->capacity *= 2;\n");
+        code.push_str("        m->keys = realloc(m->keys, sizeof(char*) * m->capacity);\n");
+        code.push_str("        m->values = realloc(m->values, sizeof(Value) * m->capacity);\n");
         code.push_str("    }\n");
-        code.push_str("    array->as.array.count--;\n");
+        code.push_str("    m->keys[m->count] = key.as.string;\n");
+        code.push_str("    m->values[m->count++] = value;\n");
+        code.push_str("    return value;\n");
+        code.push_str("}\n\n");
+        
+        code.push_str("bool map_has(Value map, Value key) {\n");
+        code.push_str("    return map_find(map.as.map, key) >= 0;\n");
+        code.push_str("}\n\n");
+        
+        code.push_str("Value map_remove(Value map, Value key) {\n");
+        code.push_str("    Map* m = map.as.map;\n");
+        code.push_str("    int index = map_find(m, key);\n");
+        code.push_str("    if (index < 0) return (Value){VALUE_NULL};\n");
+        code.push_str("    Value removed = m->values[index];\n");
+        code.push_str("    for (int i = index; i < m->count - 1; i++) {\n");
+        code.push_str("        m->keys[i] = m->keys[i + 1];\n");
+        code.push_str("        m->values[i] = m->values[i + 1];\n");
+        code.push_str("    }\n");
+        code.push_str("    m->count--;\n");
+        code.push_str("    return removed;\n");
+        code.push_str("}\n\n");
+        
+        code.push_str("Value map_keys(Value map) {\n");
+        code.push_str("    Value keys = create_array();\n");
+        code.push_str("    for (int i = 0; i < map.as.map->count; i++) {\n");
+        code.push_str("        array_push(keys, (Value){VALUE_STRING, .as.string = map.as.map->keys[i]});\n");
+        code.push_str("    }\n");
+        code.push_str("    return keys;\n");
+        code.push_str("}\n\n");
+        
+        code.push_str("Value map_values(Value map) {\n");
+        code.push_str("    Value values = create_array();\n");
+        code.push_str("    for (int i = 0; i < map.as.map->count; i++) {\n");
+        code.push_str("        array_push(values, map.as.map->values[i]);\n");
+        code.push_str("    }\n");
+        code.push_str("    return values;\n");
+        code.push_str("}\n\n");
+        
+        // 索引、长度和删除对数组按下标，对字典按键
+        code.push_str("Value index_get(Value container, Value key) {\n");
+        code.push_str("    if (container.type == VALUE_MAP) return map_get(container, key);\n");
+        code.push_str("    return array_get(container, (int)key.as.number);\n");
+        code.push_str("}\n\n");
+        
+        code.push_str("Value index_set(Value container, Value key, Value value) {\n");
+        code.push_str("    if (container.type == VALUE_MAP) return map_set(container, key, value);\n");
+        code.push_str("    return array_set(container, (int)key.as.number, value);\n");
+        code.push_str("}\n\n");
+        
+        code.push_str("int value_length(Value container) {\n");
+        code.push_str("    if (container.type == VALUE_MAP) return container.as.map->count;\n");
+        code.push_str("    return array_length(container);\n");
+        code.push_str("}\n\n");
+        
+        code.push_str("Value remove_item(Value container, Value key) {\n");
+        code.push_str("    if (container.type == VALUE_MAP) return map_remove(container, key);\n");
+        code.push_str("    return array_remove(container, (int)key.as.number);\n");
+        code.push_str("}\n\n");
+        
+        code.push_str("const char* type_name(Value value) {\n");
+        code.push_str("    switch (value.type) {\n");
+        code.push_str("        case VALUE_NUMBER: return \"数字\";\n");
+        code.push_str("        case VALUE_STRING: return \"字符串\";\n");
+        code.push_str("        case VALUE_BOOLEAN: return \"布尔值\";\n");
+        code.push_str("        case VALUE_ARRAY: return \"数组\";\n");
+        code.push_str("        case VALUE_MAP: return \"字典\";\n");
+        code.push_str("        case VALUE_ERROR: return \"错误\";\n");
+        code.push_str("        default: return \"空\";\n");
+        code.push_str("    }\n");
+        code.push_str("}\n\n");
+        
+        // 值不符合类型标注时报 E0217，信息与解释器相同
+        code.push_str("Value expect_type(Value value, ValueType type, const char* expected, const char* what) {\n");
+        code.push_str("    if (value.type == type) return value;\n");
+        code.push_str("    char* message = malloc(strlen(what) + strlen(expected) + 64);\n");
+        code.push_str("    sprintf(message, \"类型不匹配: %s 应该是 %s，得到 %s\", what, expected, type_name(value));\n");
+        code.push_str("    runtime_error(\"E0217\", message);\n");
+        code.push_str("    return value;\n");
+        code.push_str("}\n\n");
+        
+        // 与解释器的 method_names 一致，没有这个方法时报 E0212
+        code.push_str("Value expect_method(Value object, const char* method) {\n");
+        code.push_str("    const char* array_methods[] = {\"长度\", \"添加\", \"删除\", NULL};\n");
+        code.push_str("    const char* map_methods[] = {\"长度\", \"键\", \"值\", \"包含\", \"删除\", NULL};\n");
+        code.push_str("    const char** methods = object.type == VALUE_ARRAY ? array_methods : object.type == VALUE_MAP ? map_methods : NULL;\n");
+        code.push_str("    for (int i = 0; methods && methods[i]; i++) {\n");
+        code.push_str("        if (strcmp(methods[i], method) == 0) return object;\n");
+        code.push_str("    }\n");
+        code.push_str("    char* message = malloc(strlen(method) + 32);\n");
+        code.push_str("    sprintf(message, \"%s没有方法 %s\", type_name(object), method);\n");
+        code.push_str("    runtime_error(\"E0212\", message);\n");
+        code.push_str("    return object;\n");
+        code.push_str("}\n\n");
+        
+        // 与解释器相同，每一项都用乘法算出
+        code.push_str("Value make_range(Value start, Value end, Value step) {\n");
+        code.push_str("    if (start.type != VALUE_NUMBER || end.type != VALUE_NUMBER || step.type != VALUE_NUMBER) {\n");
+        code.push_str("        runtime_error(\"E0204\", \"范围的起点、终点和步长必须是数字\");\n");
+        code.push_str("    }\n");
+        code.push_str("    if (step.as.number == 0) runtime_error(\"E0204\", \"范围的步长不能为 0\");\n");
+        code.push_str("    Value result = create_array();\n");
+        code.push_str("    for (int i = 0; ; i++) {\n");
+        code.push_str("        double value = start.as.number + (double)i * step.as.number;\n");
+        code.push_str("        if ((step.as.number > 0 && value >= end.as.number) || (step.as.number < 0 && value <= end.as.number)) break;\n");
+        code.push_str("        array_push(result, (Value){VALUE_NUMBER, .as.number = value});\n");
+        code.push_str("    }\n");
+        code.push_str("    return result;\n");
+        code.push_str("}\n\n");
+        
+        // 遍历前先取出所有项，循环体中修改数组或字典不影响这次遍历；
+        // 字符串按 UTF-8 字符拆开
+        code.push_str("Value iteration_values(Value iterable) {\n");
+        code.push_str("    if (iterable.type == VALUE_MAP) return map_values(iterable);\n");
+        code.push_str("    Value items = create_array();\n");
+        code.push_str("    if (iterable.type == VALUE_ARRAY) {\n");
+        code.push_str("        for (int i = 0; i < iterable.as.array->count; i++) array_push(items, iterable.as.array->values[i]);\n");
+        code.push_str("    } else if (iterable.type == VALUE_STRING) {\n");
+        code.push_str("        const char* s = iterable.as.string;\n");
+        code.push_str("        while (*s) {\n");
+        code.push_str("            unsigned char lead = (unsigned char)*s;\n");
+        code.push_str("            int length = lead >= 0xF0 ? 4 : lead >= 0xE0 ? 3 : lead >= 0xC0 ? 2 : 1;\n");
+        code.push_str("            char* ch = malloc(length + 1);\n");
+        code.push_str("            memcpy(ch, s, length);\n");
+        code.push_str("            ch[length] = '\\0';\n");
+        code.push_str("            array_push(items, (Value){VALUE_STRING, .as.string = ch});\n");
+        code.push_str("            s += length;\n");
+        code.push_str("        }\n");
+        code.push_str("    } else {\n");
+        code.push_str("        runtime_error(\"E0209\", \"只能遍历字符串、数组或字典\");\n");
+        code.push_str("    }\n");
+        code.push_str("    return items;\n");
+        code.push_str("}\n\n");
+        
+        // 字典给出键，字符串和数组给出从 0 开始的下标
+        code.push_str("Value iteration_keys(Value iterable) {\n");
+        code.push_str("    if (iterable.type == VALUE_MAP) return map_keys(iterable);\n");
+        code.push_str("    int count = iteration_values(iterable).as.array->count;\n");
+        code.push_str("    return make_range((Value){VALUE_NUMBER, .as.number = 0}, (Value){VALUE_NUMBER, .as.number = count}, (Value){VALUE_NUMBER, .as.number = 1});\n");
+        code.push_str("}\n\n");
+        
+        // 只有一个循环变量时，字典给出键，字符串和数组给出值
+        code.push_str("Value iteration_items(Value iterable) {\n");
+        code.push_str("    if (iterable.type == VALUE_MAP) return map_keys(iterable);\n");
+        code.push_str("    return iteration_values(iterable);\n");
+        code.push_str("}\n\n");
+        
+        // 错误处理：没有尝试语句时输出错误并结束程序
+        code.push_str("void raise_error(Value error) {\n");
+        code.push_str("    if (try_depth == 0) {\n");
+        code.push_str("        fflush(stdout);\n");
+        code.push_str("        fprintf(stderr, \"错误[%s]: %s\\n\", error.as.error->kind, error.as.error->message);\n");
+        code.push_str("        exit(1);\n");
+        code.push_str("    }\n");
+        code.push_str("    current_error = error;\n");
+        code.push_str("    longjmp(try_stack[--try_depth], 1);\n");
+        code.push_str("}\n\n");
+        
+        code.push_str("Value make_error(const char* kind, char* message, int line, int column, Value value) {\n");
+        code.push_str("    ErrorObject* error = malloc(sizeof(ErrorObject));\n");
+        code.push_str("    error->kind = kind;\n");
+        code.push_str("    error->message = message;\n");
+        code.push_str("    error->line = line;\n");
+        code.push_str("    error->column = column;\n");
+        code.push_str("    error->value = value;\n");
+        code.push_str("    return (Value){VALUE_ERROR, .as.error = error};\n");
+        code.push_str("}\n\n");
+        
+        code.push_str("char* value_to_text(Value value) {\n");
+        code.push_str("    if (value.type == VALUE_STRING) return value.as.string;\n");
+        code.push_str("    return display_text(value);\n");
+        code.push_str("}\n\n");
+        
+        // 重新抛出捕获到的错误时保留原来的种类和位置
+        code.push_str("void throw_value(Value value, int line, int column) {\n");
+        code.push_str("    if (value.type == VALUE_ERROR) raise_error(value);\n");
+        code.push_str("    raise_error(make_error(\"E0213\", value_to_text(value), line, column, value));\n");
+        code.push_str("}\n\n");
+        
+        code.push_str("void runtime_error(const char* kind, const char* message) {\n");
+        code.push_str("    raise_error(make_error(kind, (char*)message, 0, 0, (Value){VALUE_NULL}));\n");
+        code.push_str("}\n\n");
+        
+        code.push_str("Value member_get(Value object, const char* name) {\n");
+        code.push_str("    if (object.type != VALUE_ERROR) return (Value){VALUE_NULL};\n");
+        code.push_str("    ErrorObject* error = object.as.error;\n");
+        code.push_str("    if (strcmp(name, \"消息\") == 0) return (Value){VALUE_STRING, .as.string = error->message};\n");
+        code.push_str("    if (strcmp(name, \"种类\") == 0) return (Value){VALUE_STRING, .as.string = (char*)error->kind};\n");
+        code.push_str("    if (strcmp(name, \"行\") == 0) return error->line ? (Value){VALUE_NUMBER, .as.number = error->line} : (Value){VALUE_NULL};\n");
+        code.push_str("    if (strcmp(name, \"列\") == 0) return error->column ? (Value){VALUE_NUMBER, .as.number = error->column} : (Value){VALUE_NULL};\n");
+        code.push_str("    if (strcmp(name, \"值\") == 0) return error->value;\n");
+        code.push_str("    return (Value){VALUE_NULL};\n");
         code.push_str("}\n\n");
         
         code.push_str("// 辅助函数\n");
@@ -494,31 +1428,48 @@ impl CodeGenerator {
         code.push_str("        case VALUE_BOOLEAN: return value.as.boolean;\n");
         code.push_str("        case VALUE_NUMBER: return value.as.number != 0;\n");
         code.push_str("        case VALUE_STRING: return strlen(value.as.string) > 0;\n");
-        code.push_str("        case VALUE_ARRAY: return value.as.array.count > 0;\n");
-        code.push_str("        default: return false;\n");
+        code.push_str("        case VALUE_NULL: return false;\n");
+        code.push_str("        default: return true;\n");
         code.push_str("    }\n");
         code.push_str("}\n\n");
         
+        // 与解释器一致，只有数字可以比较大小
         code.push_str("int compare_values(Value a, Value b) {\n");
-        code.push_str("    if (a.type != b.type) return 0;\n");
+        code.push_str("    if (a.type != VALUE_NUMBER || b.type != VALUE_NUMBER) runtime_error(\"E0204\", \"类型不匹配: 无法比较\");\n");
+        code.push_str("    return (a.as.number > b.as.number) - (a.as.number < b.as.number);\n");
+        code.push_str("}\n\n");
+        
+        // 与解释器的 is_equal 一致：不同类型的值永远不相等
+        code.push_str("bool values_equal(Value a, Value b) {\n");
+        code.push_str("    if (a.type != b.type) return false;\n");
         code.push_str("    switch (a.type) {\n");
-        code.push_str("        case VALUE_NUMBER: return (a.as.number > b.as.number) - (a.as.number < b.as.number);\n");
-        code.push_str("        case VALUE_STRING: return strcmp(a.as.string, b.as.string);\n");
-        code.push_str("        case VALUE_BOOLEAN: return (a.as.boolean > b.as.boolean) - (a.as.boolean < b.as.boolean);\n");
-        code.push_str("        default: return 0;\n");
+        code.push_str("        case VALUE_NUMBER: return a.as.number == b.as.number;\n");
+        code.push_str("        case VALUE_STRING: return strcmp(a.as.string, b.as.string) == 0;\n");
+        code.push_str("        case VALUE_BOOLEAN: return a.as.boolean == b.as.boolean;\n");
+        code.push_str("        case VALUE_ARRAY: return a.as.array == b.as.array;\n");
+        code.push_str("        case VALUE_MAP: return a.as.map == b.as.map;\n");
+        code.push_str("        case VALUE_ERROR: return a.as.error == b.as.error;\n");
+        code.push_str("        case VALUE_NULL: return true;\n");
+        code.push_str("        default: return false;\n");
         code.push_str("    }\n");
         code.push_str("}\n\n");
         
+        code.push_str("char* concat_strings(const char* a, const char* b) {\n");
+        code.push_str("    char* result = malloc(strlen(a) + strlen(b) + 1);\n");
+        code.push_str("    strcpy(result, a);\n");
+        code.push_str("    strcat(result, b);\n");
+        code.push_str("    return result;\n");
+        code.push_str("}\n\n");
+        
         code.push_str("Value add_values(Value a, Value b) {\n");
         code.push_str("    if (a.type == VALUE_NUMBER && b.type == VALUE_NUMBER) {\n");
         code.push_str("        return (Value){VALUE_NUMBER, .as.number = a.as.number + b.as.number};\n");
         code.push_str("    }\n");
-        code.push_str("    if (a.type == VALUE_STRING && b.type == VALUE_STRING) {\n");
-        code.push_str("        char* result = malloc(strlen(a.as.string) + strlen(b.as.string) + 1);\n");
-        code.push_str("        strcpy(result, a.as.string);\n");
-        code.push_str("        strcat(result, b.as.string);\n");
-        code.push_str("        return (Value){VALUE_STRING, .as.string = result};\n");
+        // 字符串与错误以外的值相加时，按显示的文本拼接
+        code.push_str("    if ((a.type == VALUE_STRING && b.type != VALUE_ERROR) || (b.type == VALUE_STRING && a.type != VALUE_ERROR)) {\n");
+        code.push_str("        return (Value){VALUE_STRING, .as.string = concat_strings(value_to_text(a), value_to_text(b))};\n");
         code.push_str("    }\n");
+        code.push_str("    runtime_error(\"E0204\", \"类型不匹配: 无法相加\");\n");
         code.push_str("    return (Value){VALUE_NULL};\n");
         code.push_str("}\n\n");
         
@@ -526,6 +1477,7 @@ impl CodeGenerator {
         code.push_str("    if (a.type == VALUE_NUMBER && b.type == VALUE_NUMBER) {\n");
         code.push_str("        return (Value){VALUE_NUMBER, .as.number = a.as.number - b.as.number};\n");
         code.push_str("    }\n");
+        code.push_str("    runtime_error(\"E0204\", \"类型不匹配: 无法相减\");\n");
         code.push_str("    return (Value){VALUE_NULL};\n");
         code.push_str("}\n\n");
         
@@ -533,17 +1485,24 @@ impl CodeGenerator {
         code.push_str("    if (a.type == VALUE_NUMBER && b.type == VALUE_NUMBER) {\n");
         code.push_str("        return (Value){VALUE_NUMBER, .as.number = a.as.number * b.as.number};\n");
         code.push_str("    }\n");
+        code.push_str("    runtime_error(\"E0204\", \"类型不匹配: 无法相乘\");\n");
         code.push_str("    return (Value){VALUE_NULL};\n");
         code.push_str("}\n\n");
         
         code.push_str("Value divide_values(Value a, Value b) {\n");
         code.push_str("    if (a.type == VALUE_NUMBER && b.type == VALUE_NUMBER) {\n");
-        code.push_str("        if (b.as.number == 0) return (Value){VALUE_NULL};\n");
+        code.push_str("        if (b.as.number == 0) runtime_error(\"E0205\", \"除零错误\");\n");
         code.push_str("        return (Value){VALUE_NUMBER, .as.number = a.as.number / b.as.number};\n");
         code.push_str("    }\n");
+        code.push_str("    runtime_error(\"E0204\", \"类型不匹配: 无法相除\");\n");
         code.push_str("    return (Value){VALUE_NULL};\n");
         code.push_str("}\n\n");
         
+        code.push_str("double divide_numbers(double a, double b) {\n");
+        code.push_str("    if (b == 0) runtime_error(\"E0205\", \"除零错误\");\n");
+        code.push_str("    return a / b;\n");
+        code.push_str("}\n\n");
+        
         code.push_str("Value negate_value(Value value) {\n");
         code.push_str("    if (value.type == VALUE_NUMBER) {\n");
         code.push_str("        return (Value){VALUE_NUMBER, .as.number = -value.as.number};\n");
@@ -553,4 +1512,151 @@ impl CodeGenerator {
         
         code
     }
-}
\ No newline at end of file
+}
+
+/// 数字、字符串和布尔值不装箱，直接用 C 的类型表示
+/// 语句中有没有尝试语句，包括嵌套的代码块中的
+fn contains_try<'a>(statements: impl IntoIterator<Item = &'a Statement>) -> bool {
+    statements.into_iter().any(|statement| match statement {
+        Statement::尝试语句 { .. } => true,
+        Statement::如果语句 { 真分支, 假分支, .. } => contains_try(真分支) || contains_try(假分支),
+        Statement::循环语句 { 体, .. } | Statement::当语句 { 体, .. } | Statement::对于语句 { 体, .. } => contains_try(体),
+        _ => false,
+    })
+}
+
+fn is_unboxed(ty: Type) -> bool {
+    matches!(ty, Type::数字 | Type::字符串 | Type::布尔值)
+}
+
+/// 类型在 C 中的写法，类型不确定时是 Value
+fn c_type(ty: Option<Type>) -> &'static str {
+    match ty {
+        Some(Type::数字) => "double",
+        Some(Type::字符串) => "char*",
+        Some(Type::布尔值) => "bool",
+        _ => "Value",
+    }
+}
+
+/// 类型在运行时的 ValueType
+fn value_type(ty: Type) -> &'static str {
+    match ty {
+        Type::数字 => "VALUE_NUMBER",
+        Type::字符串 => "VALUE_STRING",
+        Type::布尔值 => "VALUE_BOOLEAN",
+        Type::数组 => "VALUE_ARRAY",
+        Type::字典 => "VALUE_MAP",
+        Type::空 => "VALUE_NULL",
+    }
+}
+
+/// 函数出错后用来满足 C 编译器的返回值，不会被使用
+fn default_value(ty: Option<Type>) -> &'static str {
+    match c_type(ty) {
+        "double" => "0",
+        "char*" => "\"\"",
+        "bool" => "false",
+        _ => "(Value){VALUE_NULL}",
+    }
+}
+
+/// 全局变量在模块初始化之前的值
+fn global_default(ty: Option<Type>) -> &'static str {
+    match c_type(ty) {
+        "Value" => "{VALUE_NULL}",
+        _ => default_value(ty),
+    }
+}
+
+/// 全局常量的初始值是数字或布尔值字面量、并且与类型标注相符时，它在 C 全局变量声明中的初始值；
+/// 负数字面量解析为 `0 - 字面量`
+fn constant_initializer(value: Option<&Expression>, ty: Option<Type>) -> Option<String> {
+    let (code, from, field) = match value? {
+        Expression::数字字面量(n) => (format!("{:?}", n), Type::数字, "number"),
+        Expression::二元运算 { 左, 运算符, 右, .. } if 运算符 == "-" => match (左.as_ref(), 右.as_ref()) {
+            (Expression::数字字面量(zero), Expression::数字字面量(n)) if *zero == 0.0 => {
+                (format!("{:?}", -n), Type::数字, "number")
+            }
+            _ => return None,
+        },
+        Expression::布尔字面量(b) => (b.to_string(), Type::布尔值, "boolean"),
+        _ => return None,
+    };
+    match ty {
+        None => Some(format!("{{{}, .as.{} = {}}}", value_type(from), field, code)),
+        Some(ty) if ty == from => Some(code),
+        _ => None,
+    }
+}
+
+/// 把 `ty` 类型的代码装箱为 Value
+fn box_value(code: String, ty: Option<Type>) -> String {
+    match ty {
+        Some(Type::数字) => format!("(Value){{VALUE_NUMBER, .as.number = {}}}", code),
+        Some(Type::字符串) => format!("(Value){{VALUE_STRING, .as.string = {}}}", code),
+        Some(Type::布尔值) => format!("(Value){{VALUE_BOOLEAN, .as.boolean = {}}}", code),
+        _ => code,
+    }
+}
+
+/// 把 `from` 类型的代码转换成 `to` 类型的代码；不能确定类型相同时在运行时检查，
+/// 不符时报 E0217，`what` 是错误信息中被检查的东西
+fn convert(code: String, from: Option<Type>, to: Option<Type>, what: &str) -> String {
+    let Some(to) = to else {
+        return box_value(code, from);
+    };
+    if from == Some(to) {
+        return code;
+    }
+    let checked = format!(
+        "expect_type({}, {}, \"{}\", {})",
+        box_value(code, from),
+        value_type(to),
+        to,
+        c_string_literal(what)
+    );
+    match to {
+        Type::数字 => format!("{}.as.number", checked),
+        Type::字符串 => format!("{}.as.string", checked),
+        Type::布尔值 => format!("{}.as.boolean", checked),
+        _ => checked,
+    }
+}
+
+/// 值的真假，与 is_truthy 一致
+fn truthy(code: String, ty: Option<Type>) -> String {
+    match ty {
+        Some(Type::布尔值) => code,
+        Some(Type::数字) => format!("({} != 0)", code),
+        Some(Type::字符串) => format!("({}[0] != '\\0')", code),
+        _ => format!("is_truthy({})", code),
+    }
+}
+
+/// 字符串拼接时值的文本，与 value_to_text 一致
+fn text(code: String, ty: Option<Type>) -> String {
+    match ty {
+        Some(Type::字符串) => code,
+        _ => format!("value_to_text({})", box_value(code, ty)),
+    }
+}
+
+/// C 字符串字面量，转义引号、反斜杠和控制字符；UTF-8 字符原样保留
+fn c_string_literal(s: &str) -> String {
+    let mut literal = String::from("\"");
+    for ch in s.chars() {
+        match ch {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\t' => literal.push_str("\\t"),
+            '\r' => literal.push_str("\\r"),
+            // 八进制转义固定三位，不会吞掉后面的数字
+            ch if ch.is_ascii_control() => literal.push_str(&format!("\\{:03o}", ch as u8)),
+            ch => literal.push(ch),
+        }
+    }
+    literal.push('"');
+    literal
+}
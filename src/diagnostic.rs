@@ -0,0 +1,262 @@
+//! 词法分析、语法分析和解释执行共用的诊断信息
+//!
+//! 错误代码按阶段划分：
+//! - `E00xx` 词法错误
+//! - `E01xx` 语法错误
+//! - `E02xx` 运行时错误，执行前静态检查能发现的同类错误使用相同代码
+//! - `E03xx` 代码生成错误
+//! - `W00xx` 词法警告
+//! - `W01xx` 静态检查警告
+
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+
+/// 源代码中的一段位置，行列号从 1 开始，长度按字符计
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
+}
+
+impl Span {
+    pub fn new(line: usize, column: usize, length: usize) -> Self {
+        Span { line, column, length }
+    }
+}
+
+/// 一个源文件的名字和内容，用来显示导入的模块中的错误
+#[derive(Debug)]
+pub struct SourceFile {
+    pub name: String,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    错误,
+    警告,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::错误 => write!(f, "错误"),
+            Severity::警告 => write!(f, "警告"),
+        }
+    }
+}
+
+/// 附加在诊断信息上的次要位置说明
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    pub span: Option<Span>,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    /// 位置所在的文件；为空时位置属于正在运行或编译的文件
+    pub source: Option<Rc<SourceFile>>,
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: impl Into<String>) -> Self {
+        Diagnostic {
+            code,
+            severity: Severity::错误,
+            message: message.into(),
+            span: None,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            source: None,
+        }
+    }
+    
+    pub fn warning(code: &'static str, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity: Severity::警告,
+            ..Diagnostic::error(code, message)
+        }
+    }
+    
+    /// 设置主要位置；已经有位置的诊断保持不变，这样内层更精确的位置不会被外层覆盖
+    pub fn with_span(mut self, span: Span) -> Self {
+        if self.span.is_none() {
+            self.span = Some(span);
+        }
+        self
+    }
+    
+    /// 设置位置所在的文件，和 `with_span` 一样不覆盖已有的文件
+    pub fn with_source(mut self, source: Option<Rc<SourceFile>>) -> Self {
+        if self.source.is_none() {
+            self.source = source;
+        }
+        self
+    }
+    
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+        });
+        self
+    }
+    
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+    
+    /// 按 rustc 的风格渲染诊断信息，带源代码行和下划线；
+    /// 诊断属于其它文件时使用那个文件，忽略 `source` 和 `filename`
+    pub fn render(&self, source: &str, filename: &str) -> String {
+        let (source, filename) = match &self.source {
+            Some(file) => (file.text.as_str(), file.name.as_str()),
+            None => (source, filename),
+        };
+        let mut out = format!("{}[{}]: {}\n", self.severity, self.code, self.message);
+        
+        let lines: Vec<&str> = source.lines().collect();
+        let mut marks: Vec<(Span, char, &str)> = Vec::new();
+        if let Some(span) = self.span {
+            marks.push((span, '^', ""));
+        }
+        for label in &self.labels {
+            marks.push((label.span, '-', &label.message));
+        }
+        marks.sort_by_key(|(span, _, _)| (span.line, span.column));
+        
+        let gutter = marks
+            .iter()
+            .map(|(span, _, _)| span.line.to_string().len())
+            .max()
+            .unwrap_or(1);
+        let pad = " ".repeat(gutter);
+        
+        match self.span.or_else(|| marks.first().map(|(span, _, _)| *span)) {
+            Some(span) => out.push_str(&format!("{}--> {}:{}:{}\n", pad, filename, span.line, span.column)),
+            None => out.push_str(&format!("{}--> {}\n", pad, filename)),
+        }
+        
+        if !marks.is_empty() {
+            out.push_str(&format!("{} |\n", pad));
+        }
+        // 同一行上的位置共用一份源代码行，下划线画在同一行中
+        let mut line_numbers: Vec<usize> = marks.iter().map(|(span, _, _)| span.line).collect();
+        line_numbers.dedup();
+        for line in line_numbers {
+            let Some(text) = line.checked_sub(1).and_then(|i| lines.get(i)) else {
+                continue;
+            };
+            out.push_str(&format!("{:>width$} | {}\n", line, expand_tabs(text), width = gutter));
+            
+            let on_line: Vec<_> = marks.iter().filter(|(span, _, _)| span.line == line).collect();
+            let mut underline: Vec<char> = Vec::new();
+            let mut messages = Vec::new();
+            for (span, marker, message) in &on_line {
+                let (start, width) = underline_range(text, *span);
+                if underline.len() < start + width {
+                    underline.resize(start + width, ' ');
+                }
+                // 主要位置的 ^ 不被重叠的次要位置覆盖
+                for cell in &mut underline[start..start + width] {
+                    if *cell != '^' {
+                        *cell = *marker;
+                    }
+                }
+                if !message.is_empty() {
+                    messages.push((start, *message));
+                }
+            }
+            
+            // 最右边的说明跟在下划线后面，其余的说明各占一行，对齐到自己的位置
+            let mut mark_line = format!("{} | {}", pad, underline.iter().collect::<String>());
+            let rightmost = on_line.iter().map(|(span, _, _)| underline_range(text, *span).0).max();
+            if messages.last().map(|(start, _)| *start) == rightmost {
+                if let Some((_, message)) = messages.pop() {
+                    mark_line.push(' ');
+                    mark_line.push_str(message);
+                }
+            }
+            out.push_str(mark_line.trim_end());
+            out.push('\n');
+            for (start, message) in messages.into_iter().rev() {
+                out.push_str(&format!("{} | {}{}\n", pad, " ".repeat(start), message));
+            }
+        }
+        
+        if !self.notes.is_empty() {
+            out.push_str(&format!("{} |\n", pad));
+        }
+        for note in &self.notes {
+            out.push_str(&format!("{} = 注: {}\n", pad, note));
+        }
+        
+        out
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.span {
+            Some(span) => write!(f, "{} (行: {}, 列: {})", self.message, span.line, span.column),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+const TAB_WIDTH: usize = 4;
+
+/// 位置在源代码行中的显示列（从 0 开始）和下划线的宽度
+fn underline_range(text: &str, span: Span) -> (usize, usize) {
+    let prefix: String = text.chars().take(span.column.saturating_sub(1)).collect();
+    let marked: String = text
+        .chars()
+        .skip(span.column.saturating_sub(1))
+        .take(span.length.max(1))
+        .collect();
+    (display_width(&prefix), display_width(&marked).max(1))
+}
+
+fn expand_tabs(text: &str) -> String {
+    text.replace('\t', &" ".repeat(TAB_WIDTH))
+}
+
+/// 终端中的显示宽度：中日韩文字和全角符号占两列
+fn display_width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+fn char_width(c: char) -> usize {
+    if c == '\t' {
+        return TAB_WIDTH;
+    }
+    let code = c as u32;
+    let wide = (0x1100..=0x115F).contains(&code) || // 谚文字母
+        (0x2E80..=0x303E).contains(&code) || // CJK部首、标点
+        (0x3041..=0x33FF).contains(&code) || // 假名、CJK符号
+        (0x3400..=0x4DBF).contains(&code) || // CJK扩展A
+        (0x4E00..=0x9FFF).contains(&code) || // CJK统一汉字
+        (0xA000..=0xA4CF).contains(&code) || // 彝文
+        (0xAC00..=0xD7A3).contains(&code) || // 谚文音节
+        (0xF900..=0xFAFF).contains(&code) || // CJK兼容汉字
+        (0xFE30..=0xFE4F).contains(&code) || // CJK兼容形式
+        (0xFF00..=0xFF60).contains(&code) || // 全角符号
+        (0xFFE0..=0xFFE6).contains(&code) ||
+        (0x20000..=0x2FFFD).contains(&code) || // CJK扩展B及以后
+        (0x30000..=0x3FFFD).contains(&code);
+    if wide {
+        2
+    } else {
+        1
+    }
+}
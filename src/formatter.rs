@@ -0,0 +1,396 @@
+//! 源代码格式化：按统一的缩进和最少的括号重新输出语法树
+//!
+//! 注释和空行来自保留注释解析出的程序（见 `Parser::with_comments`）。连续的空行合并为一行，
+//! 代码块开头和结尾的空行去掉。`变量` 写成 `让`，`类` 写成 `结构`，`!` 写成 `非`。
+
+use crate::ast::{Expression, Program, Statement, Type};
+use crate::lexer::{Lexer, Token, TokenType};
+
+const INDENT: &str = "    ";
+
+// 运算的优先级，数字越大结合越紧，与解析器的层次一致
+const ASSIGNMENT: u8 = 1;
+const LOGICAL: u8 = 2;
+const EQUALITY: u8 = 3;
+const COMPARISON: u8 = 4;
+const RANGE: u8 = 5;
+const TERM: u8 = 6;
+const FACTOR: u8 = 7;
+const UNARY: u8 = 8;
+const POSTFIX: u8 = 9;
+
+/// 格式化整个程序，结果以换行结尾
+pub fn format(program: &Program) -> String {
+    let mut printer = Printer::new(0);
+    printer.statements(&program.语句);
+    printer.out
+}
+
+/// 参数列表和返回类型，如 `(a: 数字, b) -> 数字`，用于函数声明和悬停提示
+pub fn parameters(names: &[String], types: &[Option<Type>], returns: Option<Type>) -> String {
+    let parameters: Vec<String> =
+        names.iter().enumerate().map(|(i, name)| annotated(name, types.get(i).copied().flatten())).collect();
+    match returns {
+        Some(ty) => format!("({}) -> {}", parameters.join(", "), ty),
+        None => format!("({})", parameters.join(", ")),
+    }
+}
+
+/// 带可选类型标注的名字，如 `年龄: 数字`
+pub fn annotated(name: &str, ty: Option<Type>) -> String {
+    match ty {
+        Some(ty) => format!("{}: {}", name, ty),
+        None => name.to_string(),
+    }
+}
+
+struct Printer {
+    out: String,
+    depth: usize,
+    /// 遇到了空行，等到输出下一行时再补上，这样代码块结尾的空行被丢弃
+    blank: bool,
+    /// 还没有输出任何行，这里的空行被丢弃
+    at_start: bool,
+    /// 最后一行已经带有行尾注释
+    commented: bool,
+}
+
+impl Printer {
+    fn new(depth: usize) -> Self {
+        Printer {
+            out: String::new(),
+            depth,
+            blank: false,
+            at_start: true,
+            commented: false,
+        }
+    }
+    
+    /// 输出一行，`text` 中换行之后的部分已经带有缩进
+    fn line(&mut self, text: &str) {
+        if self.blank && !self.at_start {
+            self.out.push('\n');
+        }
+        self.blank = false;
+        self.at_start = false;
+        self.commented = false;
+        self.out.push_str(&INDENT.repeat(self.depth));
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+    
+    fn statements(&mut self, statements: &[Statement]) {
+        for (i, statement) in statements.iter().enumerate() {
+            match statement {
+                // 后面还有语句时，没有值的 返回 要用分号结束，否则下一行会被当成返回值
+                Statement::返回语句 { 值: None, .. } if statements[i + 1..].iter().any(is_code) => self.line("返回;"),
+                _ => self.statement(statement),
+            }
+        }
+    }
+    
+    fn statement(&mut self, statement: &Statement) {
+        let text = match statement {
+            Statement::表达式语句(expr) => {
+                let text = self.expression(expr, 0);
+                // 以负号开头的语句会被当成上一行的减法
+                if text.starts_with('-') {
+                    format!("({})", text)
+                } else {
+                    text
+                }
+            }
+            Statement::变量声明 { 名字, 类型, 初始值, 是常量, 是公开, .. } => {
+                let keyword = if *是常量 { "常量" } else { "让" };
+                let name = annotated(名字, *类型);
+                match 初始值 {
+                    Some(value) => {
+                        format!("{}{} {} = {}", public_prefix(*是公开), keyword, name, self.expression(value, ASSIGNMENT))
+                    }
+                    None => format!("{}{} {}", public_prefix(*是公开), keyword, name),
+                }
+            }
+            Statement::函数声明 { 名字, 参数, 参数类型, 返回类型, 体, 是公开, .. } => {
+                let signature = parameters(参数, 参数类型, *返回类型);
+                self.block(&format!("{}函数 {}{}", public_prefix(*是公开), 名字, signature), 体)
+            }
+            Statement::结构声明 { 名字, 字段, 方法, 是公开, .. } => {
+                let head = format!("{}结构 {}", public_prefix(*是公开), 名字);
+                if 方法.is_empty() && 字段.is_empty() {
+                    format!("{} {{}}", head)
+                } else if 方法.is_empty() {
+                    format!("{} {{ {} }}", head, 字段.join(", "))
+                } else {
+                    // 字段写在第一行，后面是方法
+                    let mut body = Printer::new(self.depth + 1);
+                    if !字段.is_empty() {
+                        body.line(&字段.join(", "));
+                    }
+                    body.statements(方法);
+                    format!("{} {{\n{}{}}}", head, body.out, INDENT.repeat(self.depth))
+                }
+            }
+            Statement::导入语句 { 路径, 别名, .. } => {
+                let path = if is_identifier(路径) {
+                    路径.clone()
+                } else {
+                    string_literal(路径)
+                };
+                match 别名 {
+                    Some(alias) => format!("导入 {} 作为 {}", path, alias),
+                    None => format!("导入 {}", path),
+                }
+            }
+            Statement::如果语句 { 条件, 真分支, 假分支, .. } => self.if_chain(条件, 真分支, 假分支),
+            Statement::循环语句 { 条件, 体, .. } => match 条件 {
+                Some(condition) => self.block(&format!("循环 {}", self.condition(condition)), 体),
+                None => self.block("循环", 体),
+            },
+            Statement::当语句 { 条件, 体, .. } => self.block(&format!("当 {}", self.condition(条件)), 体),
+            Statement::对于语句 { 变量, 值变量, 可迭代, 体, .. } => {
+                let variables = match 值变量 {
+                    Some(second) => format!("({}, {})", 变量, second),
+                    None => 变量.clone(),
+                };
+                self.block(&format!("对于 {} 在 {}", variables, self.condition(可迭代)), 体)
+            }
+            Statement::尝试语句 { 体, 捕获变量, 捕获, 最终, .. } => {
+                let mut text = self.block("尝试", 体);
+                if let Some(handler) = 捕获 {
+                    let head = match 捕获变量 {
+                        Some((name, _)) => format!("捕获 ({})", name),
+                        None => "捕获".to_string(),
+                    };
+                    text.push(' ');
+                    text.push_str(&self.block(&head, handler));
+                }
+                if let Some(finally) = 最终 {
+                    text.push(' ');
+                    text.push_str(&self.block("最终", finally));
+                }
+                text
+            }
+            Statement::抛出语句 { 值, .. } => format!("抛出 {}", self.expression(值, 0)),
+            Statement::返回语句 { 值, .. } => match 值 {
+                Some(value) => format!("返回 {}", self.expression(value, 0)),
+                None => "返回".to_string(),
+            },
+            Statement::跳出语句 { .. } => "跳出".to_string(),
+            Statement::继续语句 { .. } => "继续".to_string(),
+            Statement::错误 { .. } => "<语法错误>".to_string(),
+            Statement::注释 { 内容, 行尾, .. } => {
+                let comment = format!("//{}", 内容.trim_end());
+                // 行尾注释接在上一行后面，上一行已经有注释时单独成行
+                if *行尾 && !self.at_start && !self.commented {
+                    self.out.pop();
+                    self.out.push(' ');
+                    self.out.push_str(&comment);
+                    self.out.push('\n');
+                    self.commented = true;
+                    return;
+                }
+                comment
+            }
+            Statement::空行 => {
+                self.blank = true;
+                return;
+            }
+        };
+        self.line(&text);
+    }
+    
+    /// `头部 { ... }`，没有语句时写成 `头部 {}`；结果的第一行不带缩进
+    fn block(&self, head: &str, statements: &[Statement]) -> String {
+        // 紧跟在左大括号后面的注释留在这一行
+        let (head_comment, statements) = match statements.split_first() {
+            Some((Statement::注释 { 内容, 行尾: true, .. }, rest)) => (format!(" //{}", 内容.trim_end()), rest),
+            _ => (String::new(), statements),
+        };
+        if head_comment.is_empty() && statements.iter().all(|statement| matches!(statement, Statement::空行)) {
+            return format!("{} {{}}", head);
+        }
+        
+        let mut body = Printer::new(self.depth + 1);
+        body.statements(statements);
+        format!("{} {{{}\n{}{}}}", head, head_comment, body.out, INDENT.repeat(self.depth))
+    }
+    
+    /// `如果 ... 否则 如果 ... 否则 ...`，只有一条 如果 语句的 否则 分支写成 `否则 如果`
+    fn if_chain(&self, condition: &Expression, then_branch: &[Statement], else_branch: &[Statement]) -> String {
+        let mut text = self.block(&format!("如果 {}", self.condition(condition)), then_branch);
+        match else_branch {
+            [] => {}
+            [Statement::如果语句 { 条件, 真分支, 假分支, .. }] => {
+                text.push_str(" 否则 ");
+                text.push_str(&self.if_chain(条件, 真分支, 假分支));
+            }
+            _ => {
+                text.push(' ');
+                text.push_str(&self.block("否则", else_branch));
+            }
+        }
+        text
+    }
+    
+    /// 代码块前面的条件或可迭代表达式，其中不在括号里的字典字面量需要给整个表达式加上括号
+    fn condition(&self, expr: &Expression) -> String {
+        let text = self.expression(expr, 0);
+        if has_bare_dictionary(expr) {
+            format!("({})", text)
+        } else {
+            text
+        }
+    }
+    
+    /// 所在位置要求的优先级低于 `min` 时加上括号
+    fn expression(&self, expr: &Expression, min: u8) -> String {
+        let text = match expr {
+            Expression::数字字面量(n) => n.to_string(),
+            Expression::字符串字面量(s) => string_literal(s),
+            Expression::布尔字面量(b) => if *b { "真" } else { "假" }.to_string(),
+            Expression::变量 { 名字, .. } => 名字.clone(),
+            Expression::自己 { .. } => "自己".to_string(),
+            Expression::二元运算 { 右, .. } if is_negation(expr) => format!("-{}", self.expression(右, UNARY)),
+            Expression::二元运算 { 左, 运算符, 右, .. } => {
+                let level = precedence(expr);
+                let operator = match 运算符.as_str() {
+                    "&&" => "且",
+                    "||" => "或",
+                    other => other,
+                };
+                // 二元运算都是左结合的，右边同级的运算要加括号
+                format!("{} {} {}", self.expression(左, level), operator, self.expression(右, level + 1))
+            }
+            Expression::一元运算 { 操作数, .. } => format!("非 {}", self.expression(操作数, UNARY)),
+            Expression::函数调用 { 函数, 参数, .. } => {
+                format!("{}({})", self.expression(函数, POSTFIX), self.arguments(参数))
+            }
+            Expression::匿名函数 { 参数, 参数类型, 返回类型, 体, .. } => {
+                self.block(&format!("函数{}", parameters(参数, 参数类型, *返回类型)), 体)
+            }
+            Expression::赋值 { 变量名, 值, .. } => format!("{} = {}", 变量名, self.expression(值, ASSIGNMENT)),
+            Expression::索引赋值 { 数组, 索引, 值, .. } => format!(
+                "{}[{}] = {}",
+                self.expression(数组, POSTFIX),
+                self.expression(索引, 0),
+                self.expression(值, ASSIGNMENT)
+            ),
+            Expression::数组字面量(元素) => format!("[{}]", self.arguments(元素)),
+            // 范围的两端都是加减法一级的表达式
+            Expression::范围 { 起, 止, .. } => format!("{}..{}", self.expression(起, TERM), self.expression(止, TERM)),
+            Expression::数组索引 { 数组, 索引, .. } => {
+                format!("{}[{}]", self.expression(数组, POSTFIX), self.expression(索引, 0))
+            }
+            Expression::字典字面量 { 条目, .. } => {
+                let entries: Vec<String> = 条目
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", self.expression(key, 0), self.expression(value, 0)))
+                    .collect();
+                format!("{{{}}}", entries.join(", "))
+            }
+            Expression::成员访问 { 对象, 成员, .. } => format!("{}.{}", self.expression(对象, POSTFIX), 成员),
+            Expression::成员赋值 { 对象, 成员, 值, .. } => {
+                format!("{}.{} = {}", self.expression(对象, POSTFIX), 成员, self.expression(值, ASSIGNMENT))
+            }
+            Expression::方法调用 { 对象, 方法, 参数, .. } => {
+                format!("{}.{}({})", self.expression(对象, POSTFIX), 方法, self.arguments(参数))
+            }
+        };
+        
+        if precedence(expr) < min {
+            format!("({})", text)
+        } else {
+            text
+        }
+    }
+    
+    fn arguments(&self, arguments: &[Expression]) -> String {
+        let arguments: Vec<String> = arguments.iter().map(|arg| self.expression(arg, 0)).collect();
+        arguments.join(", ")
+    }
+}
+
+fn precedence(expr: &Expression) -> u8 {
+    match expr {
+        Expression::赋值 { .. } | Expression::索引赋值 { .. } | Expression::成员赋值 { .. } => ASSIGNMENT,
+        Expression::二元运算 { .. } if is_negation(expr) => UNARY,
+        Expression::二元运算 { 运算符, .. } => match 运算符.as_str() {
+            "&&" | "||" => LOGICAL,
+            "==" | "!=" => EQUALITY,
+            ">" | ">=" | "<" | "<=" => COMPARISON,
+            "+" | "-" => TERM,
+            _ => FACTOR,
+        },
+        Expression::范围 { .. } => RANGE,
+        Expression::一元运算 { .. } => UNARY,
+        _ => POSTFIX,
+    }
+}
+
+/// 解析器把负号 `-x` 解析为 `0 - x`，两种写法得到相同的语法树，统一写成 `-x`
+/// 表达式中有不在括号、中括号或参数列表里的字典字面量；这样的表达式写在条件中时，
+/// 解析器会把字典的 `{` 当成代码块的开始
+pub fn has_bare_dictionary(expr: &Expression) -> bool {
+    match expr {
+        Expression::字典字面量 { .. } => true,
+        Expression::二元运算 { 左, 右, .. } => has_bare_dictionary(左) || has_bare_dictionary(右),
+        Expression::一元运算 { 操作数, .. } => has_bare_dictionary(操作数),
+        Expression::范围 { 起, 止, .. } => has_bare_dictionary(起) || has_bare_dictionary(止),
+        Expression::赋值 { 值, .. } => has_bare_dictionary(值),
+        Expression::索引赋值 { 数组, 值, .. } => has_bare_dictionary(数组) || has_bare_dictionary(值),
+        Expression::成员赋值 { 对象, 值, .. } => has_bare_dictionary(对象) || has_bare_dictionary(值),
+        Expression::函数调用 { 函数: object, .. }
+        | Expression::数组索引 { 数组: object, .. }
+        | Expression::成员访问 { 对象: object, .. }
+        | Expression::方法调用 { 对象: object, .. } => has_bare_dictionary(object),
+        Expression::数字字面量(_)
+        | Expression::字符串字面量(_)
+        | Expression::布尔字面量(_)
+        | Expression::变量 { .. }
+        | Expression::自己 { .. }
+        | Expression::匿名函数 { .. }
+        | Expression::数组字面量(_) => false,
+    }
+}
+
+fn is_negation(expr: &Expression) -> bool {
+    matches!(expr, Expression::二元运算 { 左, 运算符, .. }
+        if 运算符 == "-" && matches!(**左, Expression::数字字面量(n) if n == 0.0))
+}
+
+fn is_code(statement: &Statement) -> bool {
+    !matches!(statement, Statement::注释 { .. } | Statement::空行)
+}
+
+fn public_prefix(is_public: bool) -> &'static str {
+    if is_public {
+        "公开 "
+    } else {
+        ""
+    }
+}
+
+/// 带引号和转义的字符串字面量
+fn string_literal(text: &str) -> String {
+    let mut literal = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\t' => literal.push_str("\\t"),
+            '\r' => literal.push_str("\\r"),
+            _ => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// 导入路径是一个普通的名字时可以不写引号
+fn is_identifier(text: &str) -> bool {
+    matches!(
+        Lexer::new(text).tokenize().as_deref(),
+        Ok([Token { token_type: TokenType::标识符(name), .. }, _]) if name == text
+    )
+}
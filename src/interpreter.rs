@@ -1,31 +1,315 @@
-use crate::ast::{Program, Statement, Expression};
-use std::collections::HashMap;
+use crate::ast::{Program, Slot, Statement, Expression, Type};
+use crate::diagnostic::{Diagnostic, SourceFile, Span};
+use crate::formatter;
+use crate::module::{import_name, ModuleLoader};
+use crate::types::mismatch;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// 数组按引用共享：赋值和传参复制的是引用，通过任何一个引用修改都对其他引用可见
+pub type Array = Rc<RefCell<Vec<Value>>>;
+
+/// 字典和数组一样按引用共享
+pub type Dict = Rc<RefCell<Dictionary>>;
+
+/// 键为字符串的字典，遍历和输出时保持插入顺序
+#[derive(Debug, Default)]
+pub struct Dictionary {
+    entries: Vec<(String, Value)>,
+    /// 键在 `entries` 中的位置
+    positions: HashMap<String, usize>,
+}
+
+impl Dictionary {
+    fn get(&self, key: &str) -> Option<&Value> {
+        self.positions.get(key).map(|&i| &self.entries[i].1)
+    }
+    
+    /// 已有的键原地更新，不改变顺序
+    fn insert(&mut self, key: String, value: Value) {
+        match self.positions.get(&key) {
+            Some(&i) => self.entries[i].1 = value,
+            None => {
+                self.positions.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+    
+    fn remove(&mut self, key: &str) -> Option<Value> {
+        let i = self.positions.remove(key)?;
+        let (_, value) = self.entries.remove(i);
+        for position in self.positions.values_mut() {
+            if *position > i {
+                *position -= 1;
+            }
+        }
+        Some(value)
+    }
+    
+    fn contains(&self, key: &str) -> bool {
+        self.positions.contains_key(key)
+    }
+    
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+    
+    fn keys(&self) -> impl Iterator<Item = &String> {
+        self.entries.iter().map(|(key, _)| key)
+    }
+    
+    fn values(&self) -> impl Iterator<Item = &Value> {
+        self.entries.iter().map(|(_, value)| value)
+    }
+}
+
+/// 结构声明求值得到的类型，调用它来创建实例
+#[derive(Debug)]
+pub struct StructType {
+    name: String,
+    fields: Vec<String>,
+    /// 方法是在结构声明所在的作用域中定义的函数值，按声明顺序保存
+    methods: Vec<(String, Value)>,
+}
+
+impl StructType {
+    fn field_index(&self, name: &str) -> Option<usize> {
+        self.fields.iter().position(|field| field == name)
+    }
+    
+    fn method(&self, name: &str) -> Option<&Value> {
+        self.methods.iter().find(|(method, _)| method == name).map(|(_, function)| function)
+    }
+}
+
+/// 结构的实例，和数组一样按引用共享，字段的值按声明顺序保存
+#[derive(Debug)]
+pub struct Instance {
+    class: Rc<StructType>,
+    fields: Vec<Value>,
+}
+
+/// `尝试` 捕获到的错误，`捕获` 中绑定的就是这种值
+#[derive(Debug)]
+pub struct ErrorValue {
+    /// 运行时错误的诊断信息；`抛出` 的值使用 E0213，位置是 `抛出` 语句
+    diagnostic: Diagnostic,
+    /// `抛出` 的原始值，运行时错误为 空
+    value: Value,
+}
+
+impl ErrorValue {
+    /// 错误值可以访问的字段
+    const FIELDS: [&'static str; 5] = ["消息", "种类", "行", "列", "值"];
+    
+    fn field(&self, name: &str) -> Option<Value> {
+        let span = self.diagnostic.span;
+        match name {
+            "消息" => Some(Value::字符串(self.diagnostic.message.clone())),
+            "种类" => Some(Value::字符串(self.diagnostic.code.to_string())),
+            "行" => Some(span.map_or(Value::空, |span| Value::数字(span.line as f64))),
+            "列" => Some(span.map_or(Value::空, |span| Value::数字(span.column as f64))),
+            "值" => Some(self.value.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// `导入` 得到的模块，成员是模块作用域中带 `公开` 的绑定
+#[derive(Debug)]
+pub struct ModuleValue {
+    name: String,
+    /// 模块自己的全局作用域，模块执行完之后一直保留
+    scope: usize,
+    exports: Vec<String>,
+}
+
+/// 数组下标或字典键求值后的容器
+enum Collection {
+    数组(Array),
+    字典(Dict),
+}
+
+impl Collection {
+    fn value(&self) -> Value {
+        match self {
+            Collection::数组(array) => Value::数组(array.clone()),
+            Collection::字典(dict) => Value::字典(dict.clone()),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub enum Value {
     数字(f64),
     字符串(String),
     布尔(bool),
-    数组(Vec<Value>),
+    数组(Array),
+    字典(Dict),
+    结构(Rc<StructType>),
+    实例(Rc<RefCell<Instance>>),
+    错误(Rc<ErrorValue>),
+    模块(Rc<ModuleValue>),
+    /// 带类型标注的参数和返回值在调用时检查
     函数 {
         参数: Vec<String>,
-        体: Vec<Statement>,
+        参数类型: Vec<Option<Type>>,
+        返回类型: Option<Type>,
+        /// 复制函数值时共用同一个函数体，不复制语法树
+        体: Rc<[Statement]>,
         作用域: usize,
     },
     内置函数(String),
     空,
 }
 
+impl Value {
+    pub fn array(elements: Vec<Value>) -> Self {
+        Value::数组(Rc::new(RefCell::new(elements)))
+    }
+    
+    /// 把这个值引用的作用域加入 `scopes`，供垃圾回收标记；
+    /// `seen` 记录已经访问过的数组和字典，它们可以包含自己
+    fn trace(&self, scopes: &mut Vec<usize>, seen: &mut HashSet<*const ()>) {
+        match self {
+            Value::函数 { 作用域, .. } => scopes.push(*作用域),
+            Value::模块(module) => scopes.push(module.scope),
+            Value::数组(array) if seen.insert(Rc::as_ptr(array) as *const ()) => {
+                for element in array.borrow().iter() {
+                    element.trace(scopes, seen);
+                }
+            }
+            Value::字典(dict) if seen.insert(Rc::as_ptr(dict) as *const ()) => {
+                for value in dict.borrow().values() {
+                    value.trace(scopes, seen);
+                }
+            }
+            Value::结构(class) => {
+                for (_, method) in &class.methods {
+                    method.trace(scopes, seen);
+                }
+            }
+            Value::错误(error) => error.value.trace(scopes, seen),
+            Value::实例(instance) if seen.insert(Rc::as_ptr(instance) as *const ()) => {
+                let instance = instance.borrow();
+                Value::结构(instance.class.clone()).trace(scopes, seen);
+                for value in &instance.fields {
+                    value.trace(scopes, seen);
+                }
+            }
+            _ => {}
+        }
+    }
+    
+    /// `open` 是正在输出的外层数组、字典和实例，再次遇到时输出 `[...]` 或 `{...}`
+    fn write_nested(&self, f: &mut std::fmt::Formatter<'_>, open: &mut Vec<*const ()>) -> std::fmt::Result {
+        match self {
+            Value::数组(array) => {
+                let pointer = Rc::as_ptr(array) as *const ();
+                if open.contains(&pointer) {
+                    return write!(f, "[...]");
+                }
+                
+                open.push(pointer);
+                write!(f, "[")?;
+                for (i, element) in array.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    element.write_nested(f, open)?;
+                }
+                open.pop();
+                write!(f, "]")
+            }
+            Value::字典(dict) => {
+                let pointer = Rc::as_ptr(dict) as *const ();
+                if open.contains(&pointer) {
+                    return write!(f, "{{...}}");
+                }
+                
+                open.push(pointer);
+                write!(f, "{{")?;
+                for (i, (key, value)) in dict.borrow().entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "\"{}\": ", key)?;
+                    value.write_nested(f, open)?;
+                }
+                open.pop();
+                write!(f, "}}")
+            }
+            Value::实例(instance) => {
+                let pointer = Rc::as_ptr(instance) as *const ();
+                let instance = instance.borrow();
+                if open.contains(&pointer) {
+                    return write!(f, "{} {{...}}", instance.class.name);
+                }
+                if instance.fields.is_empty() {
+                    return write!(f, "{} {{}}", instance.class.name);
+                }
+                
+                open.push(pointer);
+                write!(f, "{} {{ ", instance.class.name)?;
+                for (i, (name, value)) in instance.class.fields.iter().zip(&instance.fields).enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: ", name)?;
+                    value.write_nested(f, open)?;
+                }
+                open.pop();
+                write!(f, " }}")
+            }
+            _ => write!(f, "{}", self),
+        }
+    }
+    
+    /// 值是否符合类型标注
+    pub fn has_type(&self, ty: Type) -> bool {
+        matches!(
+            (self, ty),
+            (Value::数字(_), Type::数字)
+                | (Value::字符串(_), Type::字符串)
+                | (Value::布尔(_), Type::布尔值)
+                | (Value::数组(_), Type::数组)
+                | (Value::字典(_), Type::字典)
+                | (Value::空, Type::空)
+        )
+    }
+    
+    /// 用于错误信息的类型名称，实例使用它的结构名
+    pub fn type_name(&self) -> String {
+        let name = match self {
+            Value::数字(_) => "数字",
+            Value::字符串(_) => "字符串",
+            Value::布尔(_) => "布尔值",
+            Value::数组(_) => "数组",
+            Value::字典(_) => "字典",
+            Value::结构(_) => "结构",
+            Value::实例(instance) => return instance.borrow().class.name.clone(),
+            Value::错误(_) => "错误",
+            Value::模块(_) => "模块",
+            Value::函数 { .. } | Value::内置函数(_) => "函数",
+            Value::空 => "空",
+        };
+        name.to_string()
+    }
+}
+
 impl std::fmt::Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Value::数字(n) => write!(f, "{}", n),
             Value::字符串(s) => write!(f, "\"{}\"", s),
-            Value::布尔(b) => write!(f, "{}", b),
-            Value::数组(元素) => {
-                let elements: Vec<String> = 元素.iter().map(|elem| elem.to_string()).collect();
-                write!(f, "[{}]", elements.join(", "))
-            }
+            Value::布尔(b) => write!(f, "{}", if *b { "真" } else { "假" }),
+            Value::数组(_) | Value::字典(_) | Value::实例(_) => self.write_nested(f, &mut Vec::new()),
+            Value::结构(class) => write!(f, "[结构: {}]", class.name),
+            Value::错误(error) => write!(f, "错误[{}]: {}", error.diagnostic.code, error.diagnostic.message),
+            Value::模块(module) => write!(f, "[模块: {}]", module.name),
             Value::函数 { .. } => write!(f, "[函数]"),
             Value::内置函数(name) => write!(f, "[内置函数: {}]", name),
             Value::空 => write!(f, "空"),
@@ -33,41 +317,178 @@ impl std::fmt::Display for Value {
     }
 }
 
+/// 语句执行的非正常结束方式
+///
+/// `跳出`、`继续` 和 `返回` 沿调用栈向外传播，直到被所在的循环或函数调用接住，
+/// 携带的位置只用于报告它们出现在循环或函数之外的错误；
+/// `错误` 是运行时错误，`抛出` 是 `抛出` 语句产生的错误，
+/// 它们一直传播到外层的 `尝试` 语句，没有被捕获时传播到 `Interpreter::execute`。
+#[derive(Debug)]
+pub enum ControlFlow {
+    跳出(Span),
+    继续(Span),
+    返回(Value, Span),
+    错误(Box<Diagnostic>),
+    抛出(Rc<ErrorValue>),
+}
+
+impl From<Box<Diagnostic>> for ControlFlow {
+    fn from(error: Box<Diagnostic>) -> Self {
+        ControlFlow::错误(error)
+    }
+}
+
+impl From<Diagnostic> for ControlFlow {
+    fn from(error: Diagnostic) -> Self {
+        ControlFlow::错误(Box::new(error))
+    }
+}
+
+impl ControlFlow {
+    /// 能被 `尝试` 捕获的错误转换成错误值，其余的控制流原样返回；
+    /// `source` 是捕获它的代码所在的文件，错误还没有所属文件时就属于这个文件
+    fn into_caught(self, source: Option<Rc<SourceFile>>) -> Result<Rc<ErrorValue>, ControlFlow> {
+        match self {
+            ControlFlow::错误(diagnostic) => Ok(Rc::new(ErrorValue {
+                diagnostic: diagnostic.with_source(source),
+                value: Value::空,
+            })),
+            ControlFlow::抛出(error) => Ok(error),
+            other => Err(other),
+        }
+    }
+    
+    /// 把逃出函数体或程序顶层的控制流转换成运行时错误
+    fn into_error(self) -> Box<Diagnostic> {
+        let error = match self {
+            ControlFlow::错误(e) => return e,
+            ControlFlow::抛出(error) => error.diagnostic.clone(),
+            ControlFlow::返回(_, span) => Diagnostic::error("E0207", "返回语句只能在函数中使用").with_span(span),
+            ControlFlow::跳出(span) => Diagnostic::error("E0207", "跳出语句只能在循环中使用").with_span(span),
+            ControlFlow::继续(span) => Diagnostic::error("E0207", "继续语句只能在循环中使用").with_span(span),
+        };
+        Box::new(error)
+    }
+}
+
+/// 作用域中的一个绑定
+#[derive(Debug)]
+struct Binding {
+    value: Value,
+    mutable: bool,
+    /// 声明处的位置，内置函数和参数没有
+    declared_at: Option<Span>,
+    /// 类型标注，赋值时检查
+    annotation: Option<Type>,
+}
+
+/// 一个作用域
+///
+/// 每个函数调用和每个 `{}` 代码块都有自己的作用域。内层作用域可以声明与外层同名的变量
+/// 或常量来遮蔽外层的绑定；同一作用域中重复声明变量会覆盖旧值，重复声明常量则是错误。
+/// 绑定按第一次声明的顺序存放，下标与名字解析得到的槽位一致。
 #[derive(Debug)]
 pub struct Environment {
-    values: HashMap<String, Value>,
+    bindings: Vec<Binding>,
+    /// 名字在 `bindings` 中的下标
+    names: HashMap<String, usize>,
     parent: Option<usize>,
+    /// 有函数在这个作用域或它的下级作用域中定义，离开作用域后可能仍被引用
+    captured: bool,
 }
 
 impl Environment {
     pub fn new() -> Self {
         Environment {
-            values: HashMap::new(),
+            bindings: Vec::new(),
+            names: HashMap::new(),
             parent: None,
+            captured: false,
         }
     }
     
     pub fn with_parent(parent_id: usize) -> Self {
         Environment {
-            values: HashMap::new(),
             parent: Some(parent_id),
+            ..Environment::new()
+        }
+    }
+    
+    /// 同名的绑定已经存在时替换它，保留原来的下标
+    fn insert(&mut self, name: &str, binding: Binding) {
+        match self.names.get(name) {
+            Some(&index) => self.bindings[index] = binding,
+            None => {
+                self.names.insert(name.to_string(), self.bindings.len());
+                self.bindings.push(binding);
+            }
         }
     }
     
     pub fn define(&mut self, name: &str, value: Value) {
-        self.values.insert(name.to_string(), value);
+        self.insert(
+            name,
+            Binding {
+                value,
+                mutable: true,
+                declared_at: None,
+                annotation: None,
+            },
+        );
+    }
+    
+    /// 绑定带类型标注的参数，值已经检查过
+    fn define_typed(&mut self, name: &str, value: Value, annotation: Option<Type>) {
+        self.insert(
+            name,
+            Binding {
+                value,
+                mutable: true,
+                declared_at: None,
+                annotation,
+            },
+        );
+    }
+    
+    /// 声明一个变量或常量，同一作用域中已有同名常量时报错
+    pub fn declare(&mut self, name: &str, value: Value, mutable: bool, span: Span) -> Result<(), Box<Diagnostic>> {
+        self.declare_typed(name, value, mutable, None, span)
     }
     
-    pub fn get(&self, name: &str) -> Option<Value> {
-        if let Some(value) = self.values.get(name) {
-            return Some(value.clone());
+    /// 声明带类型标注的变量或常量，值已经检查过
+    fn declare_typed(
+        &mut self,
+        name: &str,
+        value: Value,
+        mutable: bool,
+        annotation: Option<Type>,
+        span: Span,
+    ) -> Result<(), Box<Diagnostic>> {
+        if let Some(existing) = self.names.get(name).map(|&index| &self.bindings[index]) {
+            if !existing.mutable {
+                let mut error = Diagnostic::error("E0211", format!("常量 {} 不能重复声明", name)).with_span(span);
+                if let Some(declared_at) = existing.declared_at {
+                    error = error.with_label(declared_at, "常量在这里声明");
+                }
+                return Err(error.into());
+            }
         }
-        None
+        
+        self.insert(
+            name,
+            Binding {
+                value,
+                mutable,
+                declared_at: Some(span),
+                annotation,
+            },
+        );
+        Ok(())
     }
     
     pub fn get_with_scope_chain(&self, name: &str, environments: &[Environment]) -> Option<Value> {
-        if let Some(value) = self.values.get(name) {
-            return Some(value.clone());
+        if let Some(&index) = self.names.get(name) {
+            return Some(self.bindings[index].value.clone());
         }
         
         if let Some(parent_id) = self.parent {
@@ -79,184 +500,691 @@ impl Environment {
         None
     }
     
-    pub fn assign(&mut self, name: &str, value: Value) -> Result<(), String> {
-        if self.values.contains_key(name) {
-            self.values.insert(name.to_string(), value);
-            Ok(())
-        } else {
-            Err(format!("未定义的变量: {}", name))
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.contains_key(name)
+    }
+    
+    pub fn assign(&mut self, name: &str, value: Value) -> Result<(), Box<Diagnostic>> {
+        match self.names.get(name) {
+            Some(&index) => self.assign_at(index, name, value),
+            None => Err(Diagnostic::error("E0200", format!("未定义的变量: {}", name)).into()),
+        }
+    }
+    
+    /// 给第 `index` 个绑定赋值，`name` 只用于错误信息
+    fn assign_at(&mut self, index: usize, name: &str, value: Value) -> Result<(), Box<Diagnostic>> {
+        let binding = &mut self.bindings[index];
+        if !binding.mutable {
+            let mut error = Diagnostic::error("E0210", format!("不能给常量 {} 赋值", name));
+            if let Some(declared_at) = binding.declared_at {
+                error = error.with_label(declared_at, "常量在这里声明");
+            }
+            return Err(error.into());
+        }
+        if let Some(annotation) = binding.annotation.filter(|annotation| !value.has_type(*annotation)) {
+            let mut error = mismatch(&format!("变量 {}", name), annotation, &value.type_name());
+            if let Some(declared_at) = binding.declared_at {
+                error = error.with_label(declared_at, "类型在这里声明");
+            }
+            return Err(error.into());
         }
+        binding.value = value;
+        Ok(())
+    }
+    
+    /// 按声明的顺序排列的名字
+    fn names_in_order(&self) -> Vec<&String> {
+        let mut names: Vec<(&String, usize)> = self.names.iter().map(|(name, &index)| (name, index)).collect();
+        names.sort_by_key(|&(_, index)| index);
+        names.into_iter().map(|(name, _)| name).collect()
     }
 }
 
+/// 某个作用域在某一时刻的全部绑定，供交互模式显示
+pub struct EnvironmentSnapshot {
+    pub id: usize,
+    pub parent: Option<usize>,
+    pub bindings: Vec<(String, Value)>,
+}
+
+/// 存活作用域数量低于这个值时不做垃圾回收
+const GC_MIN_THRESHOLD: usize = 1024;
+
+/// 解释器
+///
+/// 作用域存放在 `environments` 中，用下标互相引用。离开作用域时，没有被函数捕获的作用域
+/// 立即回收；被捕获的作用域要等到垃圾回收确认没有函数值再引用它们之后才回收。垃圾回收
+/// 在顶层语句之间、每次循环迭代开始和函数返回时进行，根是当前作用域、`scope_stack` 中
+/// 暂时离开的作用域和 `temporaries` 中还没有存入作用域的值。回收的下标记在 `free_envs`
+/// 中，供新的作用域复用。
 pub struct Interpreter {
     environments: Vec<Environment>,
-    globals: usize,
+    free_envs: Vec<usize>,
     current_env: usize,
+    /// 进入函数调用、代码块或模块之前所在的作用域，执行完后回到这些作用域
+    scope_stack: Vec<usize>,
+    /// 已经求出、还没有存入任何作用域的值，例如求值右边时的左边运算数
+    temporaries: Vec<Value>,
+    /// 存活作用域超过这个数量时，在下一个回收点做垃圾回收
+    gc_threshold: usize,
+    modules: ModuleLoader,
+    /// 正在执行的文件所在的目录，导入路径从这里开始查找
+    directory: PathBuf,
+    /// 已经执行过的模块，每个模块只执行一次
+    imported: HashMap<PathBuf, Rc<ModuleValue>>,
+    /// 模块的全局作用域对应的源文件，用来给模块中的错误标上文件
+    module_sources: HashMap<usize, Rc<SourceFile>>,
 }
 
 impl Interpreter {
     pub fn new() -> Self {
-        let mut env = Environment::new();
-        
-        // 添加内置函数
-        env.define("打印", Value::内置函数("内置打印".to_string()));
-        env.define("输入", Value::内置函数("内置输入".to_string()));
-        
         Interpreter {
-            environments: vec![env],
-            globals: 0,
+            environments: vec![global_environment()],
+            free_envs: Vec::new(),
             current_env: 0,
+            scope_stack: Vec::new(),
+            temporaries: Vec::new(),
+            gc_threshold: GC_MIN_THRESHOLD,
+            modules: ModuleLoader::new(),
+            directory: PathBuf::from("."),
+            imported: HashMap::new(),
+            module_sources: HashMap::new(),
         }
     }
     
-    pub fn execute(&mut self, program: &Program) -> Result<(), String> {
+    /// 在执行 `program` 之前加载它导入的模块，`directory` 是它所在的目录
+    pub fn load_imports(&mut self, program: &Program, directory: &Path) -> Result<(), Vec<Diagnostic>> {
+        self.directory = directory.to_path_buf();
+        self.modules.load_imports(program, directory, None)
+    }
+    
+    pub fn execute(&mut self, program: &Program) -> Result<(), Box<Diagnostic>> {
         for statement in &program.语句 {
-            self.execute_statement(statement)?;
+            self.execute_top_level(statement)?;
         }
         Ok(())
     }
     
-    fn execute_statement(&mut self, statement: &Statement) -> Result<Value, String> {
+    /// 执行一条顶层语句。顶层语句之间没有尚未存入作用域的临时值，可以安全地回收垃圾
+    fn execute_top_level(&mut self, statement: &Statement) -> Result<(), Box<Diagnostic>> {
+        let result = self.execute_statement(statement).map_err(ControlFlow::into_error);
+        self.maybe_collect_garbage(None);
+        result
+    }
+    
+    /// 交互模式下执行一段输入，返回最后一条表达式语句的值
+    pub fn execute_interactive(&mut self, program: &Program) -> Result<Option<Value>, Box<Diagnostic>> {
+        let Some((last, rest)) = program.语句.split_last() else {
+            return Ok(None);
+        };
+        
+        for statement in rest {
+            self.execute_top_level(statement)?;
+        }
+        
+        match last {
+            Statement::表达式语句(expr) => {
+                let value = self.evaluate_expression(expr).map_err(ControlFlow::into_error);
+                self.maybe_collect_garbage(value.as_ref().ok());
+                Ok(Some(value?))
+            }
+            _ => {
+                self.execute_top_level(last)?;
+                Ok(None)
+            }
+        }
+    }
+    
+    /// 全局作用域中的名字，按绑定的顺序排列，交互模式用它解析下一次输入
+    pub fn global_names(&self) -> Vec<String> {
+        self.environments[0].names_in_order().into_iter().cloned().collect()
+    }
+    
+    /// 列出所有作用域中的绑定，按作用域编号排列，内置函数除外
+    pub fn environments(&self) -> Vec<EnvironmentSnapshot> {
+        self.environments
+            .iter()
+            .enumerate()
+            .filter(|(id, _)| !self.free_envs.contains(id))
+            .map(|(id, env)| {
+                let mut bindings: Vec<(String, Value)> = env
+                    .names
+                    .iter()
+                    .map(|(name, &index)| (name, &env.bindings[index].value))
+                    .filter(|(_, value)| !matches!(value, Value::内置函数(_)))
+                    .map(|(name, value)| (name.clone(), value.clone()))
+                    .collect();
+                bindings.sort_by(|a, b| a.0.cmp(&b.0));
+                EnvironmentSnapshot {
+                    id,
+                    parent: env.parent,
+                    bindings,
+                }
+            })
+            .collect()
+    }
+    
+    fn execute_statement(&mut self, statement: &Statement) -> Result<(), ControlFlow> {
         match statement {
             Statement::表达式语句(expr) => {
                 self.evaluate_expression(expr)?;
-                Ok(Value::空)
+                Ok(())
             }
-            Statement::变量声明 { 名字, 初始值, 是常量: _ } => {
+            Statement::变量声明 { 名字, 类型, 初始值, 是常量, 位置, .. } => {
                 let value = match 初始值 {
                     Some(expr) => self.evaluate_expression(expr)?,
                     None => Value::空,
                 };
+                if let Some(annotation) = 类型.filter(|annotation| !value.has_type(*annotation)) {
+                    let kind = if *是常量 { "常量" } else { "变量" };
+                    let span = 初始值.as_ref().and_then(Expression::span).unwrap_or(*位置);
+                    let mut error = mismatch(&format!("{} {}", kind, 名字), annotation, &value.type_name()).with_span(span);
+                    if span != *位置 {
+                        error = error.with_label(*位置, "类型在这里声明");
+                    }
+                    return Err(error.into());
+                }
                 
                 let env = self.get_current_environment_mut();
-                env.define(名字, value);
-                Ok(Value::空)
+                env.declare_typed(名字, value, !是常量, *类型, *位置)?;
+                Ok(())
             }
-            Statement::函数声明 { 名字, 参数, 体 } => {
+            Statement::函数声明 { 名字, 参数, 参数类型, 返回类型, 体, 位置, .. } => {
                 let function = Value::函数 {
                     参数: 参数.clone(),
-                    体: 体.clone(),
+                    参数类型: 参数类型.clone(),
+                    返回类型: *返回类型,
+                    体: Rc::from(体.as_slice()),
                     作用域: self.current_env,
                 };
+                self.mark_captured(self.current_env);
                 
                 let env = self.get_current_environment_mut();
-                env.define(名字, function);
-                Ok(Value::空)
+                env.declare(名字, function, true, *位置)?;
+                Ok(())
+            }
+            Statement::结构声明 { 名字, 字段, 方法, 位置, .. } => {
+                let methods = 方法
+                    .iter()
+                    .filter_map(|method| match method {
+                        Statement::函数声明 { 名字, 参数, 参数类型, 返回类型, 体, .. } => Some((
+                            名字.clone(),
+                            Value::函数 {
+                                参数: 参数.clone(),
+                                参数类型: 参数类型.clone(),
+                                返回类型: *返回类型,
+                                体: Rc::from(体.as_slice()),
+                                作用域: self.current_env,
+                            },
+                        )),
+                        _ => None,
+                    })
+                    .collect();
+                self.mark_captured(self.current_env);
+                
+                let class = Value::结构(Rc::new(StructType {
+                    name: 名字.clone(),
+                    fields: 字段.clone(),
+                    methods,
+                }));
+                self.get_current_environment_mut().declare(名字, class, true, *位置)?;
+                Ok(())
             }
-            Statement::如果语句 { 条件, 真分支, 假分支 } => {
+            Statement::如果语句 { 条件, 真分支, 假分支, .. } => {
                 let condition_value = self.evaluate_expression(条件)?;
-                let should_execute = self.is_truthy(&condition_value);
                 
-                if should_execute {
-                    for stmt in 真分支 {
-                        self.execute_statement(stmt)?;
-                    }
+                if self.is_truthy(&condition_value) {
+                    self.execute_block(真分支)
                 } else {
-                    for stmt in 假分支 {
-                        self.execute_statement(stmt)?;
-                    }
+                    self.execute_block(假分支)
                 }
-                Ok(Value::空)
             }
-            Statement::循环语句 { 条件, 体 } => {
-                loop {
-                    if let Some(condition_expr) = 条件 {
-                        let condition_value = self.evaluate_expression(condition_expr)?;
-                        if !self.is_truthy(&condition_value) {
-                            break;
-                        }
-                    }
-                    
-                    for stmt in 体 {
-                        match self.execute_statement(stmt) {
-                            Ok(Value::空) => continue,
-                            Ok(_) => break,
-                            Err(e) if e == "跳出" => break,
-                            Err(e) if e == "继续" => continue,
-                            Err(e) => return Err(e),
-                        }
-                    }
+            Statement::循环语句 { 条件, 体, .. } => self.execute_loop(条件.as_ref(), 体),
+            Statement::当语句 { 条件, 体, .. } => self.execute_loop(Some(条件), 体),
+            Statement::对于语句 { 变量, 值变量, 可迭代, 体, 位置 } => {
+                // 范围按下标逐项算出，不生成整个数组
+                if let Some(bounds) = self.range_of(可迭代)? {
+                    let mut index = 0;
+                    return self.execute_for(变量, 值变量.as_deref(), false, 体, *位置, |_| {
+                        let value = range_item(bounds, index)?;
+                        index += 1;
+                        Some((Value::数字((index - 1) as f64), Value::数字(value)))
+                    });
                 }
-                Ok(Value::空)
-            }
-            Statement::当语句 { 条件, 体 } => {
-                let mut condition_value = self.evaluate_expression(条件)?;
-                while self.is_truthy(&condition_value) {
-                    condition_value = self.evaluate_expression(条件)?;
-                    for stmt in 体 {
-                        match self.execute_statement(stmt) {
-                            Ok(Value::空) => continue,
-                            Ok(_) => break,
-                            Err(e) if e == "跳出" => break,
-                            Err(e) if e == "继续" => continue,
-                            Err(e) => return Err(e),
-                        }
+                
+                let iterable = self.evaluate_expression(可迭代)?;
+                let iterable_span = 可迭代.span().unwrap_or(*位置);
+                let is_dict = matches!(iterable, Value::字典(_));
+                
+                // 先取出所有要遍历的项，循环体中修改数组或字典不影响这次遍历。
+                // 字符串和数组的 (下标, 值) 从 0 开始编号，字典的 (键, 值) 按插入顺序
+                let pairs: Vec<(Value, Value)> = match iterable {
+                    Value::字符串(s) => s.chars().map(|ch| Value::字符串(ch.to_string())).enumerate().map(numbered).collect(),
+                    Value::数组(array) => array.borrow().iter().cloned().enumerate().map(numbered).collect(),
+                    Value::字典(dict) => dict
+                        .borrow()
+                        .entries
+                        .iter()
+                        .map(|(key, value)| (Value::字符串(key.clone()), value.clone()))
+                        .collect(),
+                    other => {
+                        return Err(Diagnostic::error("E0209", format!("只能遍历字符串、数组或字典，但得到了{}", other.type_name()))
+                            .with_span(iterable_span)
+                            .into());
                     }
-                }
-                Ok(Value::空)
+                };
+                
+                // 还没有遍历到的项按倒序放在 `temporaries` 中，循环体中的垃圾回收不会回收它们引用的作用域
+                let base = self.temporaries.len();
+                self.temporaries.extend(pairs.into_iter().rev().flat_map(|(key, value)| [value, key]));
+                let outcome = self.execute_for(变量, 值变量.as_deref(), is_dict, 体, *位置, |this| {
+                    if this.temporaries.len() == base {
+                        return None;
+                    }
+                    let key = this.temporaries.pop().expect("遍历的键");
+                    let value = this.temporaries.pop().expect("遍历的值");
+                    Some((key, value))
+                });
+                self.temporaries.truncate(base);
+                outcome
             }
-            Statement::对于语句 { 变量, 可迭代, 体 } => {
-                let iterable = self.evaluate_expression(可迭代)?;
+            Statement::尝试语句 { 体, 捕获变量, 捕获, 最终, .. } => {
+                let mut outcome = self.execute_block(体);
                 
-                match iterable {
-                    Value::字符串(s) => {
-                        for ch in s.chars() {
-                            let env = self.get_current_environment_mut();
-                            env.define(变量, Value::字符串(ch.to_string()));
-                            
-                            for stmt in 体 {
-                                match self.execute_statement(stmt) {
-                                    Ok(Value::空) => continue,
-                                    Ok(_) => break,
-                                    Err(e) if e == "跳出" => break,
-                                    Err(e) if e == "继续" => continue,
-                                    Err(e) => return Err(e),
-                                }
+                if let Some(handler) = 捕获 {
+                    let source = self.source_of(self.current_env);
+                    outcome = match outcome.map_err(|flow| flow.into_caught(source)) {
+                        Err(Ok(error)) => self.in_new_scope(self.current_env, |this| {
+                            if let Some((name, span)) = 捕获变量 {
+                                this.get_current_environment_mut().declare(name, Value::错误(error), true, *span)?;
                             }
-                        }
-                    }
-                    _ => return Err("只能遍历字符串".to_string()),
+                            this.execute_statements(handler)
+                        }),
+                        Err(Err(other)) => Err(other),
+                        Ok(()) => Ok(()),
+                    };
                 }
                 
-                Ok(Value::空)
+                // 最终 总会执行，它自己非正常结束时取代之前的结果；
+                // 执行期间保留之前的返回值或抛出的错误
+                if let Some(finally) = 最终 {
+                    let pending = match &outcome {
+                        Err(ControlFlow::返回(value, _)) => value.clone(),
+                        Err(ControlFlow::抛出(error)) => Value::错误(error.clone()),
+                        _ => Value::空,
+                    };
+                    self.holding(pending, |this| this.execute_block(finally)).1?;
+                }
+                outcome
             }
-            Statement::返回语句(值) => {
+            Statement::抛出语句 { 值, 位置 } => {
+                // 重新抛出捕获到的错误时保留原来的种类和位置
+                let error = match self.evaluate_expression(值)? {
+                    Value::错误(error) => error,
+                    value => {
+                        let message = match &value {
+                            Value::字符串(s) => s.clone(),
+                            other => other.to_string(),
+                        };
+                        let diagnostic = Diagnostic::error("E0213", message)
+                            .with_span(*位置)
+                            .with_note("抛出的错误没有被 尝试 语句捕获")
+                            .with_source(self.source_of(self.current_env));
+                        Rc::new(ErrorValue { diagnostic, value })
+                    }
+                };
+                Err(ControlFlow::抛出(error))
+            }
+            Statement::导入语句 { 路径, 别名, 位置 } => {
+                let module = self.import(路径, *位置)?;
+                let name = import_name(路径, 别名.as_deref());
+                self.get_current_environment_mut().declare(&name, Value::模块(module), false, *位置)?;
+                Ok(())
+            }
+            Statement::返回语句 { 值, 位置 } => {
                 let return_value = match 值 {
                     Some(expr) => self.evaluate_expression(expr)?,
                     None => Value::空,
                 };
-                // 生成可解析的返回字符串
-                let return_str = match return_value {
-                    Value::数字(n) => n.to_string(),
-                    Value::字符串(s) => format!("\"{}\"", s),
-                    Value::布尔(b) => b.to_string(),
-                    Value::空 => String::new(),
-                    _ => String::new(),
-                };
-                Err(format!("返回{}", return_str))
+                Err(ControlFlow::返回(return_value, *位置))
+            }
+            Statement::跳出语句 { 位置 } => Err(ControlFlow::跳出(*位置)),
+            Statement::继续语句 { 位置 } => Err(ControlFlow::继续(*位置)),
+            Statement::错误 { 位置 } => {
+                Err(Diagnostic::error("E0106", "无法执行含有语法错误的语句").with_span(*位置).into())
+            }
+            Statement::注释 { .. } | Statement::空行 => Ok(()),
+        }
+    }
+    
+    /// 在新的块作用域中执行一组语句
+    fn execute_block(&mut self, statements: &[Statement]) -> Result<(), ControlFlow> {
+        self.in_new_scope(self.current_env, |this| this.execute_statements(statements))
+    }
+    
+    fn execute_statements(&mut self, statements: &[Statement]) -> Result<(), ControlFlow> {
+        for stmt in statements {
+            self.execute_statement(stmt)?;
+        }
+        Ok(())
+    }
+    
+    /// `循环 条件`、`循环` 和 `当 条件` 共用：每次执行循环体之前求值条件，
+    /// 条件不成立时结束，一开始就不成立则一次也不执行；没有条件时一直循环
+    fn execute_loop(&mut self, condition: Option<&Expression>, body: &[Statement]) -> Result<(), ControlFlow> {
+        loop {
+            self.maybe_collect_garbage(None);
+            if let Some(condition) = condition {
+                let condition_value = self.evaluate_expression(condition)?;
+                if !self.is_truthy(&condition_value) {
+                    return Ok(());
+                }
+            }
+            
+            match self.execute_block(body) {
+                Ok(()) | Err(ControlFlow::继续(_)) => {}
+                Err(ControlFlow::跳出(_)) => return Ok(()),
+                Err(other) => return Err(other),
             }
-            Statement::跳出语句 => Err("跳出".to_string()),
-            Statement::继续语句 => Err("继续".to_string()),
         }
     }
     
-    fn evaluate_expression(&mut self, expression: &Expression) -> Result<Value, String> {
+    /// 执行导入的模块并返回它的公开成员，同一个模块只执行一次
+    fn import(&mut self, path: &str, span: Span) -> Result<Rc<ModuleValue>, ControlFlow> {
+        let module = self
+            .modules
+            .get(&self.directory, path)
+            .ok_or_else(|| Diagnostic::error("E0214", format!("模块 {} 没有加载", path)).with_span(span))?;
+        if let Some(imported) = self.imported.get(&module.path) {
+            return Ok(imported.clone());
+        }
+        
+        // 模块有自己的全局作用域，在模块所在的目录中查找它导入的模块
+        let scope = self.environments.len();
+        self.environments.push(global_environment());
+        self.module_sources.insert(scope, module.source.clone());
+        self.scope_stack.push(std::mem::replace(&mut self.current_env, scope));
+        let saved_directory = std::mem::replace(&mut self.directory, module.directory().to_path_buf());
+        
+        let outcome = self.execute_statements(&module.program.语句);
+        
+        self.current_env = self.scope_stack.pop().expect("作用域栈不应为空");
+        self.directory = saved_directory;
+        match outcome {
+            Ok(()) => {}
+            Err(thrown @ ControlFlow::抛出(_)) => return Err(thrown),
+            Err(other) => return Err(other.into_error().with_source(Some(module.source.clone())).into()),
+        }
+        
+        let imported = Rc::new(ModuleValue {
+            name: module.name.clone(),
+            scope,
+            exports: module.exports(),
+        });
+        self.imported.insert(module.path.clone(), imported.clone());
+        Ok(imported)
+    }
+    
+    /// 作用域所属模块的源文件，入口文件中的作用域没有
+    fn source_of(&self, scope: usize) -> Option<Rc<SourceFile>> {
+        let mut env_id = scope;
+        while let Some(parent) = self.environments[env_id].parent {
+            env_id = parent;
+        }
+        self.module_sources.get(&env_id).cloned()
+    }
+    
+    /// 创建一个以 `parent` 为上级的作用域，在其中执行 `f`，结束后回到原来的作用域
+    fn in_new_scope<T>(&mut self, parent: usize, f: impl FnOnce(&mut Self) -> T) -> T {
+        let new_env_id = self.allocate_environment(parent);
+        self.scope_stack.push(self.current_env);
+        self.current_env = new_env_id;
+        
+        let result = f(self);
+        
+        self.current_env = self.scope_stack.pop().expect("作用域栈不应为空");
+        if !self.environments[new_env_id].captured {
+            self.free_environment(new_env_id);
+        }
+        result
+    }
+    
+    fn allocate_environment(&mut self, parent: usize) -> usize {
+        match self.free_envs.pop() {
+            Some(id) => {
+                self.environments[id] = Environment::with_parent(parent);
+                id
+            }
+            None => {
+                self.environments.push(Environment::with_parent(parent));
+                self.environments.len() - 1
+            }
+        }
+    }
+    
+    fn free_environment(&mut self, id: usize) {
+        self.environments[id] = Environment::new();
+        self.free_envs.push(id);
+    }
+    
+    fn live_environments(&self) -> usize {
+        self.environments.len() - self.free_envs.len()
+    }
+    
+    /// 在 `id` 中定义的函数会引用它和它的所有上级作用域
+    fn mark_captured(&mut self, id: usize) {
+        let mut env_id = Some(id);
+        while let Some(id) = env_id {
+            let env = &mut self.environments[id];
+            if env.captured {
+                break;
+            }
+            env.captured = true;
+            env_id = env.parent;
+        }
+    }
+    
+    /// 存活的作用域足够多时回收垃圾，`temporary` 是还没有存入任何作用域、
+    /// 也不在 `temporaries` 中的值
+    fn maybe_collect_garbage(&mut self, temporary: Option<&Value>) {
+        if self.live_environments() > self.gc_threshold {
+            // 导入的模块可能再被导入，它们的作用域一直保留
+            let mut roots = vec![self.current_env];
+            roots.extend(&self.scope_stack);
+            roots.extend(self.module_sources.keys().copied());
+            let mut seen = HashSet::new();
+            for value in self.temporaries.iter().chain(temporary) {
+                value.trace(&mut roots, &mut seen);
+            }
+            self.collect_garbage(roots);
+        }
+    }
+    
+    /// 执行 对于 语句的循环：`next` 依次给出每一项的 (下标或键, 值)，给出 None 时结束
+    fn execute_for(
+        &mut self,
+        variable: &str,
+        value_variable: Option<&str>,
+        is_dict: bool,
+        body: &[Statement],
+        span: Span,
+        mut next: impl FnMut(&mut Self) -> Option<(Value, Value)>,
+    ) -> Result<(), ControlFlow> {
+        loop {
+            self.maybe_collect_garbage(None);
+            let Some((key, value)) = next(self) else {
+                return Ok(());
+            };
+            
+            // 每次迭代都有新的作用域，循环变量在循环体结束后不再可见
+            let outcome = self.in_new_scope(self.current_env, |this| {
+                let env = this.get_current_environment_mut();
+                match value_variable {
+                    Some(name) => {
+                        env.declare(variable, key, true, span)?;
+                        env.declare(name, value, true, span)?;
+                    }
+                    // 只有一个变量时，字典给出键，字符串、数组和范围给出值
+                    None if is_dict => env.declare(variable, key, true, span)?,
+                    None => env.declare(variable, value, true, span)?,
+                }
+                this.execute_statements(body)
+            });
+            
+            match outcome {
+                Ok(()) | Err(ControlFlow::继续(_)) => {}
+                Err(ControlFlow::跳出(_)) => return Ok(()),
+                Err(other) => return Err(other),
+            }
+        }
+    }
+    
+    /// 遍历的是 `起..止` 或者对内置函数 范围 的调用时，求出范围的起点、终点和步长
+    fn range_of(&mut self, iterable: &Expression) -> Result<Option<(f64, f64, f64)>, ControlFlow> {
+        match iterable {
+            Expression::范围 { 起, 止, 位置 } => {
+                let start = self.evaluate_expression(起)?;
+                let (start, end) = self.holding(start, |this| this.evaluate_expression(止));
+                Ok(Some(range_bounds(&start, &end?, &Value::数字(1.0), *位置)?))
+            }
+            Expression::函数调用 { 函数, 参数, 位置 } => match 函数.as_ref() {
+                Expression::变量 { 名字, 槽位, .. }
+                    if matches!(self.lookup(名字, 槽位.get()), Some(Value::内置函数(name)) if name == "内置范围") =>
+                {
+                    let arguments = self.evaluate_all(参数)?;
+                    Ok(Some(range_arguments(&arguments, *位置)?))
+                }
+                _ => Ok(None),
+            },
+            _ => Ok(None),
+        }
+    }
+    
+    /// 执行 `f` 期间把 `held` 放在 `temporaries` 中，结束后取回
+    fn holding<T>(&mut self, held: Value, f: impl FnOnce(&mut Self) -> T) -> (Value, T) {
+        self.temporaries.push(held);
+        let result = f(self);
+        let held = self.temporaries.pop().expect("临时值栈不应为空");
+        (held, result)
+    }
+    
+    /// 依次求值一组表达式，已经求出的值放在 `temporaries` 中，直到全部求出
+    fn evaluate_all<'e>(&mut self, expressions: impl IntoIterator<Item = &'e Expression>) -> Result<Vec<Value>, ControlFlow> {
+        let base = self.temporaries.len();
+        for expression in expressions {
+            match self.evaluate_expression(expression) {
+                Ok(value) => self.temporaries.push(value),
+                Err(flow) => {
+                    self.temporaries.truncate(base);
+                    return Err(flow);
+                }
+            }
+        }
+        Ok(self.temporaries.split_off(base))
+    }
+    
+    /// 标记-清除：从根作用域出发，沿上级作用域和函数值捕获的作用域标记，回收其余的作用域
+    fn collect_garbage(&mut self, roots: Vec<usize>) {
+        let mut reachable = vec![false; self.environments.len()];
+        for &id in &self.free_envs {
+            reachable[id] = true;
+        }
+        
+        let mut pending = roots;
+        let mut seen_collections = HashSet::new();
+        while let Some(id) = pending.pop() {
+            if reachable[id] {
+                continue;
+            }
+            reachable[id] = true;
+            
+            let env = &self.environments[id];
+            pending.extend(env.parent);
+            for binding in &env.bindings {
+                binding.value.trace(&mut pending, &mut seen_collections);
+            }
+        }
+        
+        for (id, reachable) in reachable.into_iter().enumerate() {
+            if !reachable {
+                self.free_environment(id);
+            }
+        }
+        self.gc_threshold = (self.live_environments() * 2).max(GC_MIN_THRESHOLD);
+    }
+    
+    /// 槽位指向的作用域；那个作用域还没有声明到这个槽位时返回 None，
+    /// 这时名字可能来自更外层的作用域，要按名字查找
+    fn slot_environment(&self, slot: Slot) -> Option<usize> {
+        let mut env_id = self.current_env;
+        for _ in 0..slot.depth {
+            env_id = self.environments[env_id].parent?;
+        }
+        (slot.index < self.environments[env_id].bindings.len()).then_some(env_id)
+    }
+    
+    /// 取变量的值，有槽位时直接按槽位取
+    fn lookup(&self, name: &str, slot: Option<Slot>) -> Option<Value> {
+        match slot.and_then(|slot| Some((self.slot_environment(slot)?, slot.index))) {
+            Some((env_id, index)) => Some(self.environments[env_id].bindings[index].value.clone()),
+            None => self.get_current_environment().get_with_scope_chain(name, &self.environments),
+        }
+    }
+    
+    /// 沿作用域链找到变量所在的作用域并赋值，有槽位时直接按槽位赋值
+    fn assign(&mut self, name: &str, slot: Option<Slot>, value: Value) -> Result<(), Box<Diagnostic>> {
+        if let Some(slot) = slot {
+            if let Some(env_id) = self.slot_environment(slot) {
+                return self.environments[env_id].assign_at(slot.index, name, value);
+            }
+        }
+        let mut env_id = Some(self.current_env);
+        while let Some(id) = env_id {
+            let env = &mut self.environments[id];
+            if env.contains(name) {
+                return env.assign(name, value);
+            }
+            env_id = env.parent;
+        }
+        Err(Diagnostic::error("E0200", format!("未定义的变量: {}", name)).into())
+    }
+    
+    fn evaluate_expression(&mut self, expression: &Expression) -> Result<Value, ControlFlow> {
         match expression {
             Expression::数字字面量(n) => Ok(Value::数字(*n)),
             Expression::字符串字面量(s) => Ok(Value::字符串(s.clone())),
-            Expression::变量(name) => {
-                let env = self.get_current_environment();
-                match env.get_with_scope_chain(name, &self.environments) {
+            Expression::布尔字面量(b) => Ok(Value::布尔(*b)),
+            Expression::变量 { 名字, 位置, 槽位 } => {
+                match self.lookup(名字, 槽位.get()) {
                     Some(value) => Ok(value),
-                    None => Err(format!("未定义的变量: {}", name)),
+                    None => Err(Diagnostic::error("E0200", format!("未定义的变量: {}", 名字)).with_span(*位置).into()),
+                }
+            }
+            Expression::自己 { 位置 } => {
+                let env = self.get_current_environment();
+                let receiver = env.get_with_scope_chain("自己", &self.environments)
+                    .ok_or_else(|| Diagnostic::error("E0200", "自己 只能在结构的方法中使用").with_span(*位置))?;
+                Ok(receiver)
+            }
+            Expression::二元运算 { 左, 运算符, 右, .. } if 运算符 == "&&" || 运算符 == "||" => {
+                // 且/或 短路求值，结果总是布尔值，与 C 后端的 is_truthy(a) && is_truthy(b) 一致
+                let left = self.evaluate_expression(左)?;
+                match (运算符.as_str(), self.is_truthy(&left)) {
+                    ("&&", false) => Ok(Value::布尔(false)),
+                    ("||", true) => Ok(Value::布尔(true)),
+                    _ => {
+                        let right = self.evaluate_expression(右)?;
+                        Ok(Value::布尔(self.is_truthy(&right)))
+                    }
                 }
             }
-            Expression::二元运算 { 左, 运算符, 右 } => {
+            Expression::二元运算 { 左, 运算符, 右, 位置 } => {
                 let left = self.evaluate_expression(左)?;
-                let right = self.evaluate_expression(右)?;
+                let (left, right) = self.holding(left, |this| this.evaluate_expression(右));
+                let right = right?;
+                let operand_types = format!("左边是{}，右边是{}", left.type_name(), right.type_name());
                 
-                match 运算符.as_str() {
+                let result = match 运算符.as_str() {
                     "+" => self.add(left, right),
                     "-" => self.subtract(left, right),
                     "*" => self.multiply(left, right),
@@ -267,271 +1195,432 @@ impl Interpreter {
                     "<" => self.less_than(left, right),
                     ">=" => self.greater_equal(left, right),
                     "<=" => self.less_equal(left, right),
-                    "&&" => Ok(Value::布尔(self.is_truthy(&left) && self.is_truthy(&right))),
-                    "||" => Ok(Value::布尔(self.is_truthy(&left) || self.is_truthy(&right))),
-                    "=" => {
-                        if let Expression::变量(name) = &**左 {
-                            let env = self.get_current_environment_mut();
-                            env.assign(name, right.clone())?;
-                            Ok(right)
-                        } else {
-                            Err("无效的赋值目标".to_string())
-                        }
-                    }
-                    _ => Err(format!("未知运算符: {}", 运算符)),
-                }
+                    _ => Err(Diagnostic::error("E0204", format!("未知运算符: {}", 运算符)).into()),
+                };
+                let value = result.map_err(|e| {
+                    let e = e.with_span(*位置);
+                    if e.code == "E0204" { e.with_note(operand_types) } else { e }
+                })?;
+                Ok(value)
             }
-            Expression::函数调用 { 函数名, 参数 } => {
-                let function_value = {
-                    let env = self.get_current_environment();
-                    env.get_with_scope_chain(函数名, &self.environments).ok_or_else(|| format!("未定义的函数: {}", 函数名))?
+            Expression::函数调用 { 函数, 参数, 位置 } => {
+                let function_value = match 函数.as_ref() {
+                    Expression::变量 { 名字, 槽位, .. } => {
+                        self.lookup(名字, 槽位.get()).ok_or_else(|| {
+                            Diagnostic::error("E0201", format!("未定义的函数: {}", 名字)).with_span(*位置)
+                        })?
+                    }
+                    callee => self.evaluate_expression(callee)?,
                 };
                 
-                match function_value {
-                    Value::内置函数(name) => {
-                        match name.as_str() {
-                            "内置打印" => {
-                                if 参数.len() != 1 {
-                                    return Err("打印函数需要一个参数".to_string());
-                                }
-                                let value = self.evaluate_expression(&参数[0])?;
-                                println!("{}", value);
-                                Ok(Value::空)
-                            }
-                            "内置输入" => {
-                                if !参数.is_empty() {
-                                    return Err("输入函数不需要参数".to_string());
-                                }
-                                use std::io;
-                                let mut input = String::new();
-                                io::stdin().read_line(&mut input).unwrap();
-                                Ok(Value::字符串(input.trim().to_string()))
-                            }
-                            _ => Err(format!("未知内置函数: {}", name)),
-                        }
-                    }
-                    Value::函数 { 参数: params, 体: body, 作用域: scope } => {
-                        if params.len() != 参数.len() {
-                            return Err(format!("参数数量不匹配: 期望 {}, 得到 {}", params.len(), 参数.len()));
-                        }
-                        
-                        // 先计算所有参数的值，在旧的作用域中
-                        let mut arg_values = Vec::new();
-                        for arg_expr in 参数 {
-                            let arg_value = self.evaluate_expression(arg_expr)?;
-                            arg_values.push(arg_value);
-                        }
-                        
-                        // 创建新的作用域
-                        let new_env_id = self.environments.len();
-                        self.environments.push(Environment::with_parent(scope));
-                        let old_env = self.current_env;
-                        self.current_env = new_env_id;
-                        
-                        // 绑定参数
-                        for (i, param) in params.iter().enumerate() {
-                            let env = self.get_current_environment_mut();
-                            env.define(param, arg_values[i].clone());
-                        }
-                        
-                        // 执行函数体
-                        let mut result = Value::空;
-                        for stmt in &body {
-                            match self.execute_statement(stmt) {
-                                Ok(Value::空) => continue,
-                                Ok(value) => {
-                                    result = value;
-                                    break;
-                                }
-                                Err(e) if e.starts_with("返回") => {
-                                    // 简单处理返回值
-                                    let return_str = e.trim_start_matches("返回");
-                                    if !return_str.is_empty() {
-                                        // 尝试解析数字
-                                        if let Ok(num) = return_str.parse::<f64>() {
-                                            result = Value::数字(num);
-                                        } else if return_str == "true" || return_str == "false" {
-                                            result = Value::布尔(return_str == "true");
-                                        } else if return_str.starts_with('"') && return_str.ends_with('"') {
-                                            result = Value::字符串(return_str[1..return_str.len()-1].to_string());
-                                        } else {
-                                            result = Value::字符串(return_str.to_string());
-                                        }
-                                    }
-                                    break;
-                                }
-                                Err(e) => {
-                                    self.current_env = old_env;
-                                    return Err(e);
-                                }
-                            }
-                        }
-                        
-                        // 恢复旧的作用域
-                        self.current_env = old_env;
-                        Ok(result)
-                    }
-                    _ => Err(format!("{} 不是函数", 函数名)),
-                }
+                // 先计算所有参数的值，在调用者的作用域中
+                let (function_value, arguments) = self.holding(function_value, |this| this.evaluate_all(参数));
+                self.call_function(function_value, None, arguments?, 函数.as_ref(), *位置)
+            }
+            Expression::匿名函数 { 参数, 参数类型, 返回类型, 体, .. } => {
+                self.mark_captured(self.current_env);
+                Ok(Value::函数 {
+                    参数: 参数.clone(),
+                    参数类型: 参数类型.clone(),
+                    返回类型: *返回类型,
+                    体: Rc::from(体.as_slice()),
+                    作用域: self.current_env,
+                })
             }
-            Expression::一元运算 { 运算符, 操作数 } => {
+            Expression::一元运算 { 运算符, 操作数, 位置 } => {
                 let operand = self.evaluate_expression(操作数)?;
                 match 运算符.as_str() {
                     "!" => Ok(Value::布尔(!self.is_truthy(&operand))),
-                    _ => Err(format!("未知一元运算符: {}", 运算符)),
+                    _ => Err(Diagnostic::error("E0204", format!("未知一元运算符: {}", 运算符)).with_span(*位置).into()),
                 }
             }
-            Expression::赋值 { 变量名, 值 } => {
+            Expression::赋值 { 变量名, 值, 位置, 槽位 } => {
                 let value = self.evaluate_expression(值)?;
-                let env = self.get_current_environment_mut();
-                env.assign(变量名, value.clone())?;
+                self.assign(变量名, 槽位.get(), value.clone()).map_err(|e| e.with_span(*位置))?;
                 Ok(value)
             }
             Expression::数组字面量(元素) => {
-                let mut array_values = Vec::new();
-                for element in 元素 {
-                    array_values.push(self.evaluate_expression(element)?);
-                }
-                Ok(Value::数组(array_values))
+                Ok(Value::array(self.evaluate_all(元素)?))
             }
-            Expression::数组索引 { 数组, 索引 } => {
-                let array_value = self.evaluate_expression(数组)?;
-                let index_value = self.evaluate_expression(索引)?;
+            Expression::范围 { 起, 止, 位置 } => {
+                let start = self.evaluate_expression(起)?;
+                let (start, end) = self.holding(start, |this| this.evaluate_expression(止));
+                Ok(range(range_bounds(&start, &end?, &Value::数字(1.0), *位置)?))
+            }
+            Expression::数组索引 { 数组, 索引, 位置 } => {
+                let collection = self.evaluate_collection(数组, "索引", *位置)?;
+                let (_, index_value) = self.holding(collection.value(), |this| this.evaluate_expression(索引));
+                let index_value = index_value?;
+                let index_span = 索引.span().unwrap_or(*位置);
                 
-                match (array_value, index_value) {
-                    (Value::数组(元素), Value::数字(idx)) => {
-                        let idx = idx as usize;
-                        if idx < 元素.len() {
-                            Ok(元素[idx].clone())
-                        } else {
-                            Err(format!("数组索引越界: 索引 {}, 长度 {}", idx, 元素.len()))
-                        }
+                match collection {
+                    Collection::数组(array) => {
+                        let elements = array.borrow();
+                        let idx = array_index(&index_value, elements.len(), index_span)?;
+                        Ok(elements[idx].clone())
+                    }
+                    Collection::字典(dict) => {
+                        let key = dict_key(&index_value, index_span)?;
+                        let value = dict.borrow().get(&key).cloned();
+                        Ok(value.ok_or_else(|| missing_key(&key, index_span))?)
                     }
-                    _ => Err("索引操作需要数组和数字索引".to_string()),
                 }
             }
-            Expression::数组长度(数组) => {
-                let array_value = self.evaluate_expression(数组)?;
-                match array_value {
-                    Value::数组(元素) => Ok(Value::数字(元素.len() as f64)),
-                    _ => Err("长度操作需要数组".to_string()),
+            Expression::索引赋值 { 数组, 索引, 值, 位置 } => {
+                let collection = self.evaluate_collection(数组, "索引赋值", *位置)?;
+                let (_, values) = self.holding(collection.value(), |this| this.evaluate_all([索引.as_ref(), 值.as_ref()]));
+                let [index_value, value]: [Value; 2] = values?.try_into().expect("求出两个值");
+                let index_span = 索引.span().unwrap_or(*位置);
+                
+                match collection {
+                    Collection::数组(array) => {
+                        let mut elements = array.borrow_mut();
+                        let idx = array_index(&index_value, elements.len(), index_span)?;
+                        elements[idx] = value.clone();
+                    }
+                    // 字典可以用赋值添加新的键
+                    Collection::字典(dict) => {
+                        let key = dict_key(&index_value, index_span)?;
+                        dict.borrow_mut().insert(key, value.clone());
+                    }
                 }
+                Ok(value)
             }
-            Expression::数组添加 { 数组, 元素 } => {
-                let array_value = self.evaluate_expression(数组)?;
-                let element_value = self.evaluate_expression(元素)?;
+            Expression::字典字面量 { 条目, 位置 } => {
+                // 字典先创建出来，已经求出的条目随它一起保留
+                let entries = Rc::new(RefCell::new(Dictionary::default()));
+                let (dict, outcome) = self.holding(Value::字典(entries.clone()), |this| {
+                    for (key_expr, value_expr) in 条目 {
+                        let key = this.evaluate_expression(key_expr)?;
+                        let key = dict_key(&key, key_expr.span().unwrap_or(*位置))?;
+                        let value = this.evaluate_expression(value_expr)?;
+                        entries.borrow_mut().insert(key, value);
+                    }
+                    Ok::<(), ControlFlow>(())
+                });
+                outcome?;
+                Ok(dict)
+            }
+            Expression::成员访问 { 对象, 成员, 位置 } => {
+                let object = self.evaluate_expression(对象)?;
+                let field = match &object {
+                    Value::实例(instance) => {
+                        let instance = instance.borrow();
+                        instance.class.field_index(成员).map(|i| instance.fields[i].clone())
+                    }
+                    Value::错误(error) => error.field(成员),
+                    Value::模块(module) => return Ok(self.module_member(module, 成员, *位置)?),
+                    _ => None,
+                };
+                Ok(field.ok_or_else(|| no_field(&object, 成员, *位置))?)
+            }
+            Expression::成员赋值 { 对象, 成员, 值, 位置 } => {
+                let object = self.evaluate_expression(对象)?;
+                let (object, value) = self.holding(object, |this| this.evaluate_expression(值));
+                let value = value?;
+                if let Value::模块(module) = &object {
+                    return Err(Diagnostic::error("E0210", format!("不能给模块 {} 的成员 {} 赋值", module.name, 成员))
+                        .with_span(*位置)
+                        .with_note("模块的成员只能在模块自己的代码中修改")
+                        .into());
+                }
+                let Value::实例(instance) = &object else {
+                    return Err(no_field(&object, 成员, *位置).into());
+                };
                 
-                match array_value {
-                    Value::数组(mut 元素) => {
-                        元素.push(element_value);
-                        Ok(Value::数组(元素))
+                // 只能修改已声明的字段，不能给实例添加新字段
+                let mut instance = instance.borrow_mut();
+                match instance.class.field_index(成员) {
+                    Some(i) => instance.fields[i] = value.clone(),
+                    None => {
+                        drop(instance);
+                        return Err(no_field(&object, 成员, *位置).into());
                     }
-                    _ => Err("添加操作需要数组".to_string()),
                 }
+                Ok(value)
+            }
+            Expression::方法调用 { 对象, 方法, 参数, 位置 } => {
+                let object = self.evaluate_expression(对象)?;
+                let (object, arguments) = self.holding(object, |this| this.evaluate_all(参数));
+                self.call_method(object, 方法, arguments?, *位置)
             }
-            Expression::数组删除 { 数组, 索引 } => {
-                let array_value = self.evaluate_expression(数组)?;
-                let index_value = self.evaluate_expression(索引)?;
+        }
+    }
+    
+    /// 计算一个应当是数组或字典的表达式
+    fn evaluate_collection(&mut self, expression: &Expression, operation: &str, span: Span) -> Result<Collection, ControlFlow> {
+        match self.evaluate_expression(expression)? {
+            Value::数组(array) => Ok(Collection::数组(array)),
+            Value::字典(dict) => Ok(Collection::字典(dict)),
+            other => Err(Diagnostic::error("E0204", format!("{}操作需要数组或字典，但得到了{}", operation, other.type_name()))
+                .with_span(span)
+                .into()),
+        }
+    }
+    
+    /// 调用函数值，`callee` 是被调用的表达式，只用于错误信息；
+    /// `receiver` 是方法调用的对象，在函数体中绑定为 `自己`
+    fn call_function(
+        &mut self,
+        function: Value,
+        receiver: Option<Value>,
+        arguments: Vec<Value>,
+        callee: &dyn std::fmt::Display,
+        span: Span,
+    ) -> Result<Value, ControlFlow> {
+        match function {
+            Value::内置函数(name) => match name.as_str() {
+                "内置打印" => {
+                    if arguments.len() != 1 {
+                        return Err(Diagnostic::error("E0203", "打印函数需要一个参数").with_span(span).into());
+                    }
+                    println!("{}", arguments[0]);
+                    Ok(Value::空)
+                }
+                "内置输入" => {
+                    if !arguments.is_empty() {
+                        return Err(Diagnostic::error("E0203", "输入函数不需要参数").with_span(span).into());
+                    }
+                    use std::io;
+                    let mut input = String::new();
+                    io::stdin().read_line(&mut input).unwrap();
+                    Ok(Value::字符串(input.trim().to_string()))
+                }
+                "内置范围" => Ok(range(range_arguments(&arguments, span)?)),
+                _ => Err(Diagnostic::error("E0201", format!("未知内置函数: {}", name)).with_span(span).into()),
+            },
+            Value::函数 { 参数: params, 参数类型: types, 返回类型: returns, 体: body, 作用域: scope } => {
+                let signature = || format!("{}{}", callee, formatter::parameters(&params, &types, returns));
+                if params.len() != arguments.len() {
+                    return Err(Diagnostic::error(
+                        "E0203",
+                        format!("参数数量不匹配: 期望 {}, 得到 {}", params.len(), arguments.len()),
+                    )
+                    .with_span(span)
+                    .with_note(signature())
+                    .into());
+                }
+                let types: Vec<Option<Type>> = (0..params.len()).map(|i| types.get(i).copied().flatten()).collect();
+                for ((param, ty), value) in params.iter().zip(&types).zip(&arguments) {
+                    if let Some(ty) = ty.filter(|ty| !value.has_type(*ty)) {
+                        let error = mismatch(&format!("参数 {}", param), ty, &value.type_name());
+                        return Err(error.with_span(span).with_note(signature()).into());
+                    }
+                }
+                
+                // 在定义函数时的作用域之下创建新的作用域，参数和函数体共用这个作用域
+                let outcome = self.in_new_scope(scope, |this| {
+                    let env = this.get_current_environment_mut();
+                    if let Some(object) = receiver {
+                        env.define("自己", object);
+                    }
+                    for ((param, ty), value) in params.iter().zip(types).zip(arguments) {
+                        env.define_typed(param, value, ty);
+                    }
+                    this.execute_statements(&body)
+                });
                 
-                match (array_value, index_value) {
-                    (Value::数组(mut 元素), Value::数字(idx)) => {
-                        let idx = idx as usize;
-                        if idx < 元素.len() {
-                            元素.remove(idx);
-                            Ok(Value::数组(元素))
-                        } else {
-                            Err(format!("数组索引越界: 索引 {}, 长度 {}", idx, 元素.len()))
-                        }
+                // 返回值不符合返回类型时，错误指向 返回 语句，执行到结尾时指向调用处
+                let (value, returned_at) = match outcome {
+                    Ok(()) => (Value::空, None),
+                    Err(ControlFlow::返回(value, at)) => (value, Some(at)),
+                    // 错误继续向调用者传播，逃出函数体的跳出和继续是错误；
+                    // 导入的模块中的函数出错时，错误的位置属于模块的文件
+                    Err(thrown @ ControlFlow::抛出(_)) => return Err(thrown),
+                    Err(other) => return Err(other.into_error().with_source(self.source_of(scope)).into()),
+                };
+                self.maybe_collect_garbage(Some(&value));
+                match returns.filter(|ty| !value.has_type(*ty)) {
+                    None => Ok(value),
+                    Some(ty) => {
+                        let error = mismatch("返回值", ty, &value.type_name());
+                        let error = match returned_at {
+                            Some(at) => error.with_span(at).with_source(self.source_of(scope)),
+                            None => error.with_span(span).with_note(format!("{} 执行到结尾时没有返回值", signature())),
+                        };
+                        Err(error.into())
                     }
-                    _ => Err("删除操作需要数组和数字索引".to_string()),
                 }
             }
+            // 调用结构名创建实例，参数按字段声明的顺序给出
+            Value::结构(class) => {
+                if class.fields.len() != arguments.len() {
+                    return Err(Diagnostic::error(
+                        "E0203",
+                        format!("参数数量不匹配: 结构 {} 有 {} 个字段, 得到 {} 个参数", class.name, class.fields.len(), arguments.len()),
+                    )
+                    .with_span(span)
+                    .with_note(format!("{}({})", class.name, class.fields.join(", ")))
+                    .into());
+                }
+                Ok(Value::实例(Rc::new(RefCell::new(Instance { class, fields: arguments }))))
+            }
+            other => Err(Diagnostic::error("E0202", format!("{} 不是函数", callee))
+                .with_span(span)
+                .with_note(format!("{} 的值是{}", callee, other.type_name()))
+                .into()),
+        }
+    }
+    
+    /// 调用对象的方法：实例先找结构的方法，再找保存了函数的字段；数组和字典使用内置方法
+    fn call_method(&mut self, object: Value, method: &str, arguments: Vec<Value>, span: Span) -> Result<Value, ControlFlow> {
+        let callee = format!("{}.{}", object.type_name(), method);
+        
+        match &object {
+            Value::实例(instance) => {
+                let (function, field) = {
+                    let instance = instance.borrow();
+                    let field = instance.class.field_index(method).map(|i| instance.fields[i].clone());
+                    (instance.class.method(method).cloned(), field)
+                };
+                match (function, field) {
+                    (Some(function), _) => self.call_function(function, Some(object), arguments, &callee, span),
+                    (None, Some(field)) => self.call_function(field, None, arguments, &callee, span),
+                    (None, None) => Err(no_method(&object, method, span).into()),
+                }
+            }
+            Value::模块(module) => {
+                let function = self.module_member(module, method, span)?;
+                let callee = format!("{}.{}", module.name, method);
+                self.call_function(function, None, arguments, &callee, span)
+            }
+            Value::数组(array) => match method {
+                "长度" => {
+                    expect_arguments(method, &arguments, 0, span)?;
+                    Ok(Value::数字(array.borrow().len() as f64))
+                }
+                "添加" => {
+                    expect_arguments(method, &arguments, 1, span)?;
+                    // 原地修改，返回数组本身以便连续调用
+                    array.borrow_mut().extend(arguments);
+                    Ok(object.clone())
+                }
+                "删除" => {
+                    expect_arguments(method, &arguments, 1, span)?;
+                    // 原地修改，返回被删除的元素
+                    let mut elements = array.borrow_mut();
+                    let idx = array_index(&arguments[0], elements.len(), span)?;
+                    Ok(elements.remove(idx))
+                }
+                _ => Err(no_method(&object, method, span).into()),
+            },
+            Value::字典(dict) => match method {
+                "长度" => {
+                    expect_arguments(method, &arguments, 0, span)?;
+                    Ok(Value::数字(dict.borrow().len() as f64))
+                }
+                "键" => {
+                    expect_arguments(method, &arguments, 0, span)?;
+                    let keys = dict.borrow().keys().map(|key| Value::字符串(key.clone())).collect();
+                    Ok(Value::array(keys))
+                }
+                "值" => {
+                    expect_arguments(method, &arguments, 0, span)?;
+                    let values = dict.borrow().values().cloned().collect();
+                    Ok(Value::array(values))
+                }
+                "包含" => {
+                    expect_arguments(method, &arguments, 1, span)?;
+                    let key = dict_key(&arguments[0], span)?;
+                    let contains = dict.borrow().contains(&key);
+                    Ok(Value::布尔(contains))
+                }
+                "删除" => {
+                    expect_arguments(method, &arguments, 1, span)?;
+                    let key = dict_key(&arguments[0], span)?;
+                    let removed = dict.borrow_mut().remove(&key);
+                    Ok(removed.ok_or_else(|| missing_key(&key, span))?)
+                }
+                _ => Err(no_method(&object, method, span).into()),
+            },
+            _ => Err(no_method(&object, method, span).into()),
         }
     }
     
-    fn add(&self, left: Value, right: Value) -> Result<Value, String> {
+    fn add(&self, left: Value, right: Value) -> Result<Value, Box<Diagnostic>> {
         match (left, right) {
             (Value::数字(a), Value::数字(b)) => Ok(Value::数字(a + b)),
             (Value::字符串(a), Value::字符串(b)) => Ok(Value::字符串(a + &b)),
             (Value::字符串(a), Value::数字(b)) => Ok(Value::字符串(a + &b.to_string())),
             (Value::数字(a), Value::字符串(b)) => Ok(Value::字符串(a.to_string() + &b)),
-            (Value::字符串(a), Value::布尔(b)) => Ok(Value::字符串(a + &b.to_string())),
-            (Value::布尔(a), Value::字符串(b)) => Ok(Value::字符串(a.to_string() + &b)),
+            (Value::字符串(a), b @ Value::布尔(_)) => Ok(Value::字符串(a + &b.to_string())),
+            (a @ Value::布尔(_), Value::字符串(b)) => Ok(Value::字符串(a.to_string() + &b)),
             (Value::字符串(a), Value::空) => Ok(Value::字符串(a + "空")),
             (Value::空, Value::字符串(b)) => Ok(Value::字符串("空".to_string() + &b)),
             // 处理数组与字符串的相加
-            (Value::字符串(a), Value::数组(elements)) => {
-                let array_str = format!("{:?}", elements);
-                Ok(Value::字符串(a + &array_str))
-            }
-            (Value::数组(elements), Value::字符串(b)) => {
-                let array_str = format!("{:?}", elements);
-                Ok(Value::字符串(array_str + &b))
-            }
-            _ => Err("类型不匹配: 无法相加".to_string()),
+            (Value::字符串(a), array @ Value::数组(_)) => Ok(Value::字符串(a + &array.to_string())),
+            (array @ Value::数组(_), Value::字符串(b)) => Ok(Value::字符串(array.to_string() + &b)),
+            (Value::字符串(a), dict @ Value::字典(_)) => Ok(Value::字符串(a + &dict.to_string())),
+            (dict @ Value::字典(_), Value::字符串(b)) => Ok(Value::字符串(dict.to_string() + &b)),
+            _ => Err(Diagnostic::error("E0204", "类型不匹配: 无法相加").into()),
         }
     }
     
-    fn subtract(&self, left: Value, right: Value) -> Result<Value, String> {
+    fn subtract(&self, left: Value, right: Value) -> Result<Value, Box<Diagnostic>> {
         match (left, right) {
             (Value::数字(a), Value::数字(b)) => Ok(Value::数字(a - b)),
-            _ => Err("类型不匹配: 无法相减".to_string()),
+            _ => Err(Diagnostic::error("E0204", "类型不匹配: 无法相减").into()),
         }
     }
     
-    fn multiply(&self, left: Value, right: Value) -> Result<Value, String> {
+    fn multiply(&self, left: Value, right: Value) -> Result<Value, Box<Diagnostic>> {
         match (left, right) {
             (Value::数字(a), Value::数字(b)) => Ok(Value::数字(a * b)),
-            _ => Err("类型不匹配: 无法相乘".to_string()),
+            _ => Err(Diagnostic::error("E0204", "类型不匹配: 无法相乘").into()),
         }
     }
     
-    fn divide(&self, left: Value, right: Value) -> Result<Value, String> {
+    fn divide(&self, left: Value, right: Value) -> Result<Value, Box<Diagnostic>> {
         match (left, right) {
             (Value::数字(a), Value::数字(b)) => {
                 if b == 0.0 {
-                    Err("除零错误".to_string())
+                    Err(Diagnostic::error("E0205", "除零错误").into())
                 } else {
                     Ok(Value::数字(a / b))
                 }
             }
-            _ => Err("类型不匹配: 无法相除".to_string()),
+            _ => Err(Diagnostic::error("E0204", "类型不匹配: 无法相除").into()),
         }
     }
     
-    fn greater_than(&self, left: Value, right: Value) -> Result<Value, String> {
+    fn greater_than(&self, left: Value, right: Value) -> Result<Value, Box<Diagnostic>> {
         match (left, right) {
             (Value::数字(a), Value::数字(b)) => Ok(Value::布尔(a > b)),
-            _ => Err("类型不匹配: 无法比较".to_string()),
+            _ => Err(Diagnostic::error("E0204", "类型不匹配: 无法比较").into()),
         }
     }
     
-    fn less_than(&self, left: Value, right: Value) -> Result<Value, String> {
+    fn less_than(&self, left: Value, right: Value) -> Result<Value, Box<Diagnostic>> {
         match (left, right) {
             (Value::数字(a), Value::数字(b)) => Ok(Value::布尔(a < b)),
-            _ => Err("类型不匹配: 无法比较".to_string()),
+            _ => Err(Diagnostic::error("E0204", "类型不匹配: 无法比较").into()),
         }
     }
     
-    fn greater_equal(&self, left: Value, right: Value) -> Result<Value, String> {
+    fn greater_equal(&self, left: Value, right: Value) -> Result<Value, Box<Diagnostic>> {
         match (left, right) {
             (Value::数字(a), Value::数字(b)) => Ok(Value::布尔(a >= b)),
-            _ => Err("类型不匹配: 无法比较".to_string()),
+            _ => Err(Diagnostic::error("E0204", "类型不匹配: 无法比较").into()),
         }
     }
     
-    fn less_equal(&self, left: Value, right: Value) -> Result<Value, String> {
+    fn less_equal(&self, left: Value, right: Value) -> Result<Value, Box<Diagnostic>> {
         match (left, right) {
             (Value::数字(a), Value::数字(b)) => Ok(Value::布尔(a <= b)),
-            _ => Err("类型不匹配: 无法比较".to_string()),
+            _ => Err(Diagnostic::error("E0204", "类型不匹配: 无法比较").into()),
         }
     }
     
     fn is_equal(&self, left: &Value, right: &Value) -> bool {
         match (left, right) {
+            (Value::结构(a), Value::结构(b)) => Rc::ptr_eq(a, b),
+            (Value::实例(a), Value::实例(b)) => Rc::ptr_eq(a, b),
             (Value::数字(a), Value::数字(b)) => a == b,
             (Value::字符串(a), Value::字符串(b)) => a == b,
             (Value::布尔(a), Value::布尔(b)) => a == b,
+            (Value::数组(a), Value::数组(b)) => Rc::ptr_eq(a, b),
+            (Value::字典(a), Value::字典(b)) => Rc::ptr_eq(a, b),
+            (Value::模块(a), Value::模块(b)) => Rc::ptr_eq(a, b),
             (Value::空, Value::空) => true,
             _ => false,
         }
@@ -547,6 +1636,24 @@ impl Interpreter {
         }
     }
     
+    /// 读取模块的公开成员，模块中对它的修改在外面也能看到
+    fn module_member(&self, module: &ModuleValue, name: &str, span: Span) -> Result<Value, Box<Diagnostic>> {
+        let value = self.environments[module.scope].get_with_scope_chain(name, &self.environments);
+        match value {
+            Some(value) if module.exports.iter().any(|export| export == name) => Ok(value),
+            _ => {
+                let error = Diagnostic::error("E0212", format!("模块 {} 没有公开的成员 {}", module.name, name)).with_span(span);
+                if value.is_some() {
+                    Err(error.with_note(format!("{} 在模块中声明时没有加 公开", name)).into())
+                } else if module.exports.is_empty() {
+                    Err(error.with_note(format!("模块 {} 没有公开任何成员", module.name)).into())
+                } else {
+                    Err(error.with_note(format!("模块 {} 公开的成员有: {}", module.name, module.exports.join("、"))).into())
+                }
+            }
+        }
+    }
+    
     fn get_current_environment(&self) -> &Environment {
         &self.environments[self.current_env]
     }
@@ -554,4 +1661,122 @@ impl Interpreter {
     fn get_current_environment_mut(&mut self) -> &mut Environment {
         &mut self.environments[self.current_env]
     }
-}
\ No newline at end of file
+}
+
+/// 入口文件和每个模块的全局作用域，只有内置函数
+fn global_environment() -> Environment {
+    let mut env = Environment::new();
+    env.define("打印", Value::内置函数("内置打印".to_string()));
+    env.define("输入", Value::内置函数("内置输入".to_string()));
+    env.define("范围", Value::内置函数("内置范围".to_string()));
+    env
+}
+
+/// 内置函数 范围 的参数：`范围(止)`、`范围(起, 止)` 或 `范围(起, 止, 步)`
+fn range_arguments(arguments: &[Value], span: Span) -> Result<(f64, f64, f64), Box<Diagnostic>> {
+    match arguments {
+        [end] => range_bounds(&Value::数字(0.0), end, &Value::数字(1.0), span),
+        [start, end] => range_bounds(start, end, &Value::数字(1.0), span),
+        [start, end, step] => range_bounds(start, end, step, span),
+        _ => Err(Diagnostic::error("E0203", "范围函数需要 1 到 3 个参数").with_span(span).into()),
+    }
+}
+
+/// 检查范围的起点、终点和步长：都必须是数字，步长不能为 0
+fn range_bounds(start: &Value, end: &Value, step: &Value, span: Span) -> Result<(f64, f64, f64), Box<Diagnostic>> {
+    let (Value::数字(start), Value::数字(end), Value::数字(step)) = (start, end, step) else {
+        return Err(Diagnostic::error("E0204", "范围的起点、终点和步长必须是数字")
+            .with_span(span)
+            .with_note(format!("得到了 {}、{} 和 {}", start.type_name(), end.type_name(), step.type_name())).into());
+    };
+    if *step == 0.0 {
+        return Err(Diagnostic::error("E0204", "范围的步长不能为 0").with_span(span).into());
+    }
+    Ok((*start, *end, *step))
+}
+
+/// 范围的第 `index` 项：从 `起` 开始每次加 `步`，不包含 `止`，超出时为 None。
+/// 每一项都用乘法算出，小数步长不会累积误差；C 后端用同样的算法
+fn range_item((start, end, step): (f64, f64, f64), index: usize) -> Option<f64> {
+    let value = start + index as f64 * step;
+    if (step > 0.0 && value >= end) || (step < 0.0 && value <= end) {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+/// `范围(起, 止, 步)` 和 `起..止` 在遍历之外得到的数组
+fn range(bounds: (f64, f64, f64)) -> Value {
+    Value::array((0..).map_while(|index| range_item(bounds, index)).map(Value::数字).collect())
+}
+
+/// 给字符串和数组的元素配上下标
+fn numbered((index, value): (usize, Value)) -> (Value, Value) {
+    (Value::数字(index as f64), value)
+}
+
+/// 检查数组下标：必须是不超过长度的非负整数
+fn array_index(index: &Value, length: usize, span: Span) -> Result<usize, Box<Diagnostic>> {
+    match index {
+        Value::数字(n) if *n >= 0.0 && n.fract() == 0.0 && (*n as usize) < length => Ok(*n as usize),
+        Value::数字(n) => Err(Diagnostic::error("E0206", format!("数组索引越界: 索引 {}, 长度 {}", n, length)).with_span(span).into()),
+        other => Err(Diagnostic::error("E0204", format!("数组索引必须是数字，但得到了{}", other.type_name())).with_span(span).into()),
+    }
+}
+
+/// 字典的键必须是字符串
+fn dict_key(key: &Value, span: Span) -> Result<String, Box<Diagnostic>> {
+    match key {
+        Value::字符串(s) => Ok(s.clone()),
+        other => Err(Diagnostic::error("E0204", format!("字典的键必须是字符串，但得到了{}", other.type_name())).with_span(span).into()),
+    }
+}
+
+fn missing_key(key: &str, span: Span) -> Diagnostic {
+    Diagnostic::error("E0208", format!("字典中没有键 \"{}\"", key))
+        .with_span(span)
+        .with_note("可以先用 .包含(键) 检查键是否存在")
+}
+
+/// 内置方法的参数数量检查
+fn expect_arguments(method: &str, arguments: &[Value], count: usize, span: Span) -> Result<(), Box<Diagnostic>> {
+    if arguments.len() == count {
+        return Ok(());
+    }
+    Err(Diagnostic::error("E0203", format!("方法 {} 需要 {} 个参数，但得到了 {} 个", method, count, arguments.len())).with_span(span).into())
+}
+
+/// 各类型可以调用的方法，用于错误提示
+fn method_names(value: &Value) -> Vec<String> {
+    let builtin: &[&str] = match value {
+        Value::数组(_) => &["长度", "添加", "删除"],
+        Value::字典(_) => &["长度", "键", "值", "包含", "删除"],
+        Value::实例(instance) => {
+            return instance.borrow().class.methods.iter().map(|(name, _)| name.clone()).collect();
+        }
+        _ => &[],
+    };
+    builtin.iter().map(|name| name.to_string()).collect()
+}
+
+fn no_method(object: &Value, method: &str, span: Span) -> Diagnostic {
+    let error = Diagnostic::error("E0212", format!("{}没有方法 {}", object.type_name(), method)).with_span(span);
+    match method_names(object) {
+        names if names.is_empty() => error,
+        names => error.with_note(format!("{}的方法有: {}", object.type_name(), names.join("、"))),
+    }
+}
+
+fn no_field(object: &Value, field: &str, span: Span) -> Diagnostic {
+    let error = Diagnostic::error("E0212", format!("{}没有字段 {}", object.type_name(), field)).with_span(span);
+    if method_names(object).iter().any(|name| name == field) {
+        return error.with_note(format!("{} 是方法，调用时需要加括号: .{}()", field, field));
+    }
+    let fields = match object {
+        Value::实例(instance) => instance.borrow().class.fields.join("、"),
+        Value::错误(_) => ErrorValue::FIELDS.join("、"),
+        _ => return error,
+    };
+    error.with_note(format!("{}的字段有: {}", object.type_name(), fields))
+}
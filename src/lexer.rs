@@ -1,7 +1,10 @@
 use std::str::Chars;
 use std::iter::Peekable;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, PartialEq)]
+use crate::diagnostic::{Diagnostic, Span};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum TokenType {
     // 关键字
     如果, // if
@@ -20,11 +23,15 @@ pub enum TokenType {
     且, // and
     或, // or
     非, // not
-    数组, // array
-    长度, // length
-    添加, // append
-    删除, // remove
-    索引, // index
+    结构, // struct
+    自己, // self
+    尝试, // try
+    捕获, // catch
+    最终, // finally
+    抛出, // throw
+    导入, // import
+    作为, // as
+    公开, // pub
     
     // 字面量
     数字(f64),
@@ -45,6 +52,7 @@ pub enum TokenType {
     大于等于, // >=
     小于等于, // <=
     点, // .
+    范围, // ..
     
     // 分隔符
     左括号, // (
@@ -56,23 +64,115 @@ pub enum TokenType {
     逗号, // ,
     分号, // ;
     冒号, // :
+    箭头, // ->
     
     // 特殊
     文件结束,
     错误(String),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Token {
     pub token_type: TokenType,
     pub line: usize,
     pub column: usize,
+    pub length: usize,
+}
+
+impl std::fmt::Display for TokenType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            TokenType::如果 => "如果",
+            TokenType::否则 => "否则",
+            TokenType::循环 => "循环",
+            TokenType::当 => "当",
+            TokenType::对于 => "对于",
+            TokenType::在 => "在",
+            TokenType::函数 => "函数",
+            TokenType::返回 => "返回",
+            TokenType::让 => "让",
+            TokenType::常量 => "常量",
+            TokenType::变量 => "变量",
+            TokenType::跳出 => "跳出",
+            TokenType::继续 => "继续",
+            TokenType::且 => "且",
+            TokenType::或 => "或",
+            TokenType::非 => "非",
+            TokenType::结构 => "结构",
+            TokenType::自己 => "自己",
+            TokenType::尝试 => "尝试",
+            TokenType::捕获 => "捕获",
+            TokenType::最终 => "最终",
+            TokenType::抛出 => "抛出",
+            TokenType::导入 => "导入",
+            TokenType::作为 => "作为",
+            TokenType::公开 => "公开",
+            TokenType::数字(n) => return write!(f, "{}", n),
+            TokenType::字符串(s) => return write!(f, "\"{}\"", s),
+            TokenType::布尔(true) => "真",
+            TokenType::布尔(false) => "假",
+            TokenType::标识符(name) => name,
+            TokenType::加 => "+",
+            TokenType::减 => "-",
+            TokenType::乘 => "*",
+            TokenType::除 => "/",
+            TokenType::赋值 => "=",
+            TokenType::等于 => "==",
+            TokenType::不等于 => "!=",
+            TokenType::大于 => ">",
+            TokenType::小于 => "<",
+            TokenType::大于等于 => ">=",
+            TokenType::小于等于 => "<=",
+            TokenType::点 => ".",
+            TokenType::范围 => "..",
+            TokenType::左括号 => "(",
+            TokenType::右括号 => ")",
+            TokenType::左大括号 => "{",
+            TokenType::右大括号 => "}",
+            TokenType::左中括号 => "[",
+            TokenType::右中括号 => "]",
+            TokenType::逗号 => ",",
+            TokenType::分号 => ";",
+            TokenType::冒号 => ":",
+            TokenType::箭头 => "->",
+            TokenType::文件结束 => "文件结束",
+            TokenType::错误(msg) => msg,
+        };
+        write!(f, "{}", text)
+    }
+}
+
+/// 所有关键字，供编辑器补全使用；`类` 是 `结构` 的别名，不单独列出
+pub const KEYWORDS: [&str; 27] = [
+    "如果", "否则", "循环", "当", "对于", "在", "函数", "返回", "让", "常量", "变量", "跳出", "继续", "且", "或", "非",
+    "结构", "自己", "尝试", "捕获", "最终", "抛出", "导入", "作为", "公开", "真", "假",
+];
+
+impl Token {
+    pub fn span(&self) -> Span {
+        Span::new(self.line, self.column, self.length)
+    }
+}
+
+/// `//` 注释不产生记号，另外保存下来供格式化使用
+#[derive(Debug, Clone)]
+pub struct Comment {
+    /// `//` 之后到行尾的文本
+    pub text: String,
+    pub line: usize,
+    pub column: usize,
+    /// 同一行中注释前面有代码
+    pub trailing: bool,
 }
 
 pub struct Lexer<'a> {
     input: Peekable<Chars<'a>>,
     line: usize,
     column: usize,
+    warnings: Vec<Diagnostic>,
+    comments: Vec<Comment>,
+    /// 上一个记号结束的行，用来判断注释是否跟在代码后面
+    last_token_line: usize,
 }
 
 impl<'a> Lexer<'a> {
@@ -81,10 +181,23 @@ impl<'a> Lexer<'a> {
             input: input.chars().peekable(),
             line: 1,
             column: 1,
+            warnings: Vec::new(),
+            comments: Vec::new(),
+            last_token_line: 0,
         }
     }
     
-    pub fn tokenize(&mut self) -> Result<Vec<Token>, String> {
+    /// 取出词法分析过程中产生的警告
+    pub fn take_warnings(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.warnings)
+    }
+    
+    /// 取出词法分析过程中跳过的注释，按在源代码中的顺序排列
+    pub fn take_comments(&mut self) -> Vec<Comment> {
+        std::mem::take(&mut self.comments)
+    }
+    
+    pub fn tokenize(&mut self) -> Result<Vec<Token>, Box<Diagnostic>> {
         let mut tokens = Vec::new();
         
         while let Some(&c) = self.input.peek() {
@@ -93,68 +206,88 @@ impl<'a> Lexer<'a> {
                 continue;
             }
             
-            let token = match c {
-                '+' => self.make_single_char_token(TokenType::加),
-                '-' => self.make_single_char_token(TokenType::减),
-                '*' => self.make_single_char_token(TokenType::乘),
+            let start_line = self.line;
+            let start_column = self.column;
+            
+            let token_type = match c {
+                '+' => self.single_char(TokenType::加),
+                '-' => self.handle_minus(),
+                '*' => self.single_char(TokenType::乘),
                 '/' => {
                     // 检查是否是注释
                     if let Some('/') = self.input.clone().nth(1) {
                         // 是注释，跳过到行尾
                         self.advance(); // 跳过第一个/
                         self.advance(); // 跳过第二个/
+                        let mut text = String::new();
                         while let Some(&next_c) = self.input.peek() {
                             if next_c == '\n' {
                                 break;
                             }
+                            text.push(next_c);
                             self.advance();
                         }
+                        self.comments.push(Comment {
+                            text,
+                            line: start_line,
+                            column: start_column,
+                            trailing: self.last_token_line == start_line,
+                        });
                         continue; // 继续下一个字符
                     } else {
-                        self.make_single_char_token(TokenType::除)
+                        self.single_char(TokenType::除)
                     }
                 }
                 '=' => self.handle_equals(),
+                '!' => self.handle_bang(),
                 '>' => self.handle_greater(),
                 '<' => self.handle_less(),
-                '(' => self.make_single_char_token(TokenType::左括号),
-                ')' => self.make_single_char_token(TokenType::右括号),
-                '{' => self.make_single_char_token(TokenType::左大括号),
-                '}' => self.make_single_char_token(TokenType::右大括号),
-                '[' => self.make_single_char_token(TokenType::左中括号),
-                ']' => self.make_single_char_token(TokenType::右中括号),
-                ',' => self.make_single_char_token(TokenType::逗号),
-                ';' => self.make_single_char_token(TokenType::分号),
-                ':' => self.make_single_char_token(TokenType::冒号),
-                '.' => self.make_single_char_token(TokenType::点),
+                '(' => self.single_char(TokenType::左括号),
+                ')' => self.single_char(TokenType::右括号),
+                '{' => self.single_char(TokenType::左大括号),
+                '}' => self.single_char(TokenType::右大括号),
+                '[' => self.single_char(TokenType::左中括号),
+                ']' => self.single_char(TokenType::右中括号),
+                ',' => self.single_char(TokenType::逗号),
+                ';' => self.single_char(TokenType::分号),
+                ':' => self.single_char(TokenType::冒号),
+                '.' => self.handle_dot(),
                 '"' => self.string_literal(),
-                _ if c.is_digit(10) => self.number_literal(),
-                _ if self.is_chinese_char(c) || c.is_alphabetic() => self.identifier_or_keyword(),
-                _ => Token {
-                    token_type: TokenType::错误(format!("未知字符: {}", c)),
-                    line: self.line,
-                    column: self.column,
-                },
+                _ if c.is_ascii_digit() => self.number_literal(),
+                _ if self.is_chinese_char(c) || c.is_alphabetic() || c == '_' => self.identifier_or_keyword(),
+                _ => {
+                    self.advance();
+                    TokenType::错误(format!("未知字符: {}", c))
+                }
+            };
+            
+            // 跨行的记号（比如多行字符串）只标出第一个字符
+            let length = if self.line == start_line { self.column - start_column } else { 1 };
+            let token = Token {
+                token_type,
+                line: start_line,
+                column: start_column,
+                length,
             };
             
-            if let TokenType::错误(_) = token.token_type {
-                return Err(format!("词法错误: {} (行: {}, 列: {})", 
-                    match token.token_type {
-                        TokenType::错误(msg) => msg,
-                        _ => String::new(),
-                    },
-                    token.line,
-                    token.column
-                ));
+            if let TokenType::错误(msg) = &token.token_type {
+                let code = match msg.as_str() {
+                    "未闭合的字符串" => "E0002",
+                    "无效的数字格式" => "E0003",
+                    _ => "E0001",
+                };
+                return Err(Diagnostic::error(code, format!("词法错误: {}", msg)).with_span(token.span()).into());
             }
             
             tokens.push(token);
+            self.last_token_line = self.line;
         }
         
         tokens.push(Token {
             token_type: TokenType::文件结束,
             line: self.line,
             column: self.column,
+            length: 1,
         });
         
         Ok(tokens)
@@ -171,91 +304,87 @@ impl<'a> Lexer<'a> {
         }
     }
     
-    fn make_single_char_token(&mut self, token_type: TokenType) -> Token {
-        let token = Token {
-            token_type,
-            line: self.line,
-            column: self.column,
-        };
+    fn single_char(&mut self, token_type: TokenType) -> TokenType {
         self.advance();
-        token
+        token_type
     }
     
-    fn handle_equals(&mut self) -> Token {
-        let start_column = self.column;
+    fn handle_equals(&mut self) -> TokenType {
         self.advance();
         if let Some(&'=') = self.input.peek() {
             self.advance();
-            Token {
-                token_type: TokenType::等于,
-                line: self.line,
-                column: start_column,
-            }
+            TokenType::等于
         } else {
-            Token {
-                token_type: TokenType::赋值,
-                line: self.line,
-                column: start_column,
-            }
+            TokenType::赋值
         }
     }
     
-    fn handle_greater(&mut self) -> Token {
-        let start_column = self.column;
+    /// `..` 是范围，单独的 `.` 访问成员
+    fn handle_dot(&mut self) -> TokenType {
+        self.advance();
+        if let Some(&'.') = self.input.peek() {
+            self.advance();
+            TokenType::范围
+        } else {
+            TokenType::点
+        }
+    }
+    
+    /// `!=` 是不等于，单独的 `!` 与关键字 `非` 相同
+    fn handle_bang(&mut self) -> TokenType {
         self.advance();
         if let Some(&'=') = self.input.peek() {
             self.advance();
-            Token {
-                token_type: TokenType::大于等于,
-                line: self.line,
-                column: start_column,
-            }
+            TokenType::不等于
         } else {
-            Token {
-                token_type: TokenType::大于,
-                line: self.line,
-                column: start_column,
-            }
+            TokenType::非
         }
     }
     
-    fn handle_less(&mut self) -> Token {
-        let start_column = self.column;
+    /// `->` 引出函数的返回类型，单独的 `-` 是减号
+    fn handle_minus(&mut self) -> TokenType {
+        self.advance();
+        if let Some(&'>') = self.input.peek() {
+            self.advance();
+            TokenType::箭头
+        } else {
+            TokenType::减
+        }
+    }
+    
+    fn handle_greater(&mut self) -> TokenType {
         self.advance();
         if let Some(&'=') = self.input.peek() {
             self.advance();
-            Token {
-                token_type: TokenType::小于等于,
-                line: self.line,
-                column: start_column,
-            }
+            TokenType::大于等于
         } else {
-            Token {
-                token_type: TokenType::小于,
-                line: self.line,
-                column: start_column,
-            }
+            TokenType::大于
         }
     }
     
-    fn string_literal(&mut self) -> Token {
+    fn handle_less(&mut self) -> TokenType {
+        self.advance();
+        if let Some(&'=') = self.input.peek() {
+            self.advance();
+            TokenType::小于等于
+        } else {
+            TokenType::小于
+        }
+    }
+    
+    fn string_literal(&mut self) -> TokenType {
         self.advance(); // 跳过开始的引号
         let mut value = String::new();
-        let start_line = self.line;
-        let start_column = self.column;
         
         while let Some(&c) = self.input.peek() {
             if c == '"' {
                 self.advance();
-                return Token {
-                    token_type: TokenType::字符串(value),
-                    line: start_line,
-                    column: start_column,
-                };
+                return TokenType::字符串(value);
             }
             
             // 处理转义字符
             if c == '\\' {
+                let escape_column = self.column;
                 self.advance(); // 跳过反斜杠
                 if let Some(&escaped_char) = self.input.peek() {
                     let actual_char = match escaped_char {
@@ -264,7 +393,15 @@ impl<'a> Lexer<'a> {
                         'r' => '\r',
                         '\\' => '\\',
                         '"' => '"',
-                        _ => escaped_char, // 未知的转义序列，原样保留
+                        _ => {
+                            // 未知的转义序列，原样保留
+                            self.warnings.push(
+                                Diagnostic::warning("W0001", format!("未知的转义序列: \\{}", escaped_char))
+                                    .with_span(Span::new(self.line, escape_column, 2))
+                                    .with_note("支持的转义序列有 \\n、\\t、\\r、\\\\ 和 \\\""),
+                            );
+                            escaped_char
+                        }
                     };
                     value.push(actual_char);
                     self.advance();
@@ -277,24 +414,18 @@ impl<'a> Lexer<'a> {
             }
         }
         
-        Token {
-            token_type: TokenType::错误("未闭合的字符串".to_string()),
-            line: start_line,
-            column: start_column,
-        }
+        TokenType::错误("未闭合的字符串".to_string())
     }
     
-    fn number_literal(&mut self) -> Token {
+    fn number_literal(&mut self) -> TokenType {
         let mut value = String::new();
-        let start_line = self.line;
-        let start_column = self.column;
         let mut has_decimal = false;
         
         while let Some(&c) = self.input.peek() {
-            if c.is_digit(10) {
+            if c.is_ascii_digit() {
                 value.push(c);
                 self.advance();
-            } else if c == '.' && !has_decimal {
+            } else if c == '.' && !has_decimal && self.digit_after_dot() {
                 value.push(c);
                 has_decimal = true;
                 self.advance();
@@ -303,25 +434,21 @@ impl<'a> Lexer<'a> {
             }
         }
         
-        if let Ok(num) = value.parse::<f64>() {
-            Token {
-                token_type: TokenType::数字(num),
-                line: start_line,
-                column: start_column,
-            }
-        } else {
-            Token {
-                token_type: TokenType::错误("无效的数字格式".to_string()),
-                line: start_line,
-                column: start_column,
-            }
+        match value.parse::<f64>() {
+            Ok(num) => TokenType::数字(num),
+            Err(_) => TokenType::错误("无效的数字格式".to_string()),
         }
     }
     
-    fn identifier_or_keyword(&mut self) -> Token {
+    /// 小数点后面必须是数字，这样 `1..10` 中的 `1.` 不会被当成小数
+    fn digit_after_dot(&self) -> bool {
+        let mut ahead = self.input.clone();
+        ahead.next();
+        ahead.peek().is_some_and(|c| c.is_ascii_digit())
+    }
+    
+    fn identifier_or_keyword(&mut self) -> TokenType {
         let mut value = String::new();
-        let start_line = self.line;
-        let start_column = self.column;
         
         while let Some(&c) = self.input.peek() {
             if self.is_chinese_char(c) || c.is_alphanumeric() || c == '_' {
@@ -332,7 +459,7 @@ impl<'a> Lexer<'a> {
             }
         }
         
-        let token_type = match value.as_str() {
+        match value.as_str() {
             "如果" => TokenType::如果,
             "否则" => TokenType::否则,
             "循环" => TokenType::循环,
@@ -349,21 +476,19 @@ impl<'a> Lexer<'a> {
             "且" => TokenType::且,
             "或" => TokenType::或,
             "非" => TokenType::非,
+            "结构" | "类" => TokenType::结构,
+            "自己" => TokenType::自己,
+            "尝试" => TokenType::尝试,
+            "捕获" => TokenType::捕获,
+            "最终" => TokenType::最终,
+            "抛出" => TokenType::抛出,
+            "导入" => TokenType::导入,
+            "作为" => TokenType::作为,
+            "公开" => TokenType::公开,
             // "数组"、"长度"、"添加"、"删除"、"索引" 不再作为关键字，而是作为标识符处理
-            // "数组" => TokenType::数组,
-            // "长度" => TokenType::长度,
-            // "添加" => TokenType::添加,
-            // "删除" => TokenType::删除,
-            // "索引" => TokenType::索引,
             "真" => TokenType::布尔(true),
             "假" => TokenType::布尔(false),
             _ => TokenType::标识符(value),
-        };
-        
-        Token {
-            token_type,
-            line: start_line,
-            column: start_column,
         }
     }
     
@@ -0,0 +1,403 @@
+//! 语言服务器：通过标准输入输出与编辑器交换 LSP 消息
+//!
+//! 文档每次打开或修改时重新做词法分析、语法分析和静态检查，并发布诊断信息；
+//! 跳转到定义、查找引用、悬停、补全和文档符号都基于 [`resolver`] 的名字解析结果。
+//! 编辑器同步的是整个文档的文本，LSP 的位置从 0 开始，列按 UTF-16 编码单元计算。
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Read, Write};
+use std::process::ExitCode;
+
+use serde_json::{json, Value};
+
+use crate::ast::{Program, Statement};
+use crate::checker;
+use crate::diagnostic::{Diagnostic, Severity, Span};
+use crate::lexer::{Lexer, KEYWORDS};
+use crate::parser::Parser;
+use crate::resolver::{self, Resolution, SymbolKind};
+use crate::types;
+
+/// 方法不存在时的 JSON-RPC 错误码
+const METHOD_NOT_FOUND: i64 = -32601;
+/// 消息内容不是合法的 JSON 时的 JSON-RPC 错误码
+const PARSE_ERROR: i64 = -32700;
+/// 消息不能处理时的 JSON-RPC 错误码，例如超过长度上限
+const INVALID_REQUEST: i64 = -32600;
+/// 一条消息的最大字节数，更长的消息跳过而不读入内存
+const MAX_MESSAGE_LENGTH: usize = 64 * 1024 * 1024;
+
+/// 打开的文档和它的分析结果
+struct Document {
+    text: String,
+    program: Program,
+    resolution: Resolution,
+    /// 与 `cnlang check` 相同的检查，但语法错误之后继续解析，一次报告所有语法错误；
+    /// 有语法错误时不做静态检查
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Document {
+    fn new(text: String) -> Self {
+        let mut lexer = Lexer::new(&text);
+        let tokens = lexer.tokenize();
+        let mut diagnostics = lexer.take_warnings();
+        let (program, errors) = match tokens {
+            Ok(tokens) => Parser::new(tokens).parse_with_recovery(),
+            Err(error) => (Program::new(), vec![*error]),
+        };
+        let mut resolution = resolver::resolve(&program);
+        if errors.is_empty() {
+            diagnostics.extend(checker::check(&program));
+            diagnostics.extend(types::check(&program, &resolution));
+            diagnostics.append(&mut resolution.diagnostics);
+        } else {
+            diagnostics.extend(errors);
+        }
+        Document {
+            text,
+            program,
+            resolution,
+            diagnostics,
+        }
+    }
+    
+    /// LSP 的位置转换为源代码中的 (行, 列)，两者都从 1 开始
+    fn position(&self, position: &Value) -> (usize, usize) {
+        let line = position["line"].as_u64().unwrap_or(0) as usize;
+        let character = position["character"].as_u64().unwrap_or(0) as usize;
+        let text = self.text.lines().nth(line).unwrap_or("");
+        let mut units = 0;
+        let mut column = 1;
+        for c in text.chars() {
+            if units >= character {
+                break;
+            }
+            units += c.len_utf16();
+            column += 1;
+        }
+        (line + 1, column)
+    }
+    
+    fn range(&self, span: Span) -> Value {
+        let text = self.text.lines().nth(span.line.saturating_sub(1)).unwrap_or("");
+        let units = |chars: usize| -> usize { text.chars().take(chars).map(char::len_utf16).sum() };
+        let start = span.column.saturating_sub(1);
+        let line = span.line.saturating_sub(1);
+        json!({
+            "start": { "line": line, "character": units(start) },
+            "end": { "line": line, "character": units(start + span.length) },
+        })
+    }
+}
+
+pub fn run() -> ExitCode {
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let mut server = Server {
+        documents: HashMap::new(),
+        shutdown: false,
+    };
+    
+    while let Some(message) = read_message(&mut input) {
+        // 无法处理的消息不知道 id，回复的 id 为 null，之后继续读下一条消息
+        let message = match message {
+            Ok(message) => message,
+            Err(BadMessage { code, message }) => {
+                write_message(&json!({ "jsonrpc": "2.0", "id": null, "error": { "code": code, "message": message } }));
+                continue;
+            }
+        };
+        let method = message["method"].as_str().unwrap_or("");
+        if method == "exit" {
+            return if server.shutdown { ExitCode::SUCCESS } else { ExitCode::FAILURE };
+        }
+        let params = &message["params"];
+        match message.get("id") {
+            // 有 id 的是请求，必须回复
+            Some(id) => {
+                let response = match server.request(method, params) {
+                    Some(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    None => json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": METHOD_NOT_FOUND, "message": format!("不支持的方法: {}", method) },
+                    }),
+                };
+                write_message(&response);
+            }
+            None => server.notification(method, params),
+        }
+    }
+    // 编辑器没有发送 exit 就关闭了输入
+    ExitCode::FAILURE
+}
+
+struct Server {
+    documents: HashMap<String, Document>,
+    /// 收到了 shutdown 请求，之后的 exit 以成功的退出码结束
+    shutdown: bool,
+}
+
+impl Server {
+    /// 处理请求，不支持的方法返回 `None`
+    fn request(&mut self, method: &str, params: &Value) -> Option<Value> {
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "hoverProvider": true,
+                    "completionProvider": {},
+                    "documentSymbolProvider": true,
+                },
+                "serverInfo": { "name": "cnlang", "version": env!("CARGO_PKG_VERSION") },
+            }),
+            "shutdown" => {
+                self.shutdown = true;
+                Value::Null
+            }
+            "textDocument/definition" => self.definition(params),
+            "textDocument/references" => self.references(params),
+            "textDocument/hover" => self.hover(params),
+            "textDocument/completion" => self.completion(params),
+            "textDocument/documentSymbol" => self.document_symbols(params),
+            _ => return None,
+        };
+        Some(result)
+    }
+    
+    /// 处理通知，不支持的通知直接忽略
+    fn notification(&mut self, method: &str, params: &Value) {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or("").to_string();
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or("").to_string();
+                self.open(uri, text);
+            }
+            "textDocument/didChange" => {
+                // 同步方式是整个文档，最后一次修改就是当前的文本
+                let changes = params["contentChanges"].as_array();
+                if let Some(text) = changes.and_then(|changes| changes.last()).and_then(|change| change["text"].as_str()) {
+                    self.open(uri, text.to_string());
+                }
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                publish_diagnostics(&uri, Vec::new());
+            }
+            _ => {}
+        }
+    }
+    
+    fn open(&mut self, uri: String, text: String) {
+        let document = Document::new(text);
+        let diagnostics = document.diagnostics.iter().map(|d| to_lsp(&document, d)).collect();
+        publish_diagnostics(&uri, diagnostics);
+        self.documents.insert(uri, document);
+    }
+    
+    /// 请求中的文档和光标处名字对应的声明
+    fn symbol_at<'a>(&'a self, params: &'a Value) -> Option<(&'a str, &'a Document, usize)> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let document = self.documents.get(uri)?;
+        let (line, column) = document.position(&params["position"]);
+        let symbol = document.resolution.symbol_at(line, column)?;
+        Some((uri, document, symbol))
+    }
+    
+    fn definition(&self, params: &Value) -> Value {
+        let Some((uri, document, symbol)) = self.symbol_at(params) else {
+            return Value::Null;
+        };
+        match document.resolution.symbols[symbol].span {
+            Some(span) => json!({ "uri": uri, "range": document.range(span) }),
+            None => Value::Null,
+        }
+    }
+    
+    fn references(&self, params: &Value) -> Value {
+        let Some((uri, document, symbol)) = self.symbol_at(params) else {
+            return json!([]);
+        };
+        let include_declaration = params["context"]["includeDeclaration"].as_bool().unwrap_or(true);
+        let declaration = document.resolution.symbols[symbol].span;
+        let locations: Vec<Value> = document
+            .resolution
+            .references_to(symbol)
+            .into_iter()
+            .filter(|&span| include_declaration || Some(span) != declaration)
+            .map(|span| json!({ "uri": uri, "range": document.range(span) }))
+            .collect();
+        json!(locations)
+    }
+    
+    fn hover(&self, params: &Value) -> Value {
+        let Some((_, document, symbol)) = self.symbol_at(params) else {
+            return Value::Null;
+        };
+        let signature = &document.resolution.symbols[symbol].signature;
+        json!({
+            "contents": { "kind": "markdown", "value": format!("```cnlang\n{}\n```", signature) },
+        })
+    }
+    
+    /// 关键字和光标处可以使用的名字
+    fn completion(&self, params: &Value) -> Value {
+        let mut items: Vec<Value> = KEYWORDS
+            .iter()
+            .map(|keyword| json!({ "label": keyword, "kind": 14 }))
+            .collect();
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+        if let Some(document) = self.documents.get(uri) {
+            let (line, column) = document.position(&params["position"]);
+            for symbol in document.resolution.visible_at(line, column) {
+                items.push(json!({
+                    "label": symbol.name,
+                    "kind": completion_kind(symbol.kind),
+                    "detail": symbol.signature,
+                }));
+            }
+        }
+        json!(items)
+    }
+    
+    /// 顶层的声明，结构的方法放在结构下面
+    fn document_symbols(&self, params: &Value) -> Value {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+        let Some(document) = self.documents.get(uri) else {
+            return json!([]);
+        };
+        let symbols: Vec<Value> = document
+            .program
+            .语句
+            .iter()
+            .filter_map(|statement| document_symbol(document, statement))
+            .collect();
+        json!(symbols)
+    }
+}
+
+/// 声明语句对应的文档符号，其他语句没有符号
+fn document_symbol(document: &Document, statement: &Statement) -> Option<Value> {
+    let (name, kind, span, children) = match statement {
+        Statement::变量声明 { 名字, 是常量, 位置, .. } => (名字.clone(), if *是常量 { 14 } else { 13 }, *位置, Vec::new()),
+        Statement::函数声明 { 名字, 位置, .. } => (名字.clone(), 12, *位置, Vec::new()),
+        Statement::结构声明 { 名字, 方法, 位置, .. } => {
+            let methods = 方法
+                .iter()
+                .filter_map(|method| match method {
+                    Statement::函数声明 { 名字, 位置, .. } => Some(json!({
+                        "name": 名字,
+                        "kind": 6,
+                        "range": document.range(*位置),
+                        "selectionRange": document.range(*位置),
+                    })),
+                    _ => None,
+                })
+                .collect();
+            (名字.clone(), 23, *位置, methods)
+        }
+        Statement::导入语句 { 路径, 位置, .. } => (路径.clone(), 2, *位置, Vec::new()),
+        _ => return None,
+    };
+    Some(json!({
+        "name": name,
+        "kind": kind,
+        "range": document.range(span),
+        "selectionRange": document.range(span),
+        "children": children,
+    }))
+}
+
+/// LSP 的 CompletionItemKind
+fn completion_kind(kind: SymbolKind) -> u8 {
+    match kind {
+        SymbolKind::函数 | SymbolKind::内置函数 => 3,
+        SymbolKind::变量 | SymbolKind::参数 => 6,
+        SymbolKind::模块 => 9,
+        SymbolKind::常量 => 21,
+        SymbolKind::结构 => 22,
+    }
+}
+
+fn to_lsp(document: &Document, diagnostic: &Diagnostic) -> Value {
+    let range = match diagnostic.span {
+        Some(span) => document.range(span),
+        None => document.range(Span::new(1, 1, 0)),
+    };
+    let severity = match diagnostic.severity {
+        Severity::错误 => 1,
+        Severity::警告 => 2,
+    };
+    let mut message = diagnostic.message.clone();
+    for note in &diagnostic.notes {
+        message.push_str(&format!("\n注: {}", note));
+    }
+    json!({
+        "range": range,
+        "severity": severity,
+        "code": diagnostic.code,
+        "source": "cnlang",
+        "message": message,
+    })
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) {
+    write_message(&json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    }));
+}
+
+/// 读过之后不能处理的消息和要回复的 JSON-RPC 错误
+struct BadMessage {
+    code: i64,
+    message: String,
+}
+
+/// 读取一条消息；输入结束或消息头不完整时返回 None，内容不是 JSON 或太长时返回错误
+fn read_message(input: &mut impl BufRead) -> Option<Result<Value, BadMessage>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header).ok()? == 0 {
+            return None;
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let length = length?;
+    if length > MAX_MESSAGE_LENGTH {
+        let skipped = io::copy(&mut Read::take(&mut *input, length as u64), &mut io::sink()).ok()?;
+        if skipped < length as u64 {
+            return None;
+        }
+        return Some(Err(BadMessage {
+            code: INVALID_REQUEST,
+            message: format!("消息长度 {} 超过上限 {} 字节", length, MAX_MESSAGE_LENGTH),
+        }));
+    }
+    let mut body = vec![0; length];
+    input.read_exact(&mut body).ok()?;
+    Some(serde_json::from_slice(&body).map_err(|e| BadMessage {
+        code: PARSE_ERROR,
+        message: format!("无法解析消息: {}", e),
+    }))
+}
+
+fn write_message(message: &Value) {
+    let body = message.to_string();
+    let mut stdout = io::stdout().lock();
+    let _ = write!(stdout, "Content-Length: {}\r\n\r\n{}", body.len(), body);
+    let _ = stdout.flush();
+}
@@ -1,122 +1,310 @@
-use std::env;
+
 use std::fs;
-use std::process;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::rc::Rc;
+
+use clap::Parser as _;
 
 mod lexer;
 mod parser;
 mod interpreter;
 mod ast;
 mod code_generator;
+mod diagnostic;
+mod checker;
+mod repl;
+mod module;
+mod toolchain;
+mod cli;
+mod formatter;
+mod resolver;
+mod lsp;
+mod types;
+mod c_support;
 
-use lexer::Lexer;
+use lexer::{Lexer, Token};
 use parser::Parser;
 use interpreter::Interpreter;
 use code_generator::{CodeGenerator, OutputType};
+use diagnostic::{Diagnostic, Severity, SourceFile};
+use ast::Program;
+use resolver::Resolution;
+use module::ModuleLoader;
+use toolchain::Toolchain;
+use cli::{Cli, Command, Target};
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    
-    if args.len() < 2 {
-        println!("用法: cnlang <文件名.cn> [选项]");
-        println!("选项:");
-        println!("  --compile     编译为可执行文件");
-        println!("  --dll         编译为动态链接库");
-        println!("  --object      编译为目标文件");
-        println!("  --c           生成C源代码");
-        println!("  --output <文件>  指定输出文件名");
-        process::exit(1);
-    }
-    
-    let filename = &args[1];
-    let mut compile_mode = false;
-    let mut output_type = None;
-    let mut output_file = None;
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let command = match (cli.command, cli.file) {
+        (Some(command), _) => command,
+        (None, Some(file)) => Command::Run { file, json: false },
+        (None, None) => Command::Repl,
+    };
     
-    // 解析命令行参数
-    for i in 2..args.len() {
-        match args[i].as_str() {
-            "--compile" => {
-                compile_mode = true;
-                output_type = Some(OutputType::Exe);
-            }
-            "--dll" => {
-                compile_mode = true;
-                output_type = Some(OutputType::Dll);
-            }
-            "--object" => {
-                compile_mode = true;
-                output_type = Some(OutputType::Object);
-            }
-            "--c" => {
-                compile_mode = true;
-                output_type = Some(OutputType::CSource);
+    match command {
+        Command::Run { file, json } => with_source(&file, |code| {
+            if json {
+                run_json(code, &file)?;
+            } else {
+                run_code(code, &file)?;
             }
-            "--output" => {
-                if i + 1 < args.len() {
-                    output_file = Some(args[i + 1].clone());
+            println!("程序执行完成");
+            Ok(())
+        }),
+        Command::Build(args) => with_source(&args.file, |code| {
+            let output_path = args
+                .output
+                .clone()
+                .unwrap_or_else(|| format!("{}{}", args.file.trim_end_matches(".cn"), args.target.suffix()));
+            // 只生成 C 源代码时不需要编译器
+            let toolchain = match args.target {
+                Target::C => None,
+                _ => {
+                    let mut toolchain = Toolchain::find(args.cc.as_deref()).map_err(|e| vec![Diagnostic::error("E0300", e)])?;
+                    toolchain.opt_level = args.opt_level.clone();
+                    toolchain.debug = args.debug;
+                    Some(toolchain)
                 }
-            }
-            _ => {}
-        }
-    }
-    
-    match fs::read_to_string(filename) {
-        Ok(content) => {
-            if compile_mode {
-                // 编译模式
-                let output_path = output_file.unwrap_or_else(|| {
-                    let base_name = filename.trim_end_matches(".cn");
-                    match output_type {
-                        Some(OutputType::Exe) => format!("{}.exe", base_name),
-                        Some(OutputType::Dll) => format!("{}.dll", base_name),
-                        Some(OutputType::Object) => format!("{}.o", base_name),
-                        Some(OutputType::CSource) => format!("{}.c", base_name),
-                        None => format!("{}.exe", base_name),
+            };
+            compile_code(code, &args.file, args.target.output_type(), toolchain, Path::new(&output_path))?;
+            println!("编译完成: {}", output_path);
+            Ok(())
+        }),
+        Command::Check { file } => with_source(&file, |code| {
+            let program = parse_source(code, &file)?;
+            ModuleLoader::new().load_imports(&program, &source_directory(&file), None)?;
+            println!("检查通过: {}", file);
+            Ok(())
+        }),
+        Command::Fmt { files, check } => {
+            let mut failed = false;
+            for file in &files {
+                let status = with_source(file, |code| {
+                    let formatted = format_source(code, file)?;
+                    if formatted == code {
+                        return Ok(());
                     }
+                    if check {
+                        println!("需要格式化: {}", file);
+                        failed = true;
+                    } else if let Err(e) = fs::write(file, formatted) {
+                        eprintln!("无法写入文件 {}: {}", file, e);
+                        failed = true;
+                    }
+                    Ok(())
                 });
-                
-                let output_type = output_type.unwrap_or(OutputType::Exe);
-                match compile_code(&content, output_type, &output_path) {
-                    Ok(_) => println!("编译完成: {}", output_path),
-                    Err(e) => println!("编译错误: {}", e),
-                }
+                failed |= status != ExitCode::SUCCESS;
+            }
+            if failed {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            }
+        }
+        Command::Ast { file, json } => with_source(&file, |code| {
+            let program = parse_syntax(code, &file)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&program).expect("语法树总能转换为 JSON"));
             } else {
-                // 解释执行模式
-                match run_code(&content) {
-                    Ok(_) => println!("程序执行完成"),
-                    Err(e) => println!("执行错误: {}", e),
+                println!("{}", program);
+            }
+            Ok(())
+        }),
+        Command::Tokens { file, json } => with_source(&file, |code| {
+            let tokens = tokenize(code, &file)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&tokens).expect("记号总能转换为 JSON"));
+            } else {
+                for token in tokens {
+                    println!("{}:{}\t{:?}", token.line, token.column, token.token_type);
                 }
             }
+            Ok(())
+        }),
+        Command::Repl => {
+            repl::run();
+            ExitCode::SUCCESS
         }
+        Command::Lsp => lsp::run(),
+    }
+}
+
+/// 读取源文件并执行 `action`；出错时输出诊断信息，返回失败的退出码
+fn with_source(filename: &str, action: impl FnOnce(&str) -> Result<(), Vec<Diagnostic>>) -> ExitCode {
+    let code = match fs::read_to_string(filename) {
+        Ok(code) => code,
         Err(e) => {
-            println!("无法读取文件 {}: {}", filename, e);
-            process::exit(1);
+            eprintln!("无法读取文件 {}: {}", filename, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    match action(&code) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(errors) => {
+            report_errors(&errors, &code, filename);
+            ExitCode::FAILURE
         }
     }
 }
 
-fn run_code(code: &str) -> Result<(), String> {
-    let mut lexer = Lexer::new(code);
-    let tokens = lexer.tokenize()?;
-    
-    let mut parser = Parser::new(tokens);
-    let ast = parser.parse()?;
+fn run_code(code: &str, filename: &str) -> Result<(), Vec<Diagnostic>> {
+    let ast = parse_source(code, filename)?;
+    execute(&ast, filename)
+}
+
+/// 运行 `ast --json` 输出的语法树；语法树先经过与源文件相同的静态检查。
+/// JSON 中的位置属于原来的源文件，报错时只显示行列号，不显示 JSON 的内容
+fn run_json(json: &str, filename: &str) -> Result<(), Vec<Diagnostic>> {
+    let ast: Program = serde_json::from_str(json)
+        .map_err(|e| vec![Diagnostic::error("E0108", format!("无法解析 JSON 语法树: {}", e))])?;
+    let source = Some(Rc::new(SourceFile {
+        name: filename.to_string(),
+        text: String::new(),
+    }));
+    let without_text = |errors: Vec<Diagnostic>| -> Vec<Diagnostic> {
+        errors.into_iter().map(|e| e.with_source(source.clone())).collect()
+    };
     
+    let resolution = resolver::resolve(&ast);
+    let (errors, warnings): (Vec<_>, Vec<_>) = static_checks(&ast, resolution)
+        .into_iter()
+        .partition(|d| d.severity == Severity::错误);
+    for warning in warnings {
+        eprint!("{}", warning.render("", filename));
+    }
+    if !errors.is_empty() {
+        return Err(without_text(errors));
+    }
+    execute(&ast, filename).map_err(without_text)
+}
+
+fn execute(ast: &Program, filename: &str) -> Result<(), Vec<Diagnostic>> {
     let mut interpreter = Interpreter::new();
-    interpreter.execute(&ast)?;
+    interpreter.load_imports(ast, &source_directory(filename))?;
+    interpreter.execute(ast).map_err(|e| vec![*e])?;
     
     Ok(())
 }
 
-fn compile_code(code: &str, output_type: OutputType, output_path: &str) -> Result<(), String> {
-    let mut lexer = Lexer::new(code);
-    let tokens = lexer.tokenize()?;
+fn compile_code(
+    code: &str,
+    filename: &str,
+    output_type: OutputType,
+    toolchain: Option<Toolchain>,
+    output_path: &Path,
+) -> Result<(), Vec<Diagnostic>> {
+    let ast = parse_source(code, filename)?;
     
-    let mut parser = Parser::new(tokens);
-    let ast = parser.parse()?;
+    let directory = source_directory(filename);
+    let mut modules = ModuleLoader::new();
+    modules.load_imports(&ast, &directory, None)?;
+    unsupported_in_c(&ast, &modules)?;
     
-    let code_generator = CodeGenerator::new(output_type);
-    code_generator.generate(&ast, output_path)?;
+    let mut code_generator = CodeGenerator::new(output_type).with_modules(modules, directory);
+    if let Some(toolchain) = toolchain {
+        code_generator = code_generator.with_toolchain(toolchain);
+    }
+    code_generator
+        .generate(&ast, output_path)
+        .map_err(|e| vec![Diagnostic::error("E0300", format!("编译错误: {}", e))])?;
     
     Ok(())
-}
\ No newline at end of file
+}
+
+/// 入口文件和导入的模块中 C 后端不支持的用法，模块中的错误显示在模块文件中
+fn unsupported_in_c(ast: &Program, modules: &ModuleLoader) -> Result<(), Vec<Diagnostic>> {
+    let mut errors = c_support::check(ast);
+    for module in modules.modules() {
+        let source = Some(module.source.clone());
+        errors.extend(c_support::check(&module.program).into_iter().map(|e| e.with_source(source.clone())));
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// 词法分析，警告直接输出到标准错误
+fn tokenize(code: &str, filename: &str) -> Result<Vec<Token>, Vec<Diagnostic>> {
+    let mut lexer = Lexer::new(code);
+    let tokens = lexer.tokenize().map_err(|e| vec![*e])?;
+    for warning in lexer.take_warnings() {
+        eprint!("{}", warning.render(code, filename));
+    }
+    Ok(tokens)
+}
+
+/// 词法分析和语法分析，不做静态检查
+fn parse_syntax(code: &str, filename: &str) -> Result<Program, Vec<Diagnostic>> {
+    let tokens = tokenize(code, filename)?;
+    Parser::new(tokens).parse()
+}
+
+/// 格式化源代码，保留注释和空行；有语法错误时不格式化
+fn format_source(code: &str, filename: &str) -> Result<String, Vec<Diagnostic>> {
+    let mut lexer = Lexer::new(code);
+    let tokens = lexer.tokenize().map_err(|e| vec![*e])?;
+    for warning in lexer.take_warnings() {
+        eprint!("{}", warning.render(code, filename));
+    }
+    let program = Parser::new(tokens).with_comments(lexer.take_comments()).parse()?;
+    Ok(formatter::format(&program))
+}
+
+/// 词法分析、语法分析和静态检查，警告直接输出到标准错误
+fn parse_source(code: &str, filename: &str) -> Result<Program, Vec<Diagnostic>> {
+    let program = parse_syntax(code, filename)?;
+    let resolution = resolver::resolve(&program);
+    report_checks(program, resolution, code, filename)
+}
+
+/// 交互模式的一次输入，`globals` 是之前的输入定义的全局名字
+fn parse_interactive(code: &str, filename: &str, globals: &[String]) -> Result<Program, Vec<Diagnostic>> {
+    let program = parse_syntax(code, filename)?;
+    let resolution = resolver::resolve_interactive(&program, globals);
+    report_checks(program, resolution, code, filename)
+}
+
+/// 执行前的静态检查：`checker` 的检查和名字解析发现的问题，按位置排列
+fn static_checks(program: &Program, resolution: Resolution) -> Vec<Diagnostic> {
+    let mut diagnostics = checker::check(program);
+    diagnostics.extend(types::check(program, &resolution));
+    diagnostics.extend(resolution.diagnostics);
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.map(|span| (span.line, span.column)));
+    diagnostics
+}
+
+/// 输出静态检查的警告；有错误时返回错误
+fn report_checks(program: Program, resolution: Resolution, code: &str, filename: &str) -> Result<Program, Vec<Diagnostic>> {
+    let (errors, warnings): (Vec<_>, Vec<_>) = static_checks(&program, resolution)
+        .into_iter()
+        .partition(|d| d.severity == Severity::错误);
+    for warning in warnings {
+        eprint!("{}", warning.render(code, filename));
+    }
+    
+    if errors.is_empty() {
+        Ok(program)
+    } else {
+        Err(errors)
+    }
+}
+
+/// 源文件所在的目录，它导入的模块从这里开始查找
+fn source_directory(filename: &str) -> PathBuf {
+    match Path::new(filename).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+fn report_errors(errors: &[Diagnostic], code: &str, filename: &str) {
+    for error in errors {
+        eprintln!("{}", error.render(code, filename));
+    }
+    if errors.len() > 1 {
+        eprintln!("错误: 共发现 {} 个错误", errors.len());
+    }
+}
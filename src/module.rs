@@ -0,0 +1,201 @@
+//! 模块：用 `导入` 使用其它 .cn 文件中 `公开` 的声明
+//!
+//! 运行或编译之前，从入口文件出发加载所有被导入的文件，一次报告找不到的模块、
+//! 模块中的语法错误和循环导入。每个文件只加载一次，解释器和 C 后端再按导入者
+//! 所在的目录和导入语句中的路径取出加载好的模块。
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::ast::{Program, Statement};
+use crate::diagnostic::{Diagnostic, SourceFile, Span};
+use crate::parse_source;
+
+/// 模块搜索路径的环境变量，多个目录的写法与 PATH 相同
+const SEARCH_PATH_VARIABLE: &str = "CNLANG_PATH";
+
+/// 模块文件的扩展名，导入路径中可以省略
+const EXTENSION: &str = "cn";
+
+/// 一个加载好的模块文件
+#[derive(Debug)]
+pub struct Module {
+    /// 文件名去掉扩展名，没有别名时导入绑定的名字
+    pub name: String,
+    /// 规范化的绝对路径，同一个文件无论怎样导入都是同一个模块
+    pub path: PathBuf,
+    /// 第一次导入时找到的文件所在的目录，模块中的导入从这里开始查找
+    directory: PathBuf,
+    pub source: Rc<SourceFile>,
+    pub program: Program,
+}
+
+impl Module {
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+    
+    /// 带 `公开` 的顶层声明的名字，按声明顺序排列
+    pub fn exports(&self) -> Vec<String> {
+        self.program
+            .语句
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::变量声明 { 名字, 是公开: true, .. }
+                | Statement::函数声明 { 名字, 是公开: true, .. }
+                | Statement::结构声明 { 名字, 是公开: true, .. } => Some(名字.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// `导入` 语句绑定的名字：有别名时用别名，否则用文件名去掉扩展名
+pub fn import_name(path: &str, alias: Option<&str>) -> String {
+    match alias {
+        Some(alias) => alias.to_string(),
+        None => Path::new(path)
+            .file_stem()
+            .map_or_else(|| path.to_string(), |stem| stem.to_string_lossy().into_owned()),
+    }
+}
+
+/// 加载并缓存模块，一次运行或编译只用一个
+#[derive(Default)]
+pub struct ModuleLoader {
+    /// 导入者所在目录之后依次查找的目录
+    search_path: Vec<PathBuf>,
+    modules: HashMap<PathBuf, Rc<Module>>,
+    /// (导入者所在目录, 导入路径) 对应的模块路径
+    resolved: HashMap<(PathBuf, String), PathBuf>,
+    /// 每个模块都排在导入它的模块之前
+    order: Vec<Rc<Module>>,
+    /// 正在加载的模块的路径和显示名，用于发现循环导入
+    loading: Vec<(PathBuf, String)>,
+}
+
+impl ModuleLoader {
+    pub fn new() -> Self {
+        let search_path = env::var_os(SEARCH_PATH_VARIABLE)
+            .map(|paths| env::split_paths(&paths).collect())
+            .unwrap_or_default();
+        ModuleLoader {
+            search_path,
+            ..ModuleLoader::default()
+        }
+    }
+    
+    /// 加载 `program` 导入的所有模块；`directory` 是 `program` 所在的目录，
+    /// `source` 是它的源文件，入口文件为空
+    pub fn load_imports(
+        &mut self,
+        program: &Program,
+        directory: &Path,
+        source: Option<&Rc<SourceFile>>,
+    ) -> Result<(), Vec<Diagnostic>> {
+        let mut errors = Vec::new();
+        for statement in &program.语句 {
+            if let Statement::导入语句 { 路径, 位置, .. } = statement {
+                if let Err(import_errors) = self.load(directory, 路径, *位置) {
+                    errors.extend(import_errors.into_iter().map(|error| error.with_source(source.cloned())));
+                }
+            }
+        }
+        
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+    
+    /// 位于 `directory` 的文件中 `导入 path` 得到的模块，必须已经用 `load_imports` 加载
+    pub fn get(&self, directory: &Path, path: &str) -> Option<Rc<Module>> {
+        let resolved = self.resolved.get(&(directory.to_path_buf(), path.to_string()))?;
+        self.modules.get(resolved).cloned()
+    }
+    
+    /// 所有加载过的模块，被导入的模块排在前面
+    pub fn modules(&self) -> &[Rc<Module>] {
+        &self.order
+    }
+    
+    fn load(&mut self, directory: &Path, path: &str, span: Span) -> Result<(), Vec<Diagnostic>> {
+        let key = (directory.to_path_buf(), path.to_string());
+        if self.resolved.contains_key(&key) {
+            return Ok(());
+        }
+        
+        let file = self.find(directory, path).ok_or_else(|| {
+            let directories: Vec<String> = std::iter::once(directory)
+                .chain(self.search_path.iter().map(PathBuf::as_path))
+                .map(|dir| format!("'{}'", dir.display()))
+                .collect();
+            vec![Diagnostic::error("E0214", format!("找不到模块: {}", path))
+                .with_span(span)
+                .with_note(format!("在这些目录中查找过: {}", directories.join("、")))]
+        })?;
+        let canonical = file.canonicalize().unwrap_or_else(|_| file.clone());
+        let name = file.display().to_string();
+        
+        if let Some(start) = self.loading.iter().position(|(loading, _)| *loading == canonical) {
+            let mut chain: Vec<&str> = self.loading[start..].iter().map(|(_, name)| name.as_str()).collect();
+            chain.push(&name);
+            return Err(vec![Diagnostic::error("E0215", format!("循环导入: {}", path))
+                .with_span(span)
+                .with_note(format!("导入链: {}", chain.join(" -> ")))]);
+        }
+        
+        if !self.modules.contains_key(&canonical) {
+            let text = fs::read_to_string(&file).map_err(|e| {
+                vec![Diagnostic::error("E0214", format!("无法读取模块 {}: {}", name, e)).with_span(span)]
+            })?;
+            // 模块中的语法错误要显示模块自己的源代码
+            let source = Rc::new(SourceFile { name: name.clone(), text });
+            let program = parse_source(&source.text, &name).map_err(|errors| {
+                errors.into_iter().map(|error| error.with_source(Some(source.clone()))).collect::<Vec<_>>()
+            })?;
+            
+            let module_directory = match file.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                _ => PathBuf::from("."),
+            };
+            self.loading.push((canonical.clone(), name));
+            let loaded = self.load_imports(&program, &module_directory, Some(&source));
+            self.loading.pop();
+            loaded?;
+            
+            let module = Rc::new(Module {
+                name: import_name(path, None),
+                path: canonical.clone(),
+                directory: module_directory,
+                source,
+                program,
+            });
+            self.modules.insert(canonical.clone(), module.clone());
+            self.order.push(module);
+        }
+        
+        self.resolved.insert(key, canonical);
+        Ok(())
+    }
+    
+    /// 依次在导入者所在的目录和搜索路径中查找，没有扩展名时补上 `.cn`
+    fn find(&self, directory: &Path, path: &str) -> Option<PathBuf> {
+        let mut file = PathBuf::from(path);
+        if file.extension().is_none() {
+            file.set_extension(EXTENSION);
+        }
+        if file.is_absolute() {
+            return file.is_file().then_some(file);
+        }
+        
+        std::iter::once(directory)
+            .chain(self.search_path.iter().map(PathBuf::as_path))
+            .map(|dir| if dir == Path::new(".") { file.clone() } else { dir.join(&file) })
+            .find(|candidate| candidate.is_file())
+    }
+}
@@ -1,9 +1,21 @@
-use crate::lexer::{Token, TokenType};
-use crate::ast::{Program, Statement, Expression};
+use std::cell::Cell;
+use std::collections::VecDeque;
+
+use crate::lexer::{Comment, Token, TokenType};
+use crate::ast::{Program, Statement, Expression, Type};
+use crate::diagnostic::{Diagnostic, Span};
+
+/// 参数名和对应的类型标注
+type Parameters = (Vec<String>, Vec<Option<Type>>);
 
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    errors: Vec<Diagnostic>,
+    /// 还没有放进程序的注释，只在保留注释时存在
+    comments: Option<VecDeque<Comment>>,
+    /// 正在解析条件或可迭代表达式，其中的 `{` 是代码块的开始而不是字典字面量
+    in_condition: bool,
 }
 
 impl Parser {
@@ -11,30 +23,176 @@ impl Parser {
         Parser {
             tokens,
             current: 0,
+            errors: Vec::new(),
+            comments: None,
+            in_condition: false,
         }
     }
     
-    pub fn parse(&mut self) -> Result<Program, String> {
+    /// 保留注释和空行：它们作为 `Statement::注释` 和 `Statement::空行` 出现在所在的代码块中，
+    /// 表达式中间的注释放在所在语句之后。只用于格式化
+    pub fn with_comments(mut self, comments: Vec<Comment>) -> Self {
+        self.comments = Some(comments.into());
+        self
+    }
+    
+    /// 解析整个程序，有任何语法错误时返回全部错误
+    pub fn parse(&mut self) -> Result<Program, Vec<Diagnostic>> {
+        let (program, errors) = self.parse_with_recovery();
+        if errors.is_empty() {
+            Ok(program)
+        } else {
+            Err(errors)
+        }
+    }
+    
+    /// 解析整个程序，遇到语法错误时跳到下一条语句继续解析
+    ///
+    /// 返回的程序中，出错的语句被替换为 `Statement::错误` 占位
+    pub fn parse_with_recovery(&mut self) -> (Program, Vec<Diagnostic>) {
         let mut program = Program::new();
         
         while !self.is_at_end() {
-            if let Some(stmt) = self.parse_statement()? {
+            program.语句.extend(self.take_trivia());
+            if let Some(stmt) = self.parse_statement() {
                 program.添加语句(stmt);
             }
         }
+        program.语句.extend(self.take_trivia());
+        
+        (program, std::mem::take(&mut self.errors))
+    }
+    
+    /// 保留注释时，取出当前记号之前的注释和空行
+    fn take_trivia(&mut self) -> Vec<Statement> {
+        let Some(comments) = self.comments.as_mut() else {
+            return Vec::new();
+        };
+        let next = &self.tokens[self.current];
+        let mut last_line = match self.current {
+            0 => 0,
+            _ => self.tokens[self.current - 1].line,
+        };
         
-        Ok(program)
+        let mut trivia = Vec::new();
+        while let Some(comment) = comments.pop_front() {
+            if (comment.line, comment.column) > (next.line, next.column) {
+                comments.push_front(comment);
+                break;
+            }
+            if comment.line > last_line + 1 {
+                trivia.push(Statement::空行);
+            }
+            last_line = last_line.max(comment.line);
+            trivia.push(Statement::注释 {
+                位置: Span::new(comment.line, comment.column, comment.text.chars().count() + 2),
+                内容: comment.text,
+                行尾: comment.trailing,
+            });
+        }
+        if next.line > last_line + 1 {
+            trivia.push(Statement::空行);
+        }
+        trivia
     }
     
-    fn parse_statement(&mut self) -> Result<Option<Statement>, String> {
+    fn parse_statement(&mut self) -> Option<Statement> {
+        // 分号是可选的语句分隔符
+        if self.match_token(&[TokenType::分号]) {
+            return None;
+        }
+        
+        let start = self.current;
+        match self.parse_statement_inner() {
+            Ok(statement) => {
+                self.match_token(&[TokenType::分号]);
+                Some(statement)
+            }
+            Err(error) => {
+                let span = error.span.unwrap_or_else(|| self.tokens[start].span());
+                self.errors.push(*error);
+                self.synchronize(start);
+                Some(Statement::错误 { 位置: span })
+            }
+        }
+    }
+    
+    /// 出错后丢弃记号，直到看起来是下一条语句的开头：
+    /// 语句关键字、所在代码块的 `}`，或者新的一行。
+    /// 跳过的 `{ ... }` 会被整体丢弃，以免把出错语句的代码块当成外层的语句。
+    fn synchronize(&mut self, start: usize) {
+        // 保证至少前进一个记号，否则可能在同一个位置反复报错
+        if self.current == start && !self.is_at_end() {
+            self.skip_token_tracking_braces(&mut 0);
+        }
+        
+        let mut depth = 0usize;
+        while !self.is_at_end() {
+            if depth == 0 {
+                if self.previous().token_type == TokenType::分号 {
+                    return;
+                }
+                
+                let next = self.peek();
+                let is_statement_keyword = matches!(
+                    next.token_type,
+                    TokenType::让
+                        | TokenType::常量
+                        | TokenType::变量
+                        | TokenType::函数
+                        | TokenType::结构
+                        | TokenType::如果
+                        | TokenType::循环
+                        | TokenType::当
+                        | TokenType::对于
+                        | TokenType::尝试
+                        | TokenType::抛出
+                        | TokenType::导入
+                        | TokenType::公开
+                        | TokenType::返回
+                        | TokenType::跳出
+                        | TokenType::继续
+                );
+                if is_statement_keyword || next.token_type == TokenType::右大括号 || next.line > self.previous().line {
+                    return;
+                }
+            }
+            
+            self.skip_token_tracking_braces(&mut depth);
+        }
+    }
+    
+    fn skip_token_tracking_braces(&mut self, depth: &mut usize) {
+        match self.advance().token_type {
+            TokenType::左大括号 => *depth += 1,
+            TokenType::右大括号 => *depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    
+    fn parse_statement_inner(&mut self) -> Result<Statement, Box<Diagnostic>> {
         if self.match_token(&[TokenType::让, TokenType::常量, TokenType::变量]) {
             return self.parse_variable_declaration();
         }
         
-        if self.match_token(&[TokenType::函数]) {
+        // `函数 名字(...)` 是函数声明，`函数(...)` 是匿名函数表达式
+        if self.check(&TokenType::函数) && self.check_next(&TokenType::标识符(String::new())) {
+            self.advance();
             return self.parse_function_declaration();
         }
         
+        if self.match_token(&[TokenType::结构]) {
+            return self.parse_struct_declaration();
+        }
+        
+        if self.match_token(&[TokenType::公开]) {
+            return self.parse_public_declaration();
+        }
+        
+        if self.match_token(&[TokenType::导入]) {
+            return self.parse_import_statement();
+        }
+        
         if self.match_token(&[TokenType::如果]) {
             return self.parse_if_statement();
         }
@@ -51,459 +209,550 @@ impl Parser {
             return self.parse_for_statement();
         }
         
+        if self.match_token(&[TokenType::尝试]) {
+            return self.parse_try_statement();
+        }
+        
+        if self.match_token(&[TokenType::抛出]) {
+            let span = self.previous().span();
+            let value = self.expect_expression("期望要抛出的值")?;
+            return Ok(Statement::抛出语句 { 值: value, 位置: span });
+        }
+        
         if self.match_token(&[TokenType::返回]) {
             return self.parse_return_statement();
         }
         
         if self.match_token(&[TokenType::跳出]) {
-            return Ok(Some(Statement::跳出语句));
+            return Ok(Statement::跳出语句 { 位置: self.previous().span() });
         }
         
         if self.match_token(&[TokenType::继续]) {
-            return Ok(Some(Statement::继续语句));
+            return Ok(Statement::继续语句 { 位置: self.previous().span() });
         }
         
         // 表达式语句
         if let Some(expr) = self.parse_expression()? {
-            return Ok(Some(Statement::表达式语句(expr)));
+            return Ok(Statement::表达式语句(expr));
         }
         
-        Ok(None)
+        Err(self.error("E0105", "期望语句").into())
     }
     
-    fn parse_variable_declaration(&mut self) -> Result<Option<Statement>, String> {
+    fn parse_variable_declaration(&mut self) -> Result<Statement, Box<Diagnostic>> {
         let is_const = self.previous().token_type == TokenType::常量;
+        let (name, span) = self.consume_identifier("期望变量名")?;
+        let annotation = if self.match_token(&[TokenType::冒号]) {
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
         
-        // 使用advance_if_matches来匹配标识符
-        if let Some(token) = self.advance_if_matches(|t| matches!(t.token_type, TokenType::标识符(_))) {
-            let name = if let TokenType::标识符(name) = token.token_type {
-                name
-            } else {
-                return Err("期望变量名".to_string());
-            };
-            
-            let initial_value = if self.match_token(&[TokenType::赋值]) {
-                self.parse_expression()?
-            } else {
-                None
-            };
-            
-            Ok(Some(Statement::变量声明 {
-                名字: name,
-                初始值: initial_value,
-                是常量: is_const,
-            }))
+        let initial_value = if self.match_token(&[TokenType::赋值]) {
+            Some(self.expect_expression("期望初始值表达式")?)
         } else {
-            Err("期望变量名".to_string())
-        }
+            None
+        };
+        
+        Ok(Statement::变量声明 {
+            名字: name,
+            类型: annotation,
+            初始值: initial_value,
+            是常量: is_const,
+            是公开: false,
+            位置: span,
+        })
     }
     
-    fn parse_function_declaration(&mut self) -> Result<Option<Statement>, String> {
-        // 使用advance_if_matches来匹配标识符
-        if let Some(token) = self.advance_if_matches(|t| matches!(t.token_type, TokenType::标识符(_))) {
-            let name = if let TokenType::标识符(name) = token.token_type {
-                name
+    fn parse_function_declaration(&mut self) -> Result<Statement, Box<Diagnostic>> {
+        let (name, span) = self.consume_identifier("期望函数名")?;
+        let (parameters, parameter_types) = self.parse_parameters()?;
+        let return_type = self.parse_return_type()?;
+        let body = self.parse_block()?;
+        
+        Ok(Statement::函数声明 {
+            名字: name,
+            参数: parameters,
+            参数类型: parameter_types,
+            返回类型: return_type,
+            体: body,
+            是公开: false,
+            位置: span,
+        })
+    }
+    
+    /// 解析结构的字段和方法，字段之间可以用逗号或换行分隔
+    fn parse_struct_declaration(&mut self) -> Result<Statement, Box<Diagnostic>> {
+        let (name, span) = self.consume_identifier("期望结构名")?;
+        let open = self.consume(TokenType::左大括号, "期望 '{'")?;
+        
+        let mut fields: Vec<String> = Vec::new();
+        let mut methods = Vec::new();
+        let mut member_names: Vec<String> = Vec::new();
+        
+        while !self.check(&TokenType::右大括号) && !self.is_at_end() {
+            // 保留的注释和空行与方法放在一起
+            methods.extend(self.take_trivia());
+            let (member, member_span) = if self.match_token(&[TokenType::函数]) {
+                let method = self.parse_function_declaration()?;
+                let Statement::函数声明 { 名字, 位置, .. } = &method else {
+                    unreachable!("parse_function_declaration 只返回函数声明");
+                };
+                let member = (名字.clone(), *位置);
+                methods.push(method);
+                member
             } else {
-                return Err("期望函数名".to_string());
+                let (field, field_span) = self.consume_identifier("期望字段名或方法")?;
+                fields.push(field.clone());
+                self.match_token(&[TokenType::逗号]);
+                (field, field_span)
             };
             
-            if !self.match_token(&[TokenType::左括号]) {
-                return Err("期望 '('".to_string());
+            // 重复的成员不影响后面的解析，记录错误后继续
+            if member_names.contains(&member) {
+                self.errors.push(
+                    Diagnostic::error("E0107", format!("结构 {} 的成员 {} 重复声明", name, member)).with_span(member_span),
+                );
             }
-            
-            let mut parameters = Vec::new();
-            
-            if !self.check(&TokenType::右括号) {
-                loop {
-                    // 使用advance_if_matches来匹配参数标识符
-                    if let Some(param_token) = self.advance_if_matches(|t| matches!(t.token_type, TokenType::标识符(_))) {
-                        let param_name = if let TokenType::标识符(name) = param_token.token_type {
-                            name
-                        } else {
-                            return Err("期望参数名".to_string());
-                        };
-                        
-                        parameters.push(param_name);
-                    } else {
-                        return Err("期望参数名".to_string());
-                    }
-                    
-                    if !self.match_token(&[TokenType::逗号]) {
-                        break;
-                    }
-                }
-            }
-            
-            if !self.match_token(&[TokenType::右括号]) {
-                return Err("期望 ')'".to_string());
-            }
-            
-            if !self.match_token(&[TokenType::左大括号]) {
-                return Err("期望 '{{'".to_string());
-            }
-            
-            let mut body = Vec::new();
-            
-            while !self.check(&TokenType::右大括号) && !self.is_at_end() {
-                if let Some(stmt) = self.parse_statement()? {
-                    body.push(stmt);
-                }
-            }
-            
-            if !self.match_token(&[TokenType::右大括号]) {
-                return Err("期望 '}}'".to_string());
-            }
-            
-            Ok(Some(Statement::函数声明 {
-                名字: name,
-                参数: parameters,
-                体: body,
-            }))
-        } else {
-            Err("期望函数名".to_string())
+            member_names.push(member);
+        }
+        methods.extend(self.take_trivia());
+        
+        if !self.match_token(&[TokenType::右大括号]) {
+            return Err(self.error("E0101", "期望 '}'").with_label(open.span(), "结构从这里开始").into());
         }
+        
+        Ok(Statement::结构声明 {
+            名字: name,
+            字段: fields,
+            方法: methods,
+            是公开: false,
+            位置: span,
+        })
     }
     
-    fn parse_if_statement(&mut self) -> Result<Option<Statement>, String> {
-        let condition = self.parse_expression()?.ok_or_else(|| "期望条件表达式".to_string())?;
+    /// `公开` 之后必须是变量、常量、函数或结构声明
+    fn parse_public_declaration(&mut self) -> Result<Statement, Box<Diagnostic>> {
+        let keyword = self.previous().span();
+        let mut declaration = if self.match_token(&[TokenType::让, TokenType::常量, TokenType::变量]) {
+            self.parse_variable_declaration()?
+        } else if self.match_token(&[TokenType::函数]) {
+            self.parse_function_declaration()?
+        } else if self.match_token(&[TokenType::结构]) {
+            self.parse_struct_declaration()?
+        } else {
+            return Err(self.error("E0101", "期望变量、函数或结构声明").with_label(keyword, "公开 只能用于声明").into());
+        };
         
-        if !self.match_token(&[TokenType::左大括号]) {
-            return Err("期望 '{{'".to_string());
+        if let Statement::变量声明 { 是公开, .. } | Statement::函数声明 { 是公开, .. } | Statement::结构声明 { 是公开, .. } =
+            &mut declaration
+        {
+            *是公开 = true;
         }
+        Ok(declaration)
+    }
+    
+    /// `导入 "路径" 作为 别名` 或 `导入 名字 作为 别名`，别名可以省略
+    fn parse_import_statement(&mut self) -> Result<Statement, Box<Diagnostic>> {
+        let span = self.previous().span();
+        let path = match self.advance_if_matches(|t| matches!(t.token_type, TokenType::字符串(_) | TokenType::标识符(_))) {
+            Some(Token { token_type: TokenType::字符串(path) | TokenType::标识符(path), .. }) => path,
+            _ => return Err(self.error("E0101", "期望模块路径或模块名").into()),
+        };
         
-        let mut true_branch = Vec::new();
+        let alias = if self.match_token(&[TokenType::作为]) {
+            Some(self.consume_identifier("期望模块的别名")?.0)
+        } else {
+            None
+        };
         
-        while !self.check(&TokenType::右大括号) && !self.is_at_end() {
-            if let Some(stmt) = self.parse_statement()? {
-                true_branch.push(stmt);
+        Ok(Statement::导入语句 {
+            路径: path,
+            别名: alias,
+            位置: span,
+        })
+    }
+    
+    /// 解析 `(参数: 类型, ...)`，返回参数名和对应的类型标注
+    fn parse_parameters(&mut self) -> Result<Parameters, Box<Diagnostic>> {
+        self.consume(TokenType::左括号, "期望 '('")?;
+        
+        let mut parameters = Vec::new();
+        let mut types = Vec::new();
+        
+        if !self.check(&TokenType::右括号) {
+            loop {
+                let (param_name, _) = self.consume_identifier("期望参数名")?;
+                parameters.push(param_name);
+                types.push(if self.match_token(&[TokenType::冒号]) { Some(self.parse_type()?) } else { None });
+                
+                if !self.match_token(&[TokenType::逗号]) {
+                    break;
+                }
             }
         }
         
-        if !self.match_token(&[TokenType::右大括号]) {
-            return Err("期望 '}}'".to_string());
+        self.consume(TokenType::右括号, "期望 ')'")?;
+        
+        Ok((parameters, types))
+    }
+    
+    /// 参数列表之后可选的 `-> 类型`
+    fn parse_return_type(&mut self) -> Result<Option<Type>, Box<Diagnostic>> {
+        if self.match_token(&[TokenType::箭头]) {
+            Ok(Some(self.parse_type()?))
+        } else {
+            Ok(None)
         }
+    }
+    
+    /// 类型标注中的类型名
+    fn parse_type(&mut self) -> Result<Type, Box<Diagnostic>> {
+        let (name, span) = self.consume_identifier("期望类型名")?;
+        Type::from_name(&name).ok_or_else(|| {
+            let names: Vec<&str> = Type::ALL.iter().map(|ty| ty.name()).collect();
+            Diagnostic::error("E0109", format!("未知的类型: {}", name))
+                .with_span(span)
+                .with_note(format!("可以使用的类型: {}", names.join("、")))
+                .into()
+        })
+    }
+    
+    fn parse_if_statement(&mut self) -> Result<Statement, Box<Diagnostic>> {
+        let span = self.previous().span();
+        let condition = self.parse_condition("期望条件表达式")?;
+        let true_branch = self.parse_block()?;
         
         let mut false_branch = Vec::new();
         
         if self.match_token(&[TokenType::否则]) {
             if self.match_token(&[TokenType::如果]) {
                 // 处理 "否则如果" - 嵌套的if语句
-                let nested_if = self.parse_if_statement()?;
-                if let Some(Statement::如果语句 { 条件, 真分支, 假分支: _ }) = nested_if {
-                    false_branch.push(Statement::如果语句 {
-                        条件,
-                        真分支,
-                        假分支: Vec::new(),
-                    });
-                }
+                false_branch.push(self.parse_if_statement()?);
             } else {
                 // 处理普通的 "否则"
-                if !self.match_token(&[TokenType::左大括号]) {
-                    return Err("期望 '{{'".to_string());
-                }
-                
-                while !self.check(&TokenType::右大括号) && !self.is_at_end() {
-                    if let Some(stmt) = self.parse_statement()? {
-                        false_branch.push(stmt);
-                    }
-                }
-                
-                if !self.match_token(&[TokenType::右大括号]) {
-                    return Err("期望 '}}'".to_string());
-                }
+                false_branch = self.parse_block()?;
             }
         }
         
-        Ok(Some(Statement::如果语句 {
+        Ok(Statement::如果语句 {
             条件: condition,
             真分支: true_branch,
             假分支: false_branch,
-        }))
+            位置: span,
+        })
     }
     
-    fn parse_loop_statement(&mut self) -> Result<Option<Statement>, String> {
-        let condition = if self.check(&TokenType::左大括号) {
-            None
-        } else {
-            Some(self.parse_expression()?.ok_or_else(|| "期望条件表达式".to_string())?)
-        };
+    fn parse_try_statement(&mut self) -> Result<Statement, Box<Diagnostic>> {
+        let try_span = self.previous().span();
+        let body = self.parse_block()?;
         
-        if !self.match_token(&[TokenType::左大括号]) {
-            return Err("期望 '{{'".to_string());
-        }
-        
-        let mut body = Vec::new();
-        
-        while !self.check(&TokenType::右大括号) && !self.is_at_end() {
-            if let Some(stmt) = self.parse_statement()? {
-                body.push(stmt);
+        // 错误变量可以写在括号中，也可以省略
+        let mut catch_variable = None;
+        let mut handler = None;
+        if self.match_token(&[TokenType::捕获]) {
+            if self.match_token(&[TokenType::左括号]) {
+                catch_variable = Some(self.consume_identifier("期望错误变量名")?);
+                self.consume(TokenType::右括号, "期望 ')'")?;
+            } else if self.check(&TokenType::标识符(String::new())) {
+                catch_variable = Some(self.consume_identifier("期望错误变量名")?);
             }
+            handler = Some(self.parse_block()?);
         }
         
-        if !self.match_token(&[TokenType::右大括号]) {
-            return Err("期望 '}}'".to_string());
+        let finally = if self.match_token(&[TokenType::最终]) {
+            Some(self.parse_block()?)
+        } else {
+            None
+        };
+        
+        if handler.is_none() && finally.is_none() {
+            return Err(self.error("E0101", "期望 '捕获' 或 '最终'").with_label(try_span, "尝试语句从这里开始").into());
         }
         
-        Ok(Some(Statement::循环语句 {
-            条件: condition,
+        Ok(Statement::尝试语句 {
             体: body,
-        }))
+            捕获变量: catch_variable,
+            捕获: handler,
+            最终: finally,
+            位置: try_span,
+        })
     }
     
-    fn parse_while_statement(&mut self) -> Result<Option<Statement>, String> {
-        let condition = self.parse_expression()?.ok_or_else(|| "期望条件表达式".to_string())?;
-        
-        if !self.match_token(&[TokenType::左大括号]) {
-            return Err("期望 '{{'".to_string());
-        }
-        
-        let mut body = Vec::new();
+    fn parse_loop_statement(&mut self) -> Result<Statement, Box<Diagnostic>> {
+        let span = self.previous().span();
+        let condition = if self.check(&TokenType::左大括号) {
+            None
+        } else {
+            Some(self.parse_condition("期望条件表达式")?)
+        };
         
-        while !self.check(&TokenType::右大括号) && !self.is_at_end() {
-            if let Some(stmt) = self.parse_statement()? {
-                body.push(stmt);
-            }
-        }
+        let body = self.parse_block()?;
         
-        if !self.match_token(&[TokenType::右大括号]) {
-            return Err("期望 '}}'".to_string());
-        }
+        Ok(Statement::循环语句 {
+            条件: condition,
+            体: body,
+            位置: span,
+        })
+    }
+    
+    fn parse_while_statement(&mut self) -> Result<Statement, Box<Diagnostic>> {
+        let span = self.previous().span();
+        let condition = self.parse_condition("期望条件表达式")?;
+        let body = self.parse_block()?;
         
-        Ok(Some(Statement::当语句 {
+        Ok(Statement::当语句 {
             条件: condition,
             体: body,
-        }))
+            位置: span,
+        })
     }
     
-    fn parse_for_statement(&mut self) -> Result<Option<Statement>, String> {
-        // 使用advance_if_matches来匹配标识符
-        if let Some(token) = self.advance_if_matches(|t| matches!(t.token_type, TokenType::标识符(_))) {
-            let variable = if let TokenType::标识符(name) = token.token_type {
-                name
-            } else {
-                return Err("期望变量名".to_string());
-            };
-            
-            if !self.match_token(&[TokenType::在]) {
-                return Err("期望 '在'".to_string());
-            }
-            
-            let iterable = self.parse_expression()?.ok_or_else(|| "期望可迭代表达式".to_string())?;
-            
-            if !self.match_token(&[TokenType::左大括号]) {
-                return Err("期望 '{{'".to_string());
-            }
-            
-            let mut body = Vec::new();
-            
-            while !self.check(&TokenType::右大括号) && !self.is_at_end() {
-                if let Some(stmt) = self.parse_statement()? {
-                    body.push(stmt);
-                }
-            }
-            
-            if !self.match_token(&[TokenType::右大括号]) {
-                return Err("期望 '}}'".to_string());
-            }
-            
-            Ok(Some(Statement::对于语句 {
-                变量: variable,
-                可迭代: iterable,
-                体: body,
-            }))
+    fn parse_for_statement(&mut self) -> Result<Statement, Box<Diagnostic>> {
+        // `对于 (键, 值) 在 ...` 同时绑定两个变量
+        let (variable, value_variable, span) = if self.match_token(&[TokenType::左括号]) {
+            let (first, span) = self.consume_identifier("期望变量名")?;
+            self.consume(TokenType::逗号, "期望 ','")?;
+            let (second, _) = self.consume_identifier("期望变量名")?;
+            self.consume(TokenType::右括号, "期望 ')'")?;
+            (first, Some(second), span)
         } else {
-            Err("期望变量名".to_string())
-        }
+            let (variable, span) = self.consume_identifier("期望变量名")?;
+            (variable, None, span)
+        };
+        
+        self.consume(TokenType::在, "期望 '在'")?;
+        
+        let iterable = self.parse_condition("期望可迭代表达式")?;
+        let body = self.parse_block()?;
+        
+        Ok(Statement::对于语句 {
+            变量: variable,
+            值变量: value_variable,
+            可迭代: iterable,
+            体: body,
+            位置: span,
+        })
     }
     
-    fn parse_return_statement(&mut self) -> Result<Option<Statement>, String> {
+    fn parse_return_statement(&mut self) -> Result<Statement, Box<Diagnostic>> {
+        let span = self.previous().span();
         let value = if self.check(&TokenType::分号) || self.check(&TokenType::右大括号) {
             None
         } else {
             self.parse_expression()?
         };
         
-        Ok(Some(Statement::返回语句(value)))
+        Ok(Statement::返回语句 {
+            值: value,
+            位置: span,
+        })
+    }
+    
+    /// 解析 `{ ... }` 包围的语句块
+    fn parse_block(&mut self) -> Result<Vec<Statement>, Box<Diagnostic>> {
+        self.without_condition(Self::parse_block_inner)
     }
     
-    fn parse_expression(&mut self) -> Result<Option<Expression>, String> {
+    fn parse_block_inner(&mut self) -> Result<Vec<Statement>, Box<Diagnostic>> {
+        let open = self.consume(TokenType::左大括号, "期望 '{'")?;
+        
+        let mut statements = Vec::new();
+        
+        while !self.check(&TokenType::右大括号) && !self.is_at_end() {
+            statements.extend(self.take_trivia());
+            if let Some(stmt) = self.parse_statement() {
+                statements.push(stmt);
+            }
+        }
+        statements.extend(self.take_trivia());
+        
+        // 缺少 '}' 时只可能是到了文件结尾，保留已经解析出的语句
+        if !self.match_token(&[TokenType::右大括号]) {
+            let error = self.error("E0101", "期望 '}'").with_label(open.span(), "代码块从这里开始");
+            self.errors.push(error);
+        }
+        
+        Ok(statements)
+    }
+    
+    fn parse_expression(&mut self) -> Result<Option<Expression>, Box<Diagnostic>> {
         self.parse_assignment()
     }
     
-    fn parse_assignment(&mut self) -> Result<Option<Expression>, String> {
+    /// 解析代码块前面的条件：`如果 a > { ... }` 缺少右边的运算数，而不是把代码块当成字典
+    fn parse_condition(&mut self, message: &str) -> Result<Expression, Box<Diagnostic>> {
+        let outer = std::mem::replace(&mut self.in_condition, true);
+        let result = self.expect_expression(message);
+        self.in_condition = outer;
+        result
+    }
+    
+    /// 括号和代码块中可以再使用字典字面量
+    fn without_condition<T>(&mut self, parse: impl FnOnce(&mut Self) -> T) -> T {
+        let outer = std::mem::replace(&mut self.in_condition, false);
+        let result = parse(self);
+        self.in_condition = outer;
+        result
+    }
+    
+    fn expect_expression(&mut self, message: &str) -> Result<Expression, Box<Diagnostic>> {
+        match self.parse_expression()? {
+            Some(expr) => Ok(expr),
+            None => Err(self.error("E0102", message).into()),
+        }
+    }
+    
+    fn parse_assignment(&mut self) -> Result<Option<Expression>, Box<Diagnostic>> {
         let expr = self.parse_logical()?;
         
         if self.match_token(&[TokenType::赋值]) {
+            let equals = self.previous();
             let value = self.parse_assignment()?;
             
-            if let Some(expr) = expr {
-                if let Expression::变量(name) = expr {
+            match expr {
+                Some(Expression::变量 { 名字, 位置, .. }) => {
                     return Ok(Some(Expression::赋值 {
-                        变量名: name,
-                        值: Box::new(value.ok_or_else(|| "期望赋值表达式".to_string())?),
+                        变量名: 名字,
+                        值: Box::new(value.ok_or_else(|| self.error("E0102", "期望赋值表达式"))?),
+                        位置,
+                        槽位: Cell::default(),
                     }));
                 }
+                Some(Expression::数组索引 { 数组, 索引, 位置 }) => {
+                    return Ok(Some(Expression::索引赋值 {
+                        数组,
+                        索引,
+                        值: Box::new(value.ok_or_else(|| self.error("E0102", "期望赋值表达式"))?),
+                        位置,
+                    }));
+                }
+                Some(Expression::成员访问 { 对象, 成员, 位置 }) => {
+                    return Ok(Some(Expression::成员赋值 {
+                        对象,
+                        成员,
+                        值: Box::new(value.ok_or_else(|| self.error("E0102", "期望赋值表达式"))?),
+                        位置,
+                    }));
+                }
+                _ => {}
             }
             
-            return Err("无效的赋值目标".to_string());
+            return Err(Diagnostic::error("E0103", "无效的赋值目标")
+                .with_span(expr.and_then(|e| e.span()).unwrap_or_else(|| equals.span())).into());
         }
         
         Ok(expr)
     }
     
-    fn parse_logical(&mut self) -> Result<Option<Expression>, String> {
-        let mut expr = self.parse_equality()?;
+    /// 解析左结合的二元运算，`operand` 是下一级优先级的解析函数
+    fn parse_binary(
+        &mut self,
+        operators: &[(TokenType, &str)],
+        operand: fn(&mut Self) -> Result<Option<Expression>, Box<Diagnostic>>,
+    ) -> Result<Option<Expression>, Box<Diagnostic>> {
+        let mut expr = operand(self)?;
         
-        while self.match_token(&[TokenType::且, TokenType::或]) {
-            let operator = match self.previous().token_type {
-                TokenType::且 => "&&".to_string(),
-                TokenType::或 => "||".to_string(),
-                _ => return Err("无效的逻辑运算符".to_string()),
+        while let Some(operator) = operators.iter().find(|(token_type, _)| self.check(token_type)).map(|(_, op)| *op) {
+            let operator_token = self.advance();
+            let left = match expr {
+                Some(left) => left,
+                None => {
+                    return Err(Diagnostic::error("E0102", format!("运算符 '{}' 左边缺少表达式", operator_token.token_type))
+                        .with_span(operator_token.span()).into());
+                }
+            };
+            let right = match operand(self)? {
+                Some(right) => right,
+                None => return Err(self.error("E0102", format!("运算符 '{}' 右边缺少表达式", operator_token.token_type)).into()),
             };
             
-            let right = self.parse_equality()?;
-            
-            if let (Some(left), Some(right)) = (expr, right) {
-                expr = Some(Expression::二元运算 {
-                    左: Box::new(left),
-                    运算符: operator,
-                    右: Box::new(right),
-                });
-            } else {
-                return Ok(None);
-            }
+            expr = Some(Expression::二元运算 {
+                左: Box::new(left),
+                运算符: operator.to_string(),
+                右: Box::new(right),
+                位置: operator_token.span(),
+            });
         }
         
         Ok(expr)
     }
     
-    fn parse_equality(&mut self) -> Result<Option<Expression>, String> {
-        let mut expr = self.parse_comparison()?;
-        
-        while self.match_token(&[TokenType::等于, TokenType::不等于]) {
-            let operator = match self.previous().token_type {
-                TokenType::等于 => "==".to_string(),
-                TokenType::不等于 => "!=".to_string(),
-                _ => return Err("无效的运算符".to_string()),
-            };
-            
-            let right = self.parse_comparison()?;
-            
-            if let (Some(left), Some(right)) = (expr, right) {
-                expr = Some(Expression::二元运算 {
-                    左: Box::new(left),
-                    运算符: operator,
-                    右: Box::new(right),
-                });
-            } else {
-                return Ok(None);
-            }
-        }
-        
-        Ok(expr)
+    fn parse_logical(&mut self) -> Result<Option<Expression>, Box<Diagnostic>> {
+        self.parse_binary(&[(TokenType::且, "&&"), (TokenType::或, "||")], Self::parse_equality)
     }
     
-    fn parse_comparison(&mut self) -> Result<Option<Expression>, String> {
-        let mut expr = self.parse_term()?;
-        
-        while self.match_token(&[TokenType::大于, TokenType::大于等于, TokenType::小于, TokenType::小于等于]) {
-            let operator = match self.previous().token_type {
-                TokenType::大于 => ">".to_string(),
-                TokenType::大于等于 => ">=".to_string(),
-                TokenType::小于 => "<".to_string(),
-                TokenType::小于等于 => "<=".to_string(),
-                _ => return Err("无效的运算符".to_string()),
-            };
-            
-            let right = self.parse_term()?;
-            
-            if let (Some(left), Some(right)) = (expr, right) {
-                expr = Some(Expression::二元运算 {
-                    左: Box::new(left),
-                    运算符: operator,
-                    右: Box::new(right),
-                });
-            } else {
-                return Ok(None);
-            }
-        }
-        
-        Ok(expr)
+    fn parse_equality(&mut self) -> Result<Option<Expression>, Box<Diagnostic>> {
+        self.parse_binary(&[(TokenType::等于, "=="), (TokenType::不等于, "!=")], Self::parse_comparison)
     }
     
-    fn parse_term(&mut self) -> Result<Option<Expression>, String> {
-        let mut expr = self.parse_factor()?;
-        
-        while self.match_token(&[TokenType::加, TokenType::减]) {
-            let operator = match self.previous().token_type {
-                TokenType::加 => "+".to_string(),
-                TokenType::减 => "-".to_string(),
-                _ => return Err("无效的运算符".to_string()),
-            };
-            
-            let right = self.parse_factor()?;
-            
-            if let (Some(left), Some(right)) = (expr, right) {
-                expr = Some(Expression::二元运算 {
-                    左: Box::new(left),
-                    运算符: operator,
-                    右: Box::new(right),
-                });
-            } else {
-                return Ok(None);
-            }
-        }
-        
-        Ok(expr)
+    fn parse_comparison(&mut self) -> Result<Option<Expression>, Box<Diagnostic>> {
+        self.parse_binary(
+            &[
+                (TokenType::大于, ">"),
+                (TokenType::大于等于, ">="),
+                (TokenType::小于, "<"),
+                (TokenType::小于等于, "<="),
+            ],
+            Self::parse_range,
+        )
     }
     
-    fn parse_factor(&mut self) -> Result<Option<Expression>, String> {
-        let mut expr = self.parse_unary()?;
-        
-        while self.match_token(&[TokenType::乘, TokenType::除]) {
-            let operator = match self.previous().token_type {
-                TokenType::乘 => "*".to_string(),
-                TokenType::除 => "/".to_string(),
-                _ => return Err("无效的运算符".to_string()),
-            };
-            
-            let right = self.parse_unary()?;
-            
-            if let (Some(left), Some(right)) = (expr, right) {
-                expr = Some(Expression::二元运算 {
-                    左: Box::new(left),
-                    运算符: operator,
-                    右: Box::new(right),
-                });
-            } else {
-                return Ok(None);
-            }
+    /// `起..止` 的优先级低于加减，`0..n + 1` 的终点是 `n + 1`；范围不能连写
+    fn parse_range(&mut self) -> Result<Option<Expression>, Box<Diagnostic>> {
+        let start = self.parse_term()?;
+        if !self.check(&TokenType::范围) {
+            return Ok(start);
         }
         
-        Ok(expr)
+        let operator = self.advance();
+        let Some(start) = start else {
+            return Err(Diagnostic::error("E0102", "范围 '..' 左边缺少起点").with_span(operator.span()).into());
+        };
+        let end = match self.parse_term()? {
+            Some(end) => end,
+            None => return Err(self.error("E0102", "范围 '..' 右边缺少终点").into()),
+        };
+        Ok(Some(Expression::范围 {
+            起: Box::new(start),
+            止: Box::new(end),
+            位置: operator.span(),
+        }))
     }
     
-    fn parse_unary(&mut self) -> Result<Option<Expression>, String> {
+    fn parse_term(&mut self) -> Result<Option<Expression>, Box<Diagnostic>> {
+        self.parse_binary(&[(TokenType::加, "+"), (TokenType::减, "-")], Self::parse_factor)
+    }
+    
+    fn parse_factor(&mut self) -> Result<Option<Expression>, Box<Diagnostic>> {
+        self.parse_binary(&[(TokenType::乘, "*"), (TokenType::除, "/")], Self::parse_unary)
+    }
+    
+    fn parse_unary(&mut self) -> Result<Option<Expression>, Box<Diagnostic>> {
         if self.match_token(&[TokenType::减]) {
+            let span = self.previous().span();
             let right = self.parse_unary()?;
             return Ok(Some(Expression::二元运算 {
                 左: Box::new(Expression::数字字面量(0.0)),
                 运算符: "-".to_string(),
-                右: Box::new(right.ok_or_else(|| "期望一元运算表达式".to_string())?),
+                右: Box::new(right.ok_or_else(|| self.error("E0102", "期望一元运算表达式"))?),
+                位置: span,
             }));
         }
         
         if self.match_token(&[TokenType::非]) {
+            let span = self.previous().span();
             let right = self.parse_unary()?;
             return Ok(Some(Expression::一元运算 {
                 运算符: "!".to_string(),
-                操作数: Box::new(right.ok_or_else(|| "期望一元运算表达式".to_string())?),
+                操作数: Box::new(right.ok_or_else(|| self.error("E0102", "期望一元运算表达式"))?),
+                位置: span,
             }));
         }
         
         let mut expr = self.parse_primary()?;
         
-        // 处理后缀表达式（数组方法调用）
+        // 处理后缀表达式（调用、索引和成员访问）
         if let Some(e) = expr {
             expr = Some(self.parse_postfix(e)?);
         }
@@ -511,84 +760,59 @@ impl Parser {
         Ok(expr)
     }
     
-    fn parse_postfix(&mut self, expr: Expression) -> Result<Expression, String> {
+    fn parse_postfix(&mut self, expr: Expression) -> Result<Expression, Box<Diagnostic>> {
         let mut result = expr;
         
-        // 检查是否有后缀操作（数组方法调用）
-        while self.match_token(&[TokenType::点]) {
-            // 使用advance_if_matches来匹配标识符
-            if let Some(token) = self.advance_if_matches(|t| matches!(t.token_type, TokenType::标识符(_))) {
-                let method_name = if let TokenType::标识符(name) = token.token_type {
-                    name
-                } else {
-                    return Err("期望方法名".to_string());
-                };
+        // 调用和索引的括号必须和前面的表达式在同一行，否则是下一条语句的开头
+        loop {
+            let same_line = self.peek().line == self.previous().line;
+            
+            if same_line && self.match_token(&[TokenType::左括号]) {
+                result = self.finish_call(result)?;
+                continue;
+            }
+            
+            if same_line && self.match_token(&[TokenType::左中括号]) {
+                let bracket = self.previous().span();
+                let index = self.without_condition(|parser| parser.expect_expression("期望索引表达式"))?;
+                self.consume(TokenType::右中括号, "期望 ']'")?;
                 
-                match method_name.as_str() {
-                    "长度" => {
-                        if !self.match_token(&[TokenType::左括号]) {
-                            return Err("期望 '('".to_string());
-                        }
-                        
-                        if !self.match_token(&[TokenType::右括号]) {
-                            return Err("期望 ')'".to_string());
-                        }
-                        
-                        result = Expression::数组长度(Box::new(result));
-                    },
-                    "添加" => {
-                        if !self.match_token(&[TokenType::左括号]) {
-                            return Err("期望 '('".to_string());
-                        }
-                        
-                        let element_expr = self.parse_expression()?;
-                        if element_expr.is_none() {
-                            return Err("期望元素表达式".to_string());
-                        }
-                        
-                        if !self.match_token(&[TokenType::右括号]) {
-                            return Err("期望 ')'".to_string());
-                        }
-                        
-                        result = Expression::数组添加 {
-                            数组: Box::new(result),
-                            元素: Box::new(element_expr.unwrap()),
-                        };
-                    },
-                    "删除" => {
-                        if !self.match_token(&[TokenType::左括号]) {
-                            return Err("期望 '('".to_string());
-                        }
-                        
-                        let index_expr = self.parse_expression()?;
-                        if index_expr.is_none() {
-                            return Err("期望索引表达式".to_string());
-                        }
-                        
-                        if !self.match_token(&[TokenType::右括号]) {
-                            return Err("期望 ')'".to_string());
-                        }
-                        
-                        result = Expression::数组删除 {
-                            数组: Box::new(result),
-                            索引: Box::new(index_expr.unwrap()),
-                        };
-                    },
-                    _ => {
-                        return Err(format!("未知的方法: {}", method_name));
-                    }
+                result = Expression::数组索引 {
+                    数组: Box::new(result),
+                    索引: Box::new(index),
+                    位置: bracket,
+                };
+                continue;
+            }
+            
+            if !self.match_token(&[TokenType::点]) {
+                break;
+            }
+            let (member, span) = self.consume_identifier("期望成员名")?;
+            
+            // 成员后面紧跟括号是方法调用，否则是字段访问
+            result = if self.peek().line == span.line && self.match_token(&[TokenType::左括号]) {
+                Expression::方法调用 {
+                    对象: Box::new(result),
+                    方法: member,
+                    参数: self.finish_arguments()?,
+                    位置: span,
                 }
             } else {
-                return Err("期望方法名".to_string());
-            }
+                Expression::成员访问 {
+                    对象: Box::new(result),
+                    成员: member,
+                    位置: span,
+                }
+            };
         }
         
         Ok(result)
     }
     
-    fn parse_primary(&mut self) -> Result<Option<Expression>, String> {
+    fn parse_primary(&mut self) -> Result<Option<Expression>, Box<Diagnostic>> {
         // 直接检查数字token
-        if let TokenType::数字(value) = &self.peek().token_type {
+        if matches!(self.peek().token_type, TokenType::数字(_)) {
             let token = self.advance();
             if let TokenType::数字(value) = token.token_type {
                 return Ok(Some(Expression::数字字面量(value)));
@@ -603,10 +827,7 @@ impl Parser {
         
         if let Some(token) = self.advance_if_matches(|t| matches!(t.token_type, TokenType::布尔(_))) {
             if let TokenType::布尔(value) = token.token_type {
-                // 将布尔值转换为数字字面量（1.0表示true，0.0表示false）
-                // 这样可以与现有的is_truthy函数兼容
-                let num_value = if value { 1.0 } else { 0.0 };
-                return Ok(Some(Expression::数字字面量(num_value)));
+                return Ok(Some(Expression::布尔字面量(value)));
             }
         }
         
@@ -616,9 +837,7 @@ impl Parser {
             
             if !self.check(&TokenType::右中括号) {
                 loop {
-                    if let Some(element) = self.parse_expression()? {
-                        elements.push(element);
-                    }
+                    elements.push(self.without_condition(|parser| parser.expect_expression("期望数组元素"))?);
                     
                     if !self.match_token(&[TokenType::逗号]) {
                         break;
@@ -626,48 +845,72 @@ impl Parser {
                 }
             }
             
-            if !self.match_token(&[TokenType::右中括号]) {
-                return Err("期望 ']'".to_string());
-            }
+            self.consume(TokenType::右中括号, "期望 ']'")?;
             
             return Ok(Some(Expression::数组字面量(elements)));
         }
         
-        if let Some(token) = self.advance_if_matches(|t| matches!(t.token_type, TokenType::标识符(_))) {
-            if let TokenType::标识符(name) = token.token_type {
-                if self.match_token(&[TokenType::左括号]) {
-                    return self.parse_function_call(name);
-                }
-                
-                // 检查是否是数组索引访问 array[index]
-                if self.match_token(&[TokenType::左中括号]) {
-                    let index_expr = self.parse_expression()?;
-                    if !self.match_token(&[TokenType::右中括号]) {
-                        return Err("期望 ']'".to_string());
-                    }
+        // 解析字典字面量 {"名字": "张三", "年龄": 25}
+        if !self.in_condition && self.match_token(&[TokenType::左大括号]) {
+            let open = self.previous().span();
+            let mut entries = Vec::new();
+            
+            if !self.check(&TokenType::右大括号) {
+                loop {
+                    let key = self.expect_expression("期望字典的键")?;
+                    self.consume(TokenType::冒号, "期望 ':'")?;
+                    let value = self.expect_expression("期望字典的值")?;
+                    entries.push((key, value));
                     
-                    if let Some(index) = index_expr {
-                        return Ok(Some(Expression::数组索引 {
-                            数组: Box::new(Expression::变量(name)),
-                            索引: Box::new(index),
-                        }));
-                    } else {
-                        return Err("期望索引表达式".to_string());
+                    if !self.match_token(&[TokenType::逗号]) {
+                        break;
                     }
                 }
-                
-                return Ok(Some(Expression::变量(name)));
             }
+            
+            if !self.match_token(&[TokenType::右大括号]) {
+                return Err(self.error("E0101", "期望 '}'").with_label(open, "左大括号在这里").into());
+            }
+            
+            return Ok(Some(Expression::字典字面量 { 条目: entries, 位置: open }));
+        }
+        
+        if let Some(token) = self.advance_if_matches(|t| matches!(t.token_type, TokenType::标识符(_))) {
+            let span = token.span();
+            if let TokenType::标识符(name) = token.token_type {
+                return Ok(Some(Expression::变量 { 名字: name, 位置: span, 槽位: Cell::default() }));
+            }
+        }
+        
+        if self.match_token(&[TokenType::自己]) {
+            return Ok(Some(Expression::自己 { 位置: self.previous().span() }));
+        }
+        
+        // 匿名函数 函数(x) { ... }
+        if self.match_token(&[TokenType::函数]) {
+            let span = self.previous().span();
+            let (parameters, parameter_types) = self.parse_parameters()?;
+            let return_type = self.parse_return_type()?;
+            let body = self.parse_block()?;
+            
+            return Ok(Some(Expression::匿名函数 {
+                参数: parameters,
+                参数类型: parameter_types,
+                返回类型: return_type,
+                体: body,
+                位置: span,
+            }));
         }
         
         if self.match_token(&[TokenType::左括号]) {
-            let expr = self.parse_expression()?;
+            let open = self.previous().span();
+            let expr = self.without_condition(|parser| parser.expect_expression("期望表达式"))?;
             
             if !self.match_token(&[TokenType::右括号]) {
-                return Err("期望 ')'".to_string());
+                return Err(self.error("E0101", "期望 ')'").with_label(open, "左括号在这里").into());
             }
             
-            return Ok(expr);
+            return Ok(Some(expr));
         }
         
         // 数组方法调用将在更高级别的解析方法中处理
@@ -675,14 +918,30 @@ impl Parser {
         Ok(None)
     }
     
-    fn parse_function_call(&mut self, function_name: String) -> Result<Option<Expression>, String> {
+    /// 解析左括号之后的调用
+    fn finish_call(&mut self, callee: Expression) -> Result<Expression, Box<Diagnostic>> {
+        let paren = self.previous().span();
+        let arguments = self.finish_arguments()?;
+        
+        let span = match &callee {
+            Expression::变量 { 位置, .. } => *位置,
+            _ => paren,
+        };
+        
+        Ok(Expression::函数调用 {
+            函数: Box::new(callee),
+            参数: arguments,
+            位置: span,
+        })
+    }
+    
+    /// 解析左括号之后的参数列表和右括号
+    fn finish_arguments(&mut self) -> Result<Vec<Expression>, Box<Diagnostic>> {
         let mut arguments = Vec::new();
         
         if !self.check(&TokenType::右括号) {
             loop {
-                if let Some(arg) = self.parse_expression()? {
-                    arguments.push(arg);
-                }
+                arguments.push(self.without_condition(|parser| parser.expect_expression("期望参数表达式"))?);
                 
                 if !self.match_token(&[TokenType::逗号]) {
                     break;
@@ -690,17 +949,42 @@ impl Parser {
             }
         }
         
-        if !self.match_token(&[TokenType::右括号]) {
-            return Err("期望 ')'".to_string());
+        self.consume(TokenType::右括号, "期望 ')'")?;
+        Ok(arguments)
+    }
+    
+    /// 在当前记号处报告错误
+    fn error(&self, code: &'static str, message: impl Into<String>) -> Diagnostic {
+        let token = self.peek();
+        let message = message.into();
+        match token.token_type {
+            // 文件结尾没有可以显示的源代码，指向最后一个记号之后
+            TokenType::文件结束 if self.current > 0 => {
+                let last = self.previous();
+                Diagnostic::error(code, format!("{}，但已到达文件结尾", message))
+                    .with_span(Span::new(last.line, last.column + last.length, 1))
+            }
+            TokenType::文件结束 => Diagnostic::error(code, format!("{}，但已到达文件结尾", message)),
+            _ => Diagnostic::error(code, format!("{}，但找到了 '{}'", message, token.token_type)).with_span(token.span()),
         }
-        
-        Ok(Some(Expression::函数调用 {
-            函数名: function_name,
-            参数: arguments,
-        }))
     }
     
-
+    fn consume(&mut self, token_type: TokenType, message: &str) -> Result<Token, Box<Diagnostic>> {
+        if self.check(&token_type) {
+            Ok(self.advance())
+        } else {
+            Err(self.error("E0101", message).into())
+        }
+    }
+    
+    fn consume_identifier(&mut self, message: &str) -> Result<(String, Span), Box<Diagnostic>> {
+        match self.advance_if_matches(|t| matches!(t.token_type, TokenType::标识符(_))) {
+            Some(Token { token_type: TokenType::标识符(name), line, column, length }) => {
+                Ok((name, Span::new(line, column, length)))
+            }
+            _ => Err(self.error("E0101", message).into()),
+        }
+    }
     
     fn match_token(&mut self, types: &[TokenType]) -> bool {
         for token_type in types {
@@ -716,15 +1000,25 @@ impl Parser {
         if self.is_at_end() {
             return false;
         }
-        
-        let current_token = &self.tokens[self.current];
-        
-        match (token_type, &current_token.token_type) {
+        Self::same_kind(token_type, &self.tokens[self.current].token_type)
+    }
+    
+    /// 检查当前记号之后的那个记号
+    fn check_next(&self, token_type: &TokenType) -> bool {
+        match self.tokens.get(self.current + 1) {
+            Some(token) => Self::same_kind(token_type, &token.token_type),
+            None => false,
+        }
+    }
+    
+    /// 只比较记号的种类，不比较携带的值
+    fn same_kind(expected: &TokenType, actual: &TokenType) -> bool {
+        match (expected, actual) {
             (TokenType::标识符(_), TokenType::标识符(_)) => true,
             (TokenType::数字(_), TokenType::数字(_)) => true,
             (TokenType::字符串(_), TokenType::字符串(_)) => true,
             (TokenType::错误(_), TokenType::错误(_)) => true,
-            _ => std::mem::discriminant(token_type) == std::mem::discriminant(&current_token.token_type),
+            _ => std::mem::discriminant(expected) == std::mem::discriminant(actual),
         }
     }
     
@@ -759,4 +1053,4 @@ impl Parser {
     fn peek(&self) -> Token {
         self.tokens[self.current].clone()
     }
-}
\ No newline at end of file
+}
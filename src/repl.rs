@@ -0,0 +1,150 @@
+//! 交互式解释器（读取-求值-输出循环）
+
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+use crate::interpreter::{Interpreter, Value};
+use crate::lexer::{Lexer, TokenType};
+use crate::{parse_interactive, report_errors, source_directory};
+
+const PROMPT: &str = "中文> ";
+const CONTINUATION_PROMPT: &str = "....> ";
+const INPUT_NAME: &str = "<输入>";
+
+pub fn run() {
+    println!("中文编程语言交互模式，输入 :帮助 查看命令，:退出 或 Ctrl+D 结束");
+    
+    let mut interpreter = Interpreter::new();
+    let mut buffer = String::new();
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    
+    loop {
+        print!("{}", if buffer.is_empty() { PROMPT } else { CONTINUATION_PROMPT });
+        let _ = io::stdout().flush();
+        
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => {
+                println!();
+                break;
+            }
+        };
+        
+        if buffer.is_empty() {
+            let trimmed = line.trim();
+            if let Some(command) = trimmed.strip_prefix(':') {
+                if !run_command(command, &mut interpreter) {
+                    break;
+                }
+                continue;
+            }
+            if trimmed.is_empty() {
+                continue;
+            }
+        }
+        
+        buffer.push_str(&line);
+        buffer.push('\n');
+        
+        // 多行输入中的空行强制提交，方便从写错的括号中脱身
+        if is_incomplete(&buffer) && !line.trim().is_empty() {
+            continue;
+        }
+        
+        let input = std::mem::take(&mut buffer);
+        evaluate(&input, INPUT_NAME, &mut interpreter);
+    }
+}
+
+/// 执行一条命令，返回 false 表示退出
+fn run_command(command: &str, interpreter: &mut Interpreter) -> bool {
+    let (name, argument) = match command.split_once(char::is_whitespace) {
+        Some((name, argument)) => (name, argument.trim()),
+        None => (command, ""),
+    };
+    
+    match name {
+        "载入" => {
+            if argument.is_empty() {
+                println!("用法: :载入 <文件名.cn>");
+            } else {
+                match fs::read_to_string(argument) {
+                    Ok(content) => evaluate(&content, argument, interpreter),
+                    Err(e) => println!("无法读取文件 {}: {}", argument, e),
+                }
+            }
+        }
+        "变量" => {
+            for env in interpreter.environments() {
+                if env.bindings.is_empty() {
+                    continue;
+                }
+                match env.parent {
+                    Some(parent) => println!("作用域 #{} (上级 #{})", env.id, parent),
+                    None => println!("作用域 #{} (全局)", env.id),
+                }
+                for (name, value) in env.bindings {
+                    println!("  {} = {}", name, value);
+                }
+            }
+        }
+        "重置" => {
+            *interpreter = Interpreter::new();
+            println!("已重置解释器状态");
+        }
+        "帮助" => {
+            println!("命令:");
+            println!("  :载入 <文件>  执行文件并保留其中的定义");
+            println!("  :变量         显示所有作用域中的变量");
+            println!("  :重置         清除所有定义");
+            println!("  :退出         退出交互模式");
+            println!("括号未闭合时可以继续输入下一行，输入空行强制执行");
+        }
+        "退出" => return false,
+        _ => println!("未知命令: :{}，输入 :帮助 查看可用命令", name),
+    }
+    true
+}
+
+fn evaluate(source: &str, filename: &str, interpreter: &mut Interpreter) {
+    let program = match parse_interactive(source, filename, &interpreter.global_names()) {
+        Ok(program) => program,
+        Err(errors) => {
+            report_errors(&errors, source, filename);
+            return;
+        }
+    };
+    if let Err(errors) = interpreter.load_imports(&program, &source_directory(filename)) {
+        report_errors(&errors, source, filename);
+        return;
+    }
+    
+    match interpreter.execute_interactive(&program) {
+        Ok(Some(Value::空)) | Ok(None) => {}
+        Ok(Some(value)) => println!("{}", value),
+        Err(mut error) => {
+            // 运行时的附加位置可能指向之前的输入，放在这次的源代码上会错位
+            error.labels.clear();
+            report_errors(&[*error], source, filename);
+        }
+    }
+}
+
+/// 括号没有闭合或字符串没有结束时，输入还不完整
+fn is_incomplete(source: &str) -> bool {
+    let tokens = match Lexer::new(source).tokenize() {
+        Ok(tokens) => tokens,
+        Err(error) => return error.code == "E0002",
+    };
+    
+    let mut depth = 0i32;
+    for token in &tokens {
+        match token.token_type {
+            TokenType::左大括号 | TokenType::左括号 | TokenType::左中括号 => depth += 1,
+            TokenType::右大括号 | TokenType::右括号 | TokenType::右中括号 => depth -= 1,
+            _ => {}
+        }
+    }
+    depth > 0
+}
@@ -0,0 +1,718 @@
+//! 名字解析：把每次使用的名字对应到它的声明
+//!
+//! 执行之前报告未定义的名字、已知函数的参数数量不匹配、调用不是函数的值、函数外的 `返回`、
+//! 循环外的 `跳出` 和 `继续`、没有使用的变量，以及 `返回` 等语句之后不会执行的代码；
+//! 同时给每次使用的名字填写槽位，解释器用它代替按名字查找。语言服务器用解析的结果
+//! 做跳转到定义、查找引用、悬停和补全。
+//!
+//! 作用域的划分与解释器一致：每个函数和每个 `{}` 代码块各有一个作用域，作用域中的绑定
+//! 按第一次声明的顺序编号。函数中的名字按源代码的顺序先在函数自己的作用域中查找；
+//! 函数外面的名字在调用时才查找，可以使用在函数之后才声明的外层名字，
+//! 所以这些名字等整个程序走完之后，再按各作用域最后的状态查找。
+
+use std::cell::Cell;
+use std::collections::HashMap;
+
+use crate::ast::{Expression, Program, Slot, Statement, Type};
+use crate::diagnostic::{Diagnostic, Span};
+use crate::formatter;
+use crate::module::import_name;
+
+/// 解释器预先定义的全局函数和它们的写法，顺序与解释器的全局作用域一致
+pub const BUILTINS: [(&str, &str); 3] = [
+    ("打印", "函数 打印(值)"),
+    ("输入", "函数 输入()"),
+    ("范围", "函数 范围(起, 止, 步长)"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    变量,
+    常量,
+    函数,
+    结构,
+    参数,
+    模块,
+    内置函数,
+}
+
+#[derive(Debug)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// 声明中名字的位置，参数和内置函数没有位置
+    pub span: Option<Span>,
+    /// 悬停时显示的声明，例如 `函数 加(a, b)`
+    pub signature: String,
+    pub scope: usize,
+    /// 在作用域中的序号，同一作用域中同名的声明共用一个序号
+    pub slot: usize,
+    /// 按固定数量的参数调用时的参数名：函数的参数或结构的字段
+    pub parameters: Option<Vec<String>>,
+    /// 初始值是字面量时值的类型，调用它是错误
+    literal: Option<&'static str>,
+    /// 变量、常量和参数的类型标注
+    pub annotation: Option<Type>,
+    /// 函数和初始值是匿名函数的变量的参数类型与返回类型
+    pub parameter_types: Vec<Option<Type>>,
+    pub returns: Option<Type>,
+    /// 被赋值或在同一作用域中重新声明过，调用时的值不能确定
+    pub changed: bool,
+    /// `让` 和 `常量` 声明的名字，声明之后一次也没有用到时给出警告
+    check_unused: bool,
+    uses: usize,
+}
+
+#[derive(Debug)]
+pub struct Scope {
+    pub parent: Option<usize>,
+    /// 函数的作用域，函数外面的名字在调用时才查找
+    pub function: bool,
+    /// 结构的方法，`自己` 占第一个绑定
+    method: bool,
+    /// 作用域覆盖的源代码从 `start` 开始，到所在语句的下一条语句之前为止；
+    /// 位置是 (行, 列)，`end` 为 `None` 时一直到文件结尾
+    pub start: (usize, usize),
+    pub end: Option<(usize, usize)>,
+    names: HashMap<String, usize>,
+    /// 作用域中绑定的数量
+    slots: usize,
+}
+
+/// 一次使用或声明
+#[derive(Debug, Clone, Copy)]
+pub struct Reference {
+    pub span: Span,
+    pub symbol: usize,
+}
+
+#[derive(Debug)]
+pub struct Resolution {
+    pub symbols: Vec<Symbol>,
+    pub scopes: Vec<Scope>,
+    /// 名字出现的位置和对应的声明，声明本身也在其中
+    pub references: Vec<Reference>,
+    /// 发现的错误和警告，按位置排列
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Resolution {
+    /// 位置上（包括紧跟在名字后面）的名字对应的声明
+    pub fn symbol_at(&self, line: usize, column: usize) -> Option<usize> {
+        self.references
+            .iter()
+            .find(|reference| {
+                let span = reference.span;
+                span.line == line && span.column <= column && column <= span.column + span.length
+            })
+            .map(|reference| reference.symbol)
+    }
+    
+    /// 声明和所有使用的位置，按在源代码中的顺序排列
+    pub fn references_to(&self, symbol: usize) -> Vec<Span> {
+        let mut spans: Vec<Span> = self
+            .references
+            .iter()
+            .filter(|reference| reference.symbol == symbol)
+            .map(|reference| reference.span)
+            .collect();
+        spans.sort_by_key(|span| (span.line, span.column));
+        spans.dedup();
+        spans
+    }
+    
+    /// 在位置上可以使用的名字，内层的名字遮住外层的同名名字
+    pub fn visible_at(&self, line: usize, column: usize) -> Vec<&Symbol> {
+        let position = (line, column);
+        let innermost = (0..self.scopes.len())
+            .filter(|&id| {
+                let scope = &self.scopes[id];
+                scope.start <= position && scope.end.is_none_or(|end| position < end)
+            })
+            .max_by_key(|&id| (self.scopes[id].start, id))
+            .unwrap_or(0);
+        
+        let mut visible: Vec<&Symbol> = Vec::new();
+        // 在函数中时，函数外面的名字不论在哪里声明都可以使用
+        let mut in_function = false;
+        let mut scope = Some(innermost);
+        while let Some(id) = scope {
+            for symbol in self.symbols.iter().filter(|symbol| symbol.scope == id) {
+                let declared = in_function || symbol.span.is_none_or(|span| (span.line, span.column) < position);
+                if declared && !visible.iter().any(|seen| seen.name == symbol.name) {
+                    visible.push(symbol);
+                }
+            }
+            in_function |= self.scopes[id].function;
+            scope = self.scopes[id].parent;
+        }
+        visible
+    }
+}
+
+/// 解析一个文件的程序，并给程序中使用的名字填写槽位
+pub fn resolve(program: &Program) -> Resolution {
+    Resolver::new(false).run(program, &[])
+}
+
+/// 解析交互模式的一次输入：`globals` 是之前的输入留在全局作用域中的名字，按绑定的顺序排列。
+/// 它们的值已经不能确定，不检查调用的参数数量；最外层的声明留给以后的输入使用，不报告没有使用
+pub fn resolve_interactive(program: &Program, globals: &[String]) -> Resolution {
+    Resolver::new(true).run(program, globals)
+}
+
+#[derive(Clone, Copy)]
+enum Access {
+    Read,
+    Assign,
+    /// 调用名字对应的值，带参数的数量
+    Call(usize),
+}
+
+/// 一次使用名字
+struct Use<'a> {
+    name: &'a str,
+    span: Span,
+    scope: usize,
+    access: Access,
+    /// 在函数中使用、函数中在这之前没有声明的名字，等程序走完之后从函数外面的这个作用域开始查找
+    deferred: Option<usize>,
+    /// 不推迟时立即找到的声明
+    symbol: Option<usize>,
+    slot: &'a Cell<Option<Slot>>,
+}
+
+struct Resolver<'a> {
+    resolution: Resolution,
+    current: usize,
+    uses: Vec<Use<'a>>,
+    /// 当前函数中包围着的循环层数，进入函数时从零开始
+    loops: usize,
+    interactive: bool,
+}
+
+impl<'a> Resolver<'a> {
+    fn new(interactive: bool) -> Self {
+        Resolver {
+            resolution: Resolution {
+                symbols: Vec::new(),
+                scopes: Vec::new(),
+                references: Vec::new(),
+                diagnostics: Vec::new(),
+            },
+            current: 0,
+            uses: Vec::new(),
+            loops: 0,
+            interactive,
+        }
+    }
+    
+    fn run(mut self, program: &'a Program, globals: &[String]) -> Resolution {
+        self.enter(false, false, (0, 0), None);
+        for (name, signature) in BUILTINS {
+            self.declare(name, SymbolKind::内置函数, None, signature.to_string());
+        }
+        for name in globals {
+            if !self.resolution.scopes[0].names.contains_key(name) {
+                self.declare(name, SymbolKind::变量, None, format!("让 {}", name));
+            }
+        }
+        if self.interactive {
+            for symbol in &mut self.resolution.symbols {
+                symbol.changed = true;
+            }
+        }
+        
+        self.resolve_statements(&program.语句, None);
+        self.finish();
+        self.resolution
+    }
+    
+    fn enter(&mut self, function: bool, method: bool, start: (usize, usize), end: Option<(usize, usize)>) {
+        let parent = (!self.resolution.scopes.is_empty()).then_some(self.current);
+        self.resolution.scopes.push(Scope {
+            parent,
+            function,
+            method,
+            start,
+            end,
+            names: HashMap::new(),
+            slots: usize::from(method),
+        });
+        self.current = self.resolution.scopes.len() - 1;
+    }
+    
+    fn leave(&mut self) {
+        self.current = self.resolution.scopes[self.current].parent.unwrap_or(0);
+    }
+    
+    /// 在当前作用域中声明名字，返回新的声明；同名的旧声明和新声明共用一个槽位
+    fn declare(&mut self, name: &str, kind: SymbolKind, span: Option<Span>, signature: String) -> usize {
+        let id = self.resolution.symbols.len();
+        let scope = &mut self.resolution.scopes[self.current];
+        let existing = scope.names.insert(name.to_string(), id);
+        let slot = match existing {
+            Some(existing) => {
+                self.resolution.symbols[existing].changed = true;
+                self.resolution.symbols[existing].slot
+            }
+            None => {
+                scope.slots += 1;
+                scope.slots - 1
+            }
+        };
+        self.resolution.symbols.push(Symbol {
+            name: name.to_string(),
+            kind,
+            span,
+            signature,
+            scope: self.current,
+            slot,
+            parameters: None,
+            literal: None,
+            annotation: None,
+            parameter_types: Vec::new(),
+            returns: None,
+            changed: existing.is_some(),
+            check_unused: false,
+            uses: 0,
+        });
+        if let Some(span) = span {
+            self.resolution.references.push(Reference { span, symbol: id });
+        }
+        id
+    }
+    
+    fn lookup_from(&self, scope: usize, name: &str) -> Option<usize> {
+        let mut scope = Some(scope);
+        while let Some(id) = scope {
+            if let Some(&symbol) = self.resolution.scopes[id].names.get(name) {
+                return Some(symbol);
+            }
+            scope = self.resolution.scopes[id].parent;
+        }
+        None
+    }
+    
+    /// 当前作用域或它的上级作用域满足 `predicate`
+    fn inside(&self, predicate: impl Fn(&Scope) -> bool) -> bool {
+        let mut scope = Some(self.current);
+        while let Some(id) = scope {
+            if predicate(&self.resolution.scopes[id]) {
+                return true;
+            }
+            scope = self.resolution.scopes[id].parent;
+        }
+        false
+    }
+    
+    /// 函数中的名字按源代码的顺序在函数自己的作用域中查找，找不到时推迟到程序走完之后在函数外面查找
+    fn use_name(&mut self, name: &'a str, span: Span, access: Access, slot: &'a Cell<Option<Slot>>) {
+        let (mut symbol, mut deferred) = (None, None);
+        let mut next = Some(self.current);
+        while let Some(id) = next {
+            let scope = &self.resolution.scopes[id];
+            if let Some(&found) = scope.names.get(name) {
+                symbol = Some(found);
+                break;
+            }
+            if scope.function {
+                deferred = scope.parent;
+                break;
+            }
+            next = scope.parent;
+        }
+        self.uses.push(Use {
+            name,
+            span,
+            scope: self.current,
+            access,
+            deferred,
+            symbol,
+            slot,
+        });
+    }
+    
+    fn error(&mut self, diagnostic: Diagnostic) {
+        self.resolution.diagnostics.push(diagnostic);
+    }
+    
+    /// 查找推迟的名字，填写槽位，再检查调用和没有使用的变量
+    fn finish(&mut self) {
+        let uses = std::mem::take(&mut self.uses);
+        let mut resolved = Vec::new();
+        for item in &uses {
+            let symbol = match item.deferred {
+                Some(outer) => self.lookup_from(outer, item.name),
+                None => item.symbol,
+            };
+            let Some(symbol) = symbol else {
+                let error = match item.access {
+                    Access::Call(_) => Diagnostic::error("E0201", format!("未定义的函数: {}", item.name)),
+                    _ => Diagnostic::error("E0200", format!("未定义的变量: {}", item.name)),
+                };
+                self.error(error.with_span(item.span));
+                continue;
+            };
+            
+            self.resolution.references.push(Reference { span: item.span, symbol });
+            let declaration = &mut self.resolution.symbols[symbol];
+            declaration.uses += 1;
+            if let Access::Assign = item.access {
+                declaration.changed = true;
+            }
+            let index = declaration.slot;
+            let mut depth = 0;
+            let mut scope = item.scope;
+            while scope != declaration.scope {
+                scope = self.resolution.scopes[scope].parent.expect("声明所在的作用域是使用处的上级");
+                depth += 1;
+            }
+            item.slot.set(Some(Slot { depth, index }));
+            resolved.push((item, symbol));
+        }
+        
+        // 所有赋值都记录之后才能确定哪些函数的值没有变过
+        for (item, symbol) in resolved {
+            if let Access::Call(arguments) = item.access {
+                self.check_call(item.name, item.span, symbol, arguments);
+            }
+        }
+        
+        for symbol in &self.resolution.symbols {
+            if symbol.check_unused && symbol.uses == 0 && !symbol.name.starts_with('_') {
+                let kind = if symbol.kind == SymbolKind::常量 { "常量" } else { "变量" };
+                let mut warning = Diagnostic::warning("W0102", format!("{} {} 声明后没有使用", kind, symbol.name))
+                    .with_note(format!("不需要的值可以用下划线开头的名字，例如 _{}", symbol.name));
+                if let Some(span) = symbol.span {
+                    warning = warning.with_span(span);
+                }
+                self.resolution.diagnostics.push(warning);
+            }
+        }
+        
+        self.resolution
+            .diagnostics
+            .sort_by_key(|diagnostic| diagnostic.span.map(|span| (span.line, span.column)));
+    }
+    
+    /// 检查对值没有变过的函数、结构或字面量的调用
+    fn check_call(&mut self, name: &str, span: Span, symbol: usize, arguments: usize) {
+        let symbol = &self.resolution.symbols[symbol];
+        if symbol.changed {
+            return;
+        }
+        let error = match (symbol.kind, &symbol.parameters) {
+            (SymbolKind::内置函数, _) => {
+                let (expected, message) = match name {
+                    "打印" => (arguments == 1, "打印函数需要一个参数"),
+                    "输入" => (arguments == 0, "输入函数不需要参数"),
+                    _ => ((1..=3).contains(&arguments), "范围函数需要 1 到 3 个参数"),
+                };
+                if expected {
+                    return;
+                }
+                Diagnostic::error("E0203", message)
+            }
+            (SymbolKind::结构, Some(fields)) if fields.len() != arguments => Diagnostic::error(
+                "E0203",
+                format!("参数数量不匹配: 结构 {} 有 {} 个字段, 得到 {} 个参数", name, fields.len(), arguments),
+            )
+            .with_note(format!("{}({})", name, fields.join(", "))),
+            (_, Some(parameters)) if parameters.len() != arguments => Diagnostic::error(
+                "E0203",
+                format!("参数数量不匹配: 期望 {}, 得到 {}", parameters.len(), arguments),
+            )
+            .with_note(format!("{}({})", name, parameters.join(", "))),
+            (_, None) if symbol.literal.is_some() => Diagnostic::error("E0202", format!("{} 不是函数", name))
+                .with_note(format!("{} 的值是{}", name, symbol.literal.unwrap_or_default())),
+            _ => return,
+        };
+        let error = match symbol.span {
+            Some(declared_at) => error.with_label(declared_at, "在这里声明"),
+            None => error,
+        };
+        self.error(error.with_span(span));
+    }
+    
+    /// `end` 是这组语句之后的第一个位置，用作最后一条语句中作用域的结尾
+    fn resolve_statements(&mut self, statements: &'a [Statement], end: Option<(usize, usize)>) {
+        // 返回、跳出、继续或抛出之后的语句不会执行，每组语句只报告第一条
+        let mut exit: Option<(&str, Span)> = None;
+        let mut reported = false;
+        for (i, statement) in statements.iter().enumerate() {
+            if let (Some((keyword, at)), Some(span), false) = (exit, statement.span(), reported) {
+                let warning = Diagnostic::warning("W0103", format!("{} 之后的代码不会执行", keyword))
+                    .with_span(span)
+                    .with_label(at, format!("在这里{}", keyword));
+                self.error(warning);
+                reported = true;
+            }
+            
+            let next = statements[i + 1..]
+                .iter()
+                .find_map(Statement::span)
+                .map(|span| (span.line, span.column))
+                .or(end);
+            self.resolve_statement(statement, next);
+            exit = exit.or(match statement {
+                Statement::返回语句 { 位置, .. } => Some(("返回", *位置)),
+                Statement::跳出语句 { 位置 } => Some(("跳出", *位置)),
+                Statement::继续语句 { 位置 } => Some(("继续", *位置)),
+                Statement::抛出语句 { 位置, .. } => Some(("抛出", *位置)),
+                _ => None,
+            });
+        }
+    }
+    
+    fn resolve_block(&mut self, statements: &'a [Statement], start: (usize, usize), end: Option<(usize, usize)>) {
+        self.enter(false, false, start, end);
+        self.resolve_statements(statements, end);
+        self.leave();
+    }
+    
+    /// 循环体中可以使用 `跳出` 和 `继续`
+    fn resolve_loop_body(&mut self, statements: &'a [Statement], start: (usize, usize), end: Option<(usize, usize)>) {
+        self.loops += 1;
+        self.resolve_block(statements, start, end);
+        self.loops -= 1;
+    }
+    
+    fn resolve_function(
+        &mut self,
+        parameters: &[String],
+        types: &[Option<Type>],
+        body: &'a [Statement],
+        method: bool,
+        span: Span,
+        end: Option<(usize, usize)>,
+    ) {
+        let loops = std::mem::take(&mut self.loops);
+        self.enter(true, method, (span.line, span.column), end);
+        for (i, param) in parameters.iter().enumerate() {
+            let annotation = types.get(i).copied().flatten();
+            let id = self.declare(param, SymbolKind::参数, None, format!("参数 {}", formatter::annotated(param, annotation)));
+            self.resolution.symbols[id].annotation = annotation;
+        }
+        self.resolve_statements(body, end);
+        self.leave();
+        self.loops = loops;
+    }
+    
+    fn resolve_statement(&mut self, statement: &'a Statement, end: Option<(usize, usize)>) {
+        let start = statement.span().map_or((0, 0), |span| (span.line, span.column));
+        match statement {
+            Statement::表达式语句(expr) => self.resolve_expression(expr, end),
+            Statement::变量声明 { 名字, 类型, 初始值, 是常量, 是公开, 位置 } => {
+                if let Some(expr) = 初始值 {
+                    self.resolve_expression(expr, end);
+                }
+                let (kind, keyword) = if *是常量 {
+                    (SymbolKind::常量, "常量")
+                } else {
+                    (SymbolKind::变量, "让")
+                };
+                let name = formatter::annotated(名字, *类型);
+                let signature = match 初始值 {
+                    Some(Expression::匿名函数 { 参数, 参数类型, 返回类型, .. }) => {
+                        format!("{} {} = 函数{}", keyword, name, formatter::parameters(参数, 参数类型, *返回类型))
+                    }
+                    _ => format!("{} {}", keyword, name),
+                };
+                let top_level = self.current == 0;
+                let id = self.declare(名字, kind, Some(*位置), signature);
+                let symbol = &mut self.resolution.symbols[id];
+                symbol.annotation = *类型;
+                match 初始值 {
+                    Some(Expression::匿名函数 { 参数, 参数类型, 返回类型, .. }) => {
+                        symbol.parameters = Some(参数.clone());
+                        symbol.parameter_types = 参数类型.clone();
+                        symbol.returns = *返回类型;
+                    }
+                    Some(expr) => symbol.literal = literal_type(expr),
+                    None => {}
+                }
+                // 公开的声明给导入它的模块使用
+                symbol.check_unused = !(*是公开 || self.interactive && top_level);
+            }
+            Statement::函数声明 { 名字, 参数, 参数类型, 返回类型, 体, 位置, .. } => {
+                let signature = format!("函数 {}{}", 名字, formatter::parameters(参数, 参数类型, *返回类型));
+                let id = self.declare(名字, SymbolKind::函数, Some(*位置), signature);
+                let symbol = &mut self.resolution.symbols[id];
+                symbol.parameters = Some(参数.clone());
+                symbol.parameter_types = 参数类型.clone();
+                symbol.returns = *返回类型;
+                self.resolve_function(参数, 参数类型, 体, false, *位置, end);
+            }
+            Statement::结构声明 { 名字, 字段, 方法, 位置, .. } => {
+                let id = self.declare(名字, SymbolKind::结构, Some(*位置), format!("结构 {} {{ {} }}", 名字, 字段.join(", ")));
+                self.resolution.symbols[id].parameters = Some(字段.clone());
+                for method in 方法 {
+                    if let Statement::函数声明 { 参数, 参数类型, 体, 位置, .. } = method {
+                        self.resolve_function(参数, 参数类型, 体, true, *位置, end);
+                    }
+                }
+            }
+            Statement::导入语句 { 路径, 别名, 位置 } => {
+                let name = import_name(路径, 别名.as_deref());
+                self.declare(&name, SymbolKind::模块, Some(*位置), format!("导入 \"{}\"", 路径));
+            }
+            Statement::如果语句 { 条件, 真分支, 假分支, .. } => {
+                self.resolve_expression(条件, end);
+                self.resolve_block(真分支, start, end);
+                self.resolve_block(假分支, block_start(假分支, start), end);
+            }
+            Statement::循环语句 { 条件, 体, .. } => {
+                if let Some(condition) = 条件 {
+                    self.resolve_expression(condition, end);
+                }
+                self.resolve_loop_body(体, start, end);
+            }
+            Statement::当语句 { 条件, 体, .. } => {
+                self.resolve_expression(条件, end);
+                self.resolve_loop_body(体, start, end);
+            }
+            Statement::对于语句 { 变量, 值变量, 可迭代, 体, 位置 } => {
+                self.resolve_expression(可迭代, end);
+                self.enter(false, false, start, end);
+                self.declare(变量, SymbolKind::变量, Some(*位置), format!("让 {}", 变量));
+                if let Some(name) = 值变量 {
+                    self.declare(name, SymbolKind::变量, None, format!("让 {}", name));
+                }
+                self.loops += 1;
+                self.resolve_statements(体, end);
+                self.loops -= 1;
+                self.leave();
+            }
+            Statement::尝试语句 { 体, 捕获变量, 捕获, 最终, .. } => {
+                self.resolve_block(体, start, end);
+                if let Some(handler) = 捕获 {
+                    self.enter(false, false, block_start(handler, start), end);
+                    if let Some((name, span)) = 捕获变量 {
+                        self.declare(name, SymbolKind::变量, Some(*span), format!("让 {}", name));
+                    }
+                    self.resolve_statements(handler, end);
+                    self.leave();
+                }
+                if let Some(finally) = 最终 {
+                    self.resolve_block(finally, block_start(finally, start), end);
+                }
+            }
+            Statement::抛出语句 { 值, .. } => self.resolve_expression(值, end),
+            Statement::返回语句 { 值, 位置 } => {
+                if !self.inside(|scope| scope.function) {
+                    self.error(Diagnostic::error("E0207", "返回语句只能在函数中使用").with_span(*位置));
+                }
+                if let Some(expr) = 值 {
+                    self.resolve_expression(expr, end);
+                }
+            }
+            Statement::跳出语句 { 位置 } | Statement::继续语句 { 位置 } if self.loops == 0 => {
+                let keyword = if matches!(statement, Statement::跳出语句 { .. }) { "跳出" } else { "继续" };
+                self.error(Diagnostic::error("E0207", format!("{}语句只能在循环中使用", keyword)).with_span(*位置));
+            }
+            Statement::跳出语句 { .. }
+            | Statement::继续语句 { .. }
+            | Statement::错误 { .. }
+            | Statement::注释 { .. }
+            | Statement::空行 => {}
+        }
+    }
+    
+    /// `end` 是所在语句的下一条语句的位置，匿名函数的作用域到那里为止
+    fn resolve_expression(&mut self, expression: &'a Expression, end: Option<(usize, usize)>) {
+        match expression {
+            Expression::数字字面量(_) | Expression::字符串字面量(_) | Expression::布尔字面量(_) => {}
+            Expression::变量 { 名字, 位置, 槽位 } => self.use_name(名字, *位置, Access::Read, 槽位),
+            Expression::自己 { 位置 } => {
+                if !self.inside(|scope| scope.method) {
+                    self.error(Diagnostic::error("E0200", "自己 只能在结构的方法中使用").with_span(*位置));
+                }
+            }
+            Expression::二元运算 { 左, 右, .. } => {
+                self.resolve_expression(左, end);
+                self.resolve_expression(右, end);
+            }
+            Expression::一元运算 { 操作数, .. } => self.resolve_expression(操作数, end),
+            Expression::函数调用 { 函数, 参数, 位置 } => {
+                match 函数.as_ref() {
+                    Expression::变量 { 名字, 槽位, .. } => self.use_name(名字, *位置, Access::Call(参数.len()), 槽位),
+                    callee => {
+                        if let Some(kind) = literal_type(callee) {
+                            let error = Diagnostic::error("E0202", format!("{} 不是函数", callee))
+                                .with_span(*位置)
+                                .with_note(format!("{} 的值是{}", callee, kind));
+                            self.error(error);
+                        }
+                        self.resolve_expression(callee, end);
+                    }
+                }
+                for arg in 参数 {
+                    self.resolve_expression(arg, end);
+                }
+            }
+            Expression::匿名函数 { 参数, 参数类型, 体, 位置, .. } => {
+                self.resolve_function(参数, 参数类型, 体, false, *位置, end)
+            }
+            Expression::赋值 { 变量名, 值, 位置, 槽位 } => {
+                self.resolve_expression(值, end);
+                self.use_name(变量名, *位置, Access::Assign, 槽位);
+            }
+            Expression::索引赋值 { 数组, 索引, 值, .. } => {
+                self.resolve_expression(数组, end);
+                self.resolve_expression(索引, end);
+                self.resolve_expression(值, end);
+            }
+            Expression::数组字面量(元素) => {
+                for element in 元素 {
+                    self.resolve_expression(element, end);
+                }
+            }
+            Expression::数组索引 { 数组, 索引, .. } => {
+                self.resolve_expression(数组, end);
+                self.resolve_expression(索引, end);
+            }
+            Expression::范围 { 起, 止, .. } => {
+                self.resolve_expression(起, end);
+                self.resolve_expression(止, end);
+            }
+            Expression::成员访问 { 对象, .. } => self.resolve_expression(对象, end),
+            Expression::成员赋值 { 对象, 值, .. } => {
+                self.resolve_expression(对象, end);
+                self.resolve_expression(值, end);
+            }
+            Expression::方法调用 { 对象, 参数, .. } => {
+                self.resolve_expression(对象, end);
+                for arg in 参数 {
+                    self.resolve_expression(arg, end);
+                }
+            }
+            Expression::字典字面量 { 条目, .. } => {
+                for (key, value) in 条目 {
+                    self.resolve_expression(key, end);
+                    self.resolve_expression(value, end);
+                }
+            }
+        }
+    }
+}
+
+/// 字面量的值的类型，与运行时错误信息中的类型名称一致
+fn literal_type(expression: &Expression) -> Option<&'static str> {
+    match expression {
+        Expression::数字字面量(_) => Some("数字"),
+        Expression::字符串字面量(_) => Some("字符串"),
+        Expression::布尔字面量(_) => Some("布尔值"),
+        Expression::数组字面量(_) => Some("数组"),
+        Expression::字典字面量 { .. } => Some("字典"),
+        _ => None,
+    }
+}
+
+/// 否则、捕获和最终的代码块没有自己的位置，从其中第一条语句开始算
+fn block_start(statements: &[Statement], fallback: (usize, usize)) -> (usize, usize) {
+    statements
+        .iter()
+        .find_map(Statement::span)
+        .map_or(fallback, |span| (span.line, span.column))
+}
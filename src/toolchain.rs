@@ -0,0 +1,165 @@
+//! 本机的 C 编译器：把 C 后端生成的代码编译为可执行文件、动态库或目标文件
+//!
+//! 依次使用命令行 `--cc` 指定的编译器、`CC` 环境变量，最后在 PATH 中查找
+//! gcc、clang、tcc 和 cc。
+
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::code_generator::OutputType;
+
+/// 在 PATH 中按顺序查找的编译器
+const KNOWN_COMPILERS: [&str; 4] = ["gcc", "clang", "tcc", "cc"];
+
+#[derive(Debug, Clone)]
+pub struct Toolchain {
+    pub program: PathBuf,
+    /// 程序名之后的参数，例如 `CC="gcc -m32"` 中的 `-m32`
+    args: Vec<String>,
+    /// 优化级别，作为 `-O级别` 传给编译器
+    pub opt_level: String,
+    /// 生成调试信息 (`-g`)
+    pub debug: bool,
+}
+
+impl Toolchain {
+    /// `explicit` 是 `--cc` 指定的编译器，可以带参数
+    pub fn find(explicit: Option<&str>) -> Result<Self, String> {
+        if let Some(command) = explicit {
+            return Self::from_command(command, "--cc");
+        }
+        if let Some(command) = env::var("CC").ok().filter(|command| !command.trim().is_empty()) {
+            return Self::from_command(&command, "CC 环境变量");
+        }
+        
+        KNOWN_COMPILERS
+            .iter()
+            .find_map(|name| find_in_path(name))
+            .map(|program| Toolchain::new(program, Vec::new()))
+            .ok_or_else(|| {
+                format!(
+                    "找不到 C 编译器，请用 --cc 指定，设置 CC 环境变量，或把 {} 之一加入 PATH",
+                    KNOWN_COMPILERS.join("、")
+                )
+            })
+    }
+    
+    fn new(program: PathBuf, args: Vec<String>) -> Self {
+        Toolchain {
+            program,
+            args,
+            opt_level: "2".to_string(),
+            debug: false,
+        }
+    }
+    
+    /// 程序名可以是路径，也可以是 PATH 中的名字
+    fn from_command(command: &str, origin: &str) -> Result<Self, String> {
+        let mut words = command.split_whitespace().map(str::to_string);
+        let name = words.next().ok_or_else(|| format!("{} 中没有编译器", origin))?;
+        let program = if name.contains(['/', '\\']) {
+            Some(PathBuf::from(&name)).filter(|path| path.is_file())
+        } else {
+            find_in_path(&name)
+        };
+        let program = program.ok_or_else(|| format!("找不到 {} 指定的 C 编译器: {}", origin, name))?;
+        Ok(Toolchain::new(program, words.collect()))
+    }
+    
+    /// 把 `c_code` 写入临时目录中的 C 文件，再编译到 `output_path`；临时目录用完即删除
+    pub fn compile(&self, c_code: &str, output_type: &OutputType, output_path: &Path) -> Result<(), String> {
+        let temp = TempDir::new()?;
+        let c_file = temp.path.join("main.c");
+        fs::write(&c_file, c_code).map_err(|e| format!("写入临时文件失败: {}", e))?;
+        
+        let mut command = Command::new(&self.program);
+        command.args(&self.args).arg(format!("-O{}", self.opt_level));
+        if self.debug {
+            command.arg("-g");
+        }
+        match output_type {
+            OutputType::Dll => {
+                command.arg("-shared");
+                if !cfg!(windows) {
+                    command.arg("-fPIC");
+                }
+            }
+            OutputType::Object => {
+                command.arg("-c");
+            }
+            OutputType::Exe | OutputType::CSource => {}
+        }
+        command.arg("-o").arg(output_path).arg(&c_file);
+        
+        let output = command
+            .output()
+            .map_err(|e| format!("无法运行 C 编译器 {}: {}", self.program.display(), e))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let error = if stderr.is_empty() { stdout } else { stderr };
+            return Err(format!("{} 编译失败:\n{}", self.program.display(), error.trim_end()));
+        }
+        Ok(())
+    }
+}
+
+/// 在 PATH 的各个目录中查找程序，Windows 上补上 .exe
+fn find_in_path(name: &str) -> Option<PathBuf> {
+    let file = format!("{}{}", name, env::consts::EXE_SUFFIX);
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(&file))
+        .find(|candidate| candidate.is_file())
+}
+
+/// 每次编译独占的临时目录，离开作用域时删除
+struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// 名字带随机部分，不能事先猜到；目录必须由这次调用新建，已经存在时换一个名字重试
+    fn new() -> Result<Self, String> {
+        const ATTEMPTS: usize = 16;
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        for _ in 0..ATTEMPTS {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_usize(NEXT.fetch_add(1, Ordering::Relaxed));
+            if let Ok(elapsed) = SystemTime::now().duration_since(UNIX_EPOCH) {
+                hasher.write_u128(elapsed.as_nanos());
+            }
+            let path = env::temp_dir().join(format!("cnlang-{}-{:016x}", process::id(), hasher.finish()));
+            match create_private_dir(&path) {
+                Ok(()) => return Ok(TempDir { path }),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(format!("无法创建临时目录 {}: {}", path.display(), e)),
+            }
+        }
+        Err(format!("无法在 {} 中创建临时目录: 尝试的 {} 个名字都已存在", env::temp_dir().display(), ATTEMPTS))
+    }
+}
+
+/// 新建只有当前用户能访问的目录
+#[cfg(unix)]
+fn create_private_dir(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    fs::DirBuilder::new().mode(0o700).create(path)
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(path: &Path) -> io::Result<()> {
+    fs::create_dir(path)
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
@@ -0,0 +1,357 @@
+//! 类型检查：按类型标注检查变量、参数和返回值
+//!
+//! 类型是渐进的：没有标注的变量和参数的类型未知，只检查类型能够确定的地方。
+//! 表达式的类型来自字面量、运算的结果、带标注的名字，以及值没有变过的函数的返回类型。
+//! 报告的错误与运行时相同：运算的类型错误是 E0204，与标注不符是 E0217。
+
+use std::collections::HashMap;
+
+use crate::ast::{Expression, Program, Statement, Type};
+use crate::diagnostic::{Diagnostic, Span};
+use crate::resolver::{Resolution, SymbolKind};
+
+/// 检查整个程序，`resolution` 是同一个程序的名字解析结果
+pub fn check(program: &Program, resolution: &Resolution) -> Vec<Diagnostic> {
+    let mut checker = TypeChecker {
+        resolution,
+        symbols: resolution
+            .references
+            .iter()
+            .map(|reference| ((reference.span.line, reference.span.column), reference.symbol))
+            .collect(),
+        annotations: annotations(resolution),
+        returns: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+    checker.check_statements(&program.语句);
+    checker.diagnostics
+}
+
+/// 每个名字可以确定的类型标注。同一作用域中重复声明的名字共用一个绑定，
+/// 只有所有声明的标注都相同时才能确定
+fn annotations(resolution: &Resolution) -> Vec<Option<Type>> {
+    let mut bindings: HashMap<(usize, usize), Option<Option<Type>>> = HashMap::new();
+    for symbol in &resolution.symbols {
+        let binding = bindings.entry((symbol.scope, symbol.slot)).or_insert(Some(symbol.annotation));
+        if *binding != Some(symbol.annotation) {
+            *binding = None;
+        }
+    }
+    resolution
+        .symbols
+        .iter()
+        .map(|symbol| bindings[&(symbol.scope, symbol.slot)].flatten())
+        .collect()
+}
+
+/// 类型不符的错误，`what` 是被检查的东西，例如 `变量 年龄`
+pub fn mismatch(what: &str, expected: Type, actual: &str) -> Diagnostic {
+    Diagnostic::error("E0217", format!("类型不匹配: {} 应该是 {}，得到 {}", what, expected, actual))
+}
+
+struct TypeChecker<'a> {
+    resolution: &'a Resolution,
+    /// 名字出现的位置 (行, 列) 对应的声明
+    symbols: HashMap<(usize, usize), usize>,
+    annotations: Vec<Option<Type>>,
+    /// 包围着的函数的返回类型，最外层不在函数中
+    returns: Vec<Option<Type>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl TypeChecker<'_> {
+    fn check_statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.check_statement(statement);
+        }
+    }
+    
+    fn check_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::表达式语句(expr) => {
+                self.type_of(expr);
+            }
+            Statement::变量声明 { 名字, 类型, 初始值, 是常量, 位置, .. } => {
+                let (actual, span) = match 初始值 {
+                    Some(expr) => (self.type_of(expr), expr.span().unwrap_or(*位置)),
+                    None => (Some(Type::空), *位置),
+                };
+                if let (Some(expected), Some(actual)) = (*类型, actual) {
+                    if expected != actual {
+                        let kind = if *是常量 { "常量" } else { "变量" };
+                        let mut error = mismatch(&format!("{} {}", kind, 名字), expected, actual.name()).with_span(span);
+                        if span != *位置 {
+                            error = error.with_label(*位置, "类型在这里声明");
+                        }
+                        if 初始值.is_none() {
+                            error = error.with_note("没有初始值的变量的值是 空");
+                        }
+                        self.diagnostics.push(error);
+                    }
+                }
+            }
+            Statement::函数声明 { 返回类型, 体, 位置, .. } => self.check_function(*返回类型, 体, *位置),
+            Statement::结构声明 { 方法, .. } => {
+                for method in 方法 {
+                    if let Statement::函数声明 { 返回类型, 体, 位置, .. } = method {
+                        self.check_function(*返回类型, 体, *位置);
+                    }
+                }
+            }
+            Statement::如果语句 { 条件, 真分支, 假分支, .. } => {
+                self.type_of(条件);
+                self.check_statements(真分支);
+                self.check_statements(假分支);
+            }
+            Statement::循环语句 { 条件, 体, .. } => {
+                if let Some(condition) = 条件 {
+                    self.type_of(condition);
+                }
+                self.check_statements(体);
+            }
+            Statement::当语句 { 条件, 体, .. } => {
+                self.type_of(条件);
+                self.check_statements(体);
+            }
+            Statement::对于语句 { 可迭代, 体, .. } => {
+                self.type_of(可迭代);
+                self.check_statements(体);
+            }
+            Statement::尝试语句 { 体, 捕获, 最终, .. } => {
+                self.check_statements(体);
+                if let Some(handler) = 捕获 {
+                    self.check_statements(handler);
+                }
+                if let Some(finally) = 最终 {
+                    self.check_statements(finally);
+                }
+            }
+            Statement::抛出语句 { 值, .. } => {
+                self.type_of(值);
+            }
+            Statement::返回语句 { 值, 位置 } => {
+                let (actual, span) = match 值 {
+                    Some(expr) => (self.type_of(expr), expr.span().unwrap_or(*位置)),
+                    None => (Some(Type::空), *位置),
+                };
+                if let (Some(Some(expected)), Some(actual)) = (self.returns.last(), actual) {
+                    if *expected != actual {
+                        self.diagnostics.push(mismatch("返回值", *expected, actual.name()).with_span(span));
+                    }
+                }
+            }
+            Statement::导入语句 { .. }
+            | Statement::跳出语句 { .. }
+            | Statement::继续语句 { .. }
+            | Statement::错误 { .. }
+            | Statement::注释 { .. }
+            | Statement::空行 => {}
+        }
+    }
+    
+    /// 有返回类型的函数执行到结尾时返回 空，返回类型不是 空 时报告在函数名上
+    fn check_function(&mut self, returns: Option<Type>, body: &[Statement], span: Span) {
+        self.returns.push(returns);
+        self.check_statements(body);
+        self.returns.pop();
+        if let Some(expected) = returns {
+            if expected != Type::空 && !always_exits(body) {
+                let error = mismatch("返回值", expected, Type::空.name())
+                    .with_span(span)
+                    .with_note("函数执行到结尾时没有返回值");
+                self.diagnostics.push(error);
+            }
+        }
+    }
+    
+    /// 在位置上使用的名字对应的声明
+    fn symbol(&self, span: Span) -> Option<usize> {
+        self.symbols.get(&(span.line, span.column)).copied()
+    }
+    
+    /// 检查表达式，返回能够确定的类型
+    fn type_of(&mut self, expression: &Expression) -> Option<Type> {
+        match expression {
+            Expression::数字字面量(_) => Some(Type::数字),
+            Expression::字符串字面量(_) => Some(Type::字符串),
+            Expression::布尔字面量(_) => Some(Type::布尔值),
+            Expression::变量 { 位置, .. } => self.symbol(*位置).and_then(|symbol| self.annotations[symbol]),
+            Expression::自己 { .. } => None,
+            Expression::二元运算 { 左, 运算符, 右, 位置 } => {
+                let left = self.type_of(左);
+                let right = self.type_of(右);
+                self.binary(运算符, left, right, *位置)
+            }
+            Expression::一元运算 { 操作数, .. } => {
+                self.type_of(操作数);
+                Some(Type::布尔值)
+            }
+            Expression::函数调用 { 函数, 参数, 位置 } => {
+                let arguments: Vec<Option<Type>> = 参数.iter().map(|arg| self.type_of(arg)).collect();
+                match 函数.as_ref() {
+                    Expression::变量 { 名字, .. } => self.call(名字, &arguments, 参数, *位置),
+                    callee => {
+                        self.type_of(callee);
+                        None
+                    }
+                }
+            }
+            Expression::匿名函数 { 返回类型, 体, 位置, .. } => {
+                self.check_function(*返回类型, 体, *位置);
+                None
+            }
+            Expression::赋值 { 变量名, 值, 位置, .. } => {
+                let actual = self.type_of(值);
+                let symbol = self.symbol(*位置);
+                if let (Some(symbol), Some(actual)) = (symbol, actual) {
+                    if let Some(expected) = self.annotations[symbol].filter(|expected| *expected != actual) {
+                        let mut error = mismatch(&format!("变量 {}", 变量名), expected, actual.name()).with_span(*位置);
+                        if let Some(declared_at) = self.resolution.symbols[symbol].span {
+                            error = error.with_label(declared_at, "类型在这里声明");
+                        }
+                        self.diagnostics.push(error);
+                    }
+                }
+                actual
+            }
+            Expression::索引赋值 { 数组, 索引, 值, .. } => {
+                self.type_of(数组);
+                self.type_of(索引);
+                self.type_of(值)
+            }
+            Expression::数组字面量(元素) => {
+                for element in 元素 {
+                    self.type_of(element);
+                }
+                Some(Type::数组)
+            }
+            Expression::范围 { 起, 止, .. } => {
+                self.type_of(起);
+                self.type_of(止);
+                Some(Type::数组)
+            }
+            Expression::数组索引 { 数组, 索引, .. } => {
+                self.type_of(数组);
+                self.type_of(索引);
+                None
+            }
+            Expression::字典字面量 { 条目, .. } => {
+                for (key, value) in 条目 {
+                    self.type_of(key);
+                    self.type_of(value);
+                }
+                Some(Type::字典)
+            }
+            Expression::成员访问 { 对象, .. } => {
+                self.type_of(对象);
+                None
+            }
+            Expression::成员赋值 { 对象, 值, .. } => {
+                self.type_of(对象);
+                self.type_of(值)
+            }
+            Expression::方法调用 { 对象, 参数, .. } => {
+                self.type_of(对象);
+                for arg in 参数 {
+                    self.type_of(arg);
+                }
+                None
+            }
+        }
+    }
+    
+    /// 二元运算结果的类型；操作数的类型能确定运算一定出错时报告错误，信息与运行时相同
+    fn binary(&mut self, operator: &str, left: Option<Type>, right: Option<Type>, span: Span) -> Option<Type> {
+        let is_number = |ty: Option<Type>| ty.is_none_or(|ty| ty == Type::数字);
+        let (result, valid, message) = match operator {
+            "&&" | "||" | "==" | "!=" => return Some(Type::布尔值),
+            // 只要有一边是字符串，另一边是什么都可以相加
+            "+" => match (left, right) {
+                (Some(Type::字符串), _) | (_, Some(Type::字符串)) => (Some(Type::字符串), true, "无法相加"),
+                (Some(Type::数字), Some(Type::数字)) => (Some(Type::数字), true, "无法相加"),
+                (Some(_), Some(_)) => (None, false, "无法相加"),
+                _ => (None, true, "无法相加"),
+            },
+            "-" => (Some(Type::数字), is_number(left) && is_number(right), "无法相减"),
+            "*" => (Some(Type::数字), is_number(left) && is_number(right), "无法相乘"),
+            "/" => (Some(Type::数字), is_number(left) && is_number(right), "无法相除"),
+            _ => (Some(Type::布尔值), is_number(left) && is_number(right), "无法比较"),
+        };
+        if !valid {
+            let note = match (left, right) {
+                (Some(left), Some(right)) => format!("左边是{}，右边是{}", left, right),
+                (Some(left), None) => format!("左边是{}", left),
+                (None, Some(right)) => format!("右边是{}", right),
+                (None, None) => unreachable!("类型都未知时不会出错"),
+            };
+            let error = Diagnostic::error("E0204", format!("类型不匹配: {}", message)).with_span(span).with_note(note);
+            self.diagnostics.push(error);
+        }
+        result
+    }
+    
+    /// 调用名字对应的函数：检查值没有变过的函数的参数类型，返回它的返回类型
+    fn call(&mut self, name: &str, arguments: &[Option<Type>], expressions: &[Expression], span: Span) -> Option<Type> {
+        let symbol = &self.resolution.symbols[self.symbol(span)?];
+        if symbol.changed {
+            return None;
+        }
+        match symbol.kind {
+            SymbolKind::内置函数 => {
+                return match name {
+                    "输入" => Some(Type::字符串),
+                    "范围" => Some(Type::数组),
+                    _ => Some(Type::空),
+                };
+            }
+            SymbolKind::函数 | SymbolKind::变量 | SymbolKind::常量 => {}
+            _ => return None,
+        }
+        // 参数数量不对时名字解析已经报告了错误
+        if symbol.parameter_types.len() == arguments.len() {
+            let parameters = symbol.parameters.iter().flatten();
+            for (i, ((expected, actual), parameter)) in
+                symbol.parameter_types.iter().zip(arguments).zip(parameters).enumerate()
+            {
+                if let (Some(expected), Some(actual)) = (*expected, *actual) {
+                    if expected != actual {
+                        let mut error = mismatch(&format!("参数 {}", parameter), expected, actual.name())
+                            .with_span(expressions[i].span().unwrap_or(span));
+                        if let Some(declared_at) = symbol.span {
+                            error = error.with_label(declared_at, format!("{} 在这里声明", name));
+                        }
+                        self.diagnostics.push(error);
+                    }
+                }
+            }
+        }
+        symbol.returns
+    }
+}
+
+/// 语句执行完之后一定不会继续往下执行：返回、抛出，或者所有分支都是这样
+pub fn always_exits(statements: &[Statement]) -> bool {
+    statements.iter().any(|statement| match statement {
+        Statement::返回语句 { .. } | Statement::抛出语句 { .. } => true,
+        Statement::如果语句 { 真分支, 假分支, .. } => always_exits(真分支) && always_exits(假分支),
+        Statement::尝试语句 { 体, 捕获, 最终, .. } => {
+            最终.as_deref().is_some_and(always_exits)
+                || always_exits(体) && 捕获.as_deref().is_none_or(always_exits)
+        }
+        // 没有条件的循环只能从 跳出 离开
+        Statement::循环语句 { 条件: None, 体, .. } => !breaks(体),
+        _ => false,
+    })
+}
+
+/// 语句中有跳出这一层循环的 `跳出`，不算内层循环和函数中的
+fn breaks(statements: &[Statement]) -> bool {
+    statements.iter().any(|statement| match statement {
+        Statement::跳出语句 { .. } => true,
+        Statement::如果语句 { 真分支, 假分支, .. } => breaks(真分支) || breaks(假分支),
+        Statement::尝试语句 { 体, 捕获, 最终, .. } => {
+            breaks(体) || 捕获.as_deref().is_some_and(breaks) || 最终.as_deref().is_some_and(breaks)
+        }
+        _ => false,
+    })
+}
@@ -0,0 +1,73 @@
+//! 数组的索引赋值和原地修改
+
+mod common;
+
+use common::{assert_error, assert_output};
+
+#[test]
+fn indexed_assignment() {
+    assert_output(
+        "array_index_assign",
+        "变量 列表 = [1, 2, 3]
+列表[0] = 10
+打印(列表)
+变量 矩阵 = [[1, 2], [3, 4]]
+矩阵[1][0] = 30
+打印(矩阵)
+",
+        "[10, 2, 3]\n[[1, 2], [30, 4]]\n",
+    );
+}
+
+#[test]
+fn add_and_remove_mutate_in_place() {
+    assert_output(
+        "array_mutate",
+        "变量 列表 = [1, 2, 3]
+列表.添加(4)
+打印(列表)
+打印(列表.删除(0))
+打印(列表)
+",
+        "[1, 2, 3, 4]\n1\n[2, 3, 4]\n",
+    );
+}
+
+#[test]
+fn arrays_are_shared_by_reference() {
+    assert_output(
+        "array_reference",
+        "变量 原来 = [1, 2]
+变量 别名 = 原来
+别名[0] = 5
+打印(原来)
+函数 填充(数组) {
+    数组.添加(99)
+}
+填充(原来)
+打印(别名)
+打印(原来 == 别名)
+打印(原来 == [5, 2, 99])
+",
+        "[5, 2]\n[5, 2, 99]\n真\n假\n",
+    );
+}
+
+#[test]
+fn constant_array_contents_can_change() {
+    assert_output("array_constant", "常量 列表 = [1]\n列表[0] = 2\n打印(列表)\n", "[2]\n");
+    assert_error("array_constant_rebind", "常量 列表 = [1]\n列表 = [2]\n", "E0210");
+}
+
+#[test]
+fn self_containing_array_prints() {
+    assert_output("array_cycle", "变量 a = [1]\na.添加(a)\n打印(a)\n", "[1, [...]]\n");
+}
+
+#[test]
+fn index_errors() {
+    assert_error("array_out_of_bounds", "变量 a = [1]\na[1] = 2\n", "E0206");
+    assert_error("array_negative", "变量 a = [1]\n打印(a[-1])\n", "E0206");
+    assert_error("array_not_array", "变量 a = 1\na[0] = 2\n", "E0204");
+    assert_error("array_bad_target", "变量 a = [1]\na.长度() = 2\n", "E0103");
+}
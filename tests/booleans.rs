@@ -0,0 +1,40 @@
+//! 布尔值的字面量、打印、比较和字符串拼接
+
+mod common;
+
+use common::{assert_error, assert_output};
+
+#[test]
+fn booleans_print_as_chinese() {
+    assert_output("bool_print", "打印(真)\n打印(假)\n打印(1 < 2)\n打印([真, 假])\n", "真\n假\n真\n[真, 假]\n");
+}
+
+#[test]
+fn booleans_are_not_numbers() {
+    assert_output("bool_not_number", "打印(真 == 1)\n打印(假 == 0)\n打印(真 == 真)\n打印(真 != 假)\n", "假\n假\n真\n真\n");
+    assert_error("bool_arithmetic", "打印(真 + 1)\n", "E0204");
+}
+
+#[test]
+fn booleans_concatenate_with_strings() {
+    assert_output("bool_concat", "打印(\"结果: \" + 真)\n打印(假 + \"!\")\n", "\"结果: 真\"\n\"假!\"\n");
+}
+
+#[test]
+fn booleans_drive_conditions() {
+    assert_output(
+        "bool_condition",
+        "变量 继续吗 = 真
+变量 次数 = 0
+循环 (继续吗) {
+    次数 = 次数 + 1
+    如果 (次数 == 3) {
+        继续吗 = 假
+    }
+}
+打印(次数)
+打印(!真)
+",
+        "3\n假\n",
+    );
+}
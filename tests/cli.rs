@@ -0,0 +1,224 @@
+//! 命令行子命令和退出码
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+/// 把 `source` 写入临时文件，再用 `args` 和文件路径运行 cnlang
+fn cnlang(name: &str, args: &[&str], source: &str) -> Output {
+    let path: PathBuf = env::temp_dir().join(format!("cnlang_cli_{}_{}.cn", name, std::process::id()));
+    fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_cnlang")).args(args).arg(&path).output().unwrap();
+    fs::remove_file(&path).unwrap();
+    output
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+#[test]
+fn run_and_exit_codes() {
+    let output = cnlang("run", &["run"], "打印(1)\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "1\n程序执行完成\n");
+    
+    // 不写子命令和用中文别名都可以运行
+    assert_eq!(stdout(&cnlang("run_file", &[], "打印(2)\n")), "2\n程序执行完成\n");
+    assert_eq!(stdout(&cnlang("run_alias", &["运行"], "打印(3)\n")), "3\n程序执行完成\n");
+    
+    let output = cnlang("runtime_error", &["run"], "打印(1)\n打印(1 / 0)\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "1\n");
+    assert!(stderr(&output).contains("错误[E0205]"), "{}", stderr(&output));
+    
+    let output = cnlang("syntax_error", &["run"], "让 = 1\n");
+    assert_eq!(output.status.code(), Some(1));
+    
+    let output = Command::new(env!("CARGO_BIN_EXE_cnlang")).args(["run", "没有的文件.cn"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("无法读取文件 没有的文件.cn"), "{}", stderr(&output));
+    
+    // 用法错误由 clap 报告，退出码为 2
+    let output = cnlang("bad_flag", &["build", "--没有的选项"], "");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn check_does_not_run() {
+    let output = cnlang("check", &["check"], "打印(\"不会执行\")\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).starts_with("检查通过: "), "{}", stdout(&output));
+    
+    let output = cnlang("check_error", &["检查"], "常量 a = 1\na = 2\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("[E0210]"), "{}", stderr(&output));
+    
+    let output = cnlang("check_import", &["check"], "导入 没有的模块\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("[E0214]"), "{}", stderr(&output));
+}
+
+#[test]
+fn repl_flag_still_works() {
+    // 子命令出现之前的 `cnlang --repl` 写法
+    let mut child = Command::new(env!("CARGO_BIN_EXE_cnlang"))
+        .arg("--repl")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all("打印(1 + 1)\n:退出\n".as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).contains("中文> 2\n"), "{}", stdout(&output));
+    
+    let output = cnlang("repl_with_file", &["--repl"], "打印(1)\n");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn errors_underline_the_span() {
+    // 中文字符占两列，下划线仍然对准出错的名字
+    let output = cnlang("caret_cjk", &["check"], "让 名字 = \"小明\"\n打印(名字 + 没有)\n");
+    let message = stderr(&output);
+    assert!(message.contains("\n2 | 打印(名字 + 没有)\n  |             ^^^^\n"), "{}", message);
+    
+    // 次要位置和主要位置在同一行时，这一行只显示一次
+    let output = cnlang("caret_same_line", &["check"], "函数 加(a) { 返回 a } 打印(加(1, 2))\n");
+    let message = stderr(&output);
+    assert!(
+        message.contains("\n1 | 函数 加(a) { 返回 a } 打印(加(1, 2))\n  |      --                    ^^\n  |      在这里声明\n"),
+        "{}",
+        message
+    );
+    assert_eq!(message.matches("1 | 函数").count(), 1, "{}", message);
+}
+
+#[test]
+fn ast_and_tokens() {
+    let output = cnlang("ast", &["ast"], "让 x = 1 + 2 * 3\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "让 x = (1 + (2 * 3))\n");
+    
+    // 条件中的字典字面量保留括号，输出仍然能解析
+    let output = cnlang("ast_dict", &["ast"], "如果 ({} == {}) {\n    打印(1)\n}\n对于 k 在 ({}.键()) {}\n");
+    assert_eq!(stdout(&output), "如果 ({} == {}) {\n  打印(1)\n}\n对于 k 在 ({}.键()) {\n}\n");
+    assert_eq!(cnlang("ast_dict_check", &["check"], &stdout(&output)).status.code(), Some(0));
+    
+    let output = cnlang("tokens", &["tokens"], "让 x = 1\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "1:1\t让\n1:3\t标识符(\"x\")\n1:5\t赋值\n1:7\t数字(1.0)\n2:1\t文件结束\n");
+    
+    let output = cnlang("tokens_error", &["tokens"], "让 x = \"没有结束\n");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn build_c_source() {
+    let dir = env::temp_dir().join(format!("cnlang_cli_build_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("程序.cn"), "打印(1)\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_cnlang"))
+        .current_dir(&dir)
+        .args(["build", "--target", "c", "程序.cn"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(fs::read_to_string(dir.join("程序.c")).unwrap().contains("int main"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn build_global_constants() {
+    let dir = env::temp_dir().join(format!("cnlang_cli_constants_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let source = "常量 上限: 数字 = 10\n常量 N = -1\n常量 两倍 = N * 2\n让 x = 1\n让 x = 2\n打印(上限 + N + 两倍 + x)\n";
+    fs::write(dir.join("程序.cn"), source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_cnlang"))
+        .current_dir(&dir)
+        .args(["build", "--target", "c", "程序.cn"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    let code = fs::read_to_string(dir.join("程序.c")).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    // 初始值是字面量的标量常量是 C 的 const；其余的全局变量在 main 中赋值，重新声明的变量只定义一次
+    assert!(code.contains("const double global_上限 = 10.0;"), "{}", code);
+    assert!(code.contains("const Value global_N = {VALUE_NUMBER, .as.number = -1.0};"), "{}", code);
+    assert!(code.contains("Value global_两倍 = {VALUE_NULL};"), "{}", code);
+    assert_eq!(code.matches("Value global_x = ").count(), 1, "{}", code);
+    
+    let output = cnlang("build_redeclared", &["build", "--target", "c"], "让 y: 数字 = 1\n让 y: 字符串 = \"a\"\n打印(y)\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("错误[E0301]: C 后端不支持用不同的类型标注重新声明全局变量 y"), "{}", stderr(&output));
+    assert!(stderr(&output).contains(":2:3"), "{}", stderr(&output));
+}
+
+#[test]
+fn build_reports_functions_as_values() {
+    // C 后端中函数不是值，在生成 C 代码之前就报错
+    let source = "函数 应用(f, x) {\n    返回 f(x)\n}\n让 双倍 = 函数(x) { 返回 x * 2 }\n打印(应用(双倍, 1))\n";
+    let output = cnlang("build_closure", &["build", "--target", "c"], source);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = stderr(&output);
+    assert!(stderr.contains("错误[E0301]: C 后端只能调用具名函数，f 是一个变量"), "{}", stderr);
+    assert!(stderr.contains("错误[E0301]: C 后端不支持匿名函数"), "{}", stderr);
+    assert!(stderr.contains(":4:8"), "{}", stderr);
+}
+
+#[test]
+fn build_reports_structs() {
+    let source = "结构 点 {\n    x, y\n    函数 和() { 返回 自己.x + 自己.y }\n}\n让 p = 点(1, 2)\np.x = 3\n打印(p.和())\n打印([1].长度())\n";
+    let output = cnlang("build_struct", &["build", "--target", "c"], source);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = stderr(&output);
+    assert!(stderr.contains("错误[E0301]: C 后端不支持结构，不能声明结构 点"), "{}", stderr);
+    assert!(stderr.contains("错误[E0301]: C 后端不支持结构，不能给成员 x 赋值"), "{}", stderr);
+    assert!(stderr.contains("错误[E0301]: C 后端不支持结构，只能调用数组和字典的内置方法，不能调用 和"), "{}", stderr);
+    assert_eq!(stderr.matches("错误[").count(), 3, "{}", stderr);
+}
+
+#[test]
+fn json_syntax_tree_runs() {
+    let source = "结构 点 { x, y\n    函数 和() { 返回 自己.x + 自己.y }\n}\n让 p = 点(1, 2)\n对于 (i, v) 在 [\"a\", \"b\"] { 打印(i + v) }\n尝试 { 抛出 \"坏\" } 捕获 (e) { 打印(e) }\n打印(p.和())\n打印(1 / 0)\n";
+    let output = cnlang("json_ast", &["ast", "--json"], source);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    let json = stdout(&output);
+    assert!(json.contains("\"结构声明\""), "{}", json);
+    
+    let output = cnlang("json_run", &["run", "--json"], &json);
+    let expected = cnlang("json_source", &["run"], source);
+    assert_eq!(stdout(&output), stdout(&expected));
+    assert_eq!(output.status.code(), Some(1));
+    // 位置仍然指向原来的源代码
+    assert!(stderr(&output).contains("错误[E0205]"), "{}", stderr(&output));
+    assert!(stderr(&output).contains(":8:6"), "{}", stderr(&output));
+    
+    let output = cnlang("json_invalid", &["run", "--json"], "{\"语句\": 1}");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("[E0108]"), "{}", stderr(&output));
+}
+
+#[test]
+fn json_tokens() {
+    let output = cnlang("json_tokens", &["tokens", "--json"], "让 x = \"好\"\n");
+    assert_eq!(output.status.code(), Some(0));
+    let json: String = stdout(&output).split_whitespace().collect();
+    assert_eq!(
+        json,
+        concat!(
+            "[{\"token_type\":\"让\",\"line\":1,\"column\":1,\"length\":1},",
+            "{\"token_type\":{\"标识符\":\"x\"},\"line\":1,\"column\":3,\"length\":1},",
+            "{\"token_type\":\"赋值\",\"line\":1,\"column\":5,\"length\":1},",
+            "{\"token_type\":{\"字符串\":\"好\"},\"line\":1,\"column\":7,\"length\":3},",
+            "{\"token_type\":\"文件结束\",\"line\":2,\"column\":1,\"length\":1}]"
+        )
+    );
+}
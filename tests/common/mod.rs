@@ -0,0 +1,101 @@
+//! 集成测试共用的辅助函数：把源代码写入临时文件，再用解释器或 C 后端运行
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// 运行一段程序，返回标准输出中程序打印的内容和标准错误
+pub fn run(name: &str, source: &str) -> (String, String) {
+    let path: PathBuf = std::env::temp_dir().join(format!("cnlang_test_{}_{}.cn", name, std::process::id()));
+    fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_cnlang")).arg(&path).output().unwrap();
+    fs::remove_file(&path).unwrap();
+    
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stdout = stdout.strip_suffix("程序执行完成\n").unwrap_or(&stdout).to_string();
+    (stdout, String::from_utf8(output.stderr).unwrap())
+}
+
+/// 把多个文件写入同一个临时目录，运行第一个文件；文件名可以带子目录
+pub fn run_files(name: &str, files: &[(&str, &str)]) -> (String, String) {
+    let dir: PathBuf = std::env::temp_dir().join(format!("cnlang_test_{}_{}", name, std::process::id()));
+    for (file, source) in files {
+        let path = dir.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, source).unwrap();
+    }
+    let output = Command::new(env!("CARGO_BIN_EXE_cnlang")).arg(dir.join(files[0].0)).output().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stdout = stdout.strip_suffix("程序执行完成\n").unwrap_or(&stdout).to_string();
+    (stdout, String::from_utf8(output.stderr).unwrap())
+}
+
+/// 用 `cnlang fmt` 格式化一段程序，返回格式化后的源代码
+pub fn format(name: &str, source: &str) -> String {
+    let path: PathBuf = std::env::temp_dir().join(format!("cnlang_fmt_{}_{}.cn", name, std::process::id()));
+    fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_cnlang")).arg("fmt").arg(&path).output().unwrap();
+    let formatted = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    
+    assert!(output.status.success(), "格式化失败:\n{}", String::from_utf8_lossy(&output.stderr));
+    formatted
+}
+
+pub fn assert_output(name: &str, source: &str, expected: &str) {
+    let (stdout, stderr) = run(name, source);
+    assert_eq!(stderr, "", "程序不应报错");
+    assert_eq!(stdout, expected);
+}
+
+pub fn assert_error(name: &str, source: &str, code: &str) {
+    let (_, stderr) = run(name, source);
+    assert!(stderr.contains(&format!("[{}]", code)), "期望错误 {}，实际输出:\n{}", code, stderr);
+}
+
+/// 编译 C 程序时依次使用的优化级别：-O0，以及 `cnlang build` 默认的级别；
+/// setjmp/longjmp 等问题只在开启优化后出现
+pub const OPT_LEVELS: [Option<&str>; 2] = [Some("0"), None];
+
+/// 用 C 后端和本机的 C 编译器（`CC`，默认 `cc`）编译后运行，`opt_level` 为 None 时使用默认的优化级别；
+/// 没有 C 编译器时返回 None
+pub fn run_compiled(name: &str, source: &str, opt_level: Option<&str>) -> Option<(String, String)> {
+    let compiler = c_compiler()?;
+    let dir: PathBuf = std::env::temp_dir().join(format!("cnlang_c_test_{}_{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (source_path, exe_path) = (dir.join("main.cn"), dir.join("main"));
+    fs::write(&source_path, source).unwrap();
+    
+    let mut command = Command::new(env!("CARGO_BIN_EXE_cnlang"));
+    command.arg("build").arg(&source_path).args(["--cc", &compiler, "--output"]).arg(&exe_path);
+    if let Some(level) = opt_level {
+        command.args(["--opt-level", level]);
+    }
+    let compiled = command.output().unwrap();
+    assert!(exe_path.exists(), "编译失败:\n{}", String::from_utf8_lossy(&compiled.stderr));
+    let output = Command::new(&exe_path).output().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    
+    Some((String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap()))
+}
+
+/// 测试使用的 C 编译器，找不到时返回 None
+pub fn c_compiler() -> Option<String> {
+    let compiler = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    Command::new(&compiler).arg("--version").output().ok().map(|_| compiler)
+}
+
+/// 解释器和每个优化级别编译出的 C 程序都应输出 `expected`
+pub fn assert_same_output(name: &str, source: &str, expected: &str) {
+    assert_output(name, source, expected);
+    for opt_level in OPT_LEVELS {
+        if let Some((stdout, stderr)) = run_compiled(name, source, opt_level) {
+            let level = opt_level.unwrap_or("默认");
+            assert_eq!(stderr, "", "C 程序不应报错（优化级别 {}）", level);
+            assert_eq!(stdout, expected, "C 后端的输出与解释器不同（优化级别 {}）", level);
+        }
+    }
+}
@@ -0,0 +1,68 @@
+//! 常量：运行前报告给常量赋值和重复声明，C 后端把标量常量声明为 const
+
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+use common::{assert_same_output, run};
+
+#[test]
+fn assigning_a_constant_is_reported_before_running() {
+    let (stdout, stderr) = run("const_assign", "打印(\"开始\")\n常量 上限 = 10\n上限 = 11\n");
+    assert_eq!(stdout, "", "有静态错误的程序不应运行");
+    assert!(stderr.contains("错误[E0210]: 不能给常量 上限 赋值"), "{}", stderr);
+    assert!(stderr.contains(":3:1"), "{}", stderr);
+    assert!(stderr.contains("常量在这里声明"), "{}", stderr);
+    
+    // 函数中给外层的常量赋值同样报错
+    let (stdout, stderr) = run("const_assign_in_function", "常量 上限 = 10\n函数 改() {\n    上限 = 1\n}\n打印(上限)\n");
+    assert_eq!(stdout, "");
+    assert!(stderr.contains("[E0210]") && stderr.contains(":3:5"), "{}", stderr);
+}
+
+#[test]
+fn redeclaring_a_constant_is_reported() {
+    let (stdout, stderr) = run("const_redeclare", "打印(\"开始\")\n常量 上限 = 10\n常量 上限 = 11\n");
+    assert_eq!(stdout, "");
+    assert!(stderr.contains("错误[E0211]: 常量 上限 不能重复声明"), "{}", stderr);
+    assert!(stderr.contains(":3:4"), "{}", stderr);
+    
+    let (stdout, stderr) = run("const_without_value", "常量 空值\n打印(空值)\n");
+    assert_eq!(stdout, "空\n");
+    assert!(stderr.contains("警告[W0101]: 常量 空值 没有初始值，它的值将永远是 空"), "{}", stderr);
+}
+
+#[test]
+fn scalar_constants_are_const_in_c() {
+    let source = "函数 f() {
+    常量 n: 数字 = 2
+    常量 m = n + 1
+    常量 名字: 字符串 = \"字\"
+    常量 列表 = [1]
+    打印(n + m)
+    打印(名字)
+    打印(列表)
+}
+f()
+";
+    assert_same_output("const_c", source, "5\n\"字\"\n[1]\n");
+    
+    let dir = std::env::temp_dir().join(format!("cnlang_const_c_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("main.cn"), source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_cnlang"))
+        .current_dir(&dir)
+        .args(["build", "--target", "c", "main.cn"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let code = fs::read_to_string(dir.join("main.c")).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    
+    assert!(code.contains("const double n = 2.0;"), "{}", code);
+    assert!(code.contains("const Value m = "), "{}", code);
+    // 字符串常量要能传给参数是 char* 的函数，数组常量还要填充元素，它们都不是 const
+    assert!(code.contains("char* 名字 = \"字\";") && !code.contains("const char* 名字"), "{}", code);
+    assert!(code.contains("Value 列表 = create_array();"), "{}", code);
+}
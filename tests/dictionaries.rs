@@ -0,0 +1,100 @@
+//! 字典字面量、按键访问、方法和遍历
+
+mod common;
+
+use common::{assert_error, assert_output, run};
+
+#[test]
+fn literal_index_and_assignment() {
+    assert_output(
+        "dict_basic",
+        "让 人 = {\"名字\": \"张三\", \"年龄\": 25}
+打印(人[\"名字\"])
+人[\"年龄\"] = 26
+人[\"城市\"] = \"北京\"
+打印(人)
+打印(人.长度())
+打印({})
+",
+        "\"张三\"\n{\"名字\": \"张三\", \"年龄\": 26, \"城市\": \"北京\"}\n3\n{}\n",
+    );
+}
+
+#[test]
+fn methods() {
+    assert_output(
+        "dict_methods",
+        "让 分数 = {\"甲\": 90, \"乙\": 80}
+打印(分数.键())
+打印(分数.值())
+打印(分数.包含(\"甲\"))
+打印(分数.包含(\"丙\"))
+打印(分数.删除(\"甲\"))
+打印(分数)
+",
+        "[\"甲\", \"乙\"]\n[90, 80]\n真\n假\n90\n{\"乙\": 80}\n",
+    );
+}
+
+#[test]
+fn iteration_over_keys_and_pairs() {
+    assert_output(
+        "dict_iteration",
+        "让 颜色 = {\"红\": 1, \"绿\": 2}
+对于 键 在 颜色 {
+    打印(键)
+}
+对于 (键, 值) 在 颜色 {
+    颜色[键 + \"色\"] = 值
+    打印(键 + \"=\" + 值)
+}
+打印(颜色.长度())
+",
+        "\"红\"\n\"绿\"\n\"红=1\"\n\"绿=2\"\n4\n",
+    );
+}
+
+#[test]
+fn dictionaries_are_shared_and_may_contain_themselves() {
+    assert_output(
+        "dict_reference",
+        "让 原来 = {\"a\": [1]}
+让 别名 = 原来
+别名[\"自己\"] = 原来
+打印(原来)
+打印(原来 == 别名)
+",
+        "{\"a\": [1], \"自己\": {...}}\n真\n",
+    );
+}
+
+#[test]
+fn errors() {
+    assert_error("dict_missing_key", "让 字典 = {\"a\": 1}\n打印(字典[\"b\"])\n", "E0208");
+    assert_error("dict_remove_missing", "让 字典 = {}\n字典.删除(\"b\")\n", "E0208");
+    assert_error("dict_number_key", "让 字典 = {1: 2}\n", "E0204");
+    assert_error("dict_keys_on_array", "让 列表 = [1]\n列表.键()\n", "E0212");
+    assert_error("pairs_over_number", "对于 (a, b) 在 1 {\n}\n", "E0209");
+    assert_error("dict_missing_colon", "让 字典 = {\"a\" 1}\n", "E0101");
+}
+
+#[test]
+fn literals_in_conditions() {
+    // 条件中的 `{` 是代码块的开始，字典字面量要写在括号中
+    assert_output(
+        "dict_in_condition",
+        "对于 (键, 值) 在 ({\"a\": 1}) {
+    打印(键)
+}
+如果 [{}] != [{\"b\": 2}] {
+    打印(\"不同\")
+}
+",
+        "\"a\"\n\"不同\"\n",
+    );
+    
+    // 不完整的条件只报告一个错误，后面的代码块不会被当成字典
+    let (_, stderr) = run("incomplete_condition", "让 a = 1\n如果 a > { 打印(a) }\n打印(a)\n");
+    assert!(stderr.contains("错误[E0102]: 运算符 '>' 右边缺少表达式"), "{}", stderr);
+    assert_eq!(stderr.matches("错误[").count(), 1, "{}", stderr);
+}
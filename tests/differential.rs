@@ -0,0 +1,195 @@
+//! 同一段程序分别用解释器和 C 后端运行，输出必须相同
+
+mod common;
+
+use common::assert_same_output;
+
+#[test]
+fn while_checks_condition_before_each_iteration() {
+    assert_same_output(
+        "diff_while",
+        "变量 i = 0
+当 i < 3 {
+    打印(i)
+    i = i + 1
+}
+当 假 {
+    打印(\"不会执行\")
+}
+打印(i)
+",
+        "0\n1\n2\n3\n",
+    );
+}
+
+#[test]
+fn loop_forms_agree() {
+    assert_same_output(
+        "diff_loops",
+        "变量 i = 0
+循环 i < 2 {
+    i = i + 1
+}
+打印(i)
+循环 {
+    i = i + 1
+    如果 i >= 5 {
+        跳出
+    }
+}
+打印(i)
+// 继续 之后重新检查条件
+变量 奇数 = []
+当 i < 10 {
+    i = i + 1
+    如果 i / 2 == 3 {
+        继续
+    }
+    奇数.添加(i)
+}
+打印(奇数)
+循环 假 {
+    打印(\"不会执行\")
+}
+",
+        "2\n5\n[7, 8, 9, 10]\n",
+    );
+}
+
+#[test]
+fn values_print_the_same() {
+    assert_same_output(
+        "diff_values",
+        "打印(1)
+打印(1.5)
+打印(-0.25)
+打印(10 / 4)
+打印(1 / 3)
+打印(100000000)
+打印(\"文字\")
+打印(真)
+打印([1, \"二\", [3.5, 假]])
+打印({\"a\": [1], \"b\": {\"c\": \"d\"}})
+打印(\"数字 \" + 42 + \" 和 \" + [1, 2])
+打印(范围(3))
+",
+        "1\n1.5\n-0.25\n2.5\n0.3333333333333333\n100000000\n\"文字\"\n真\n[1, \"二\", [3.5, 假]]\n{\"a\": [1], \"b\": {\"c\": \"d\"}}\n\"数字 42 和 [1, 2]\"\n[0, 1, 2]\n",
+    );
+}
+
+#[test]
+fn functions_and_errors_agree() {
+    assert_same_output(
+        "diff_functions",
+        "函数 斐波那契(n) {
+    如果 n < 2 {
+        返回 n
+    }
+    返回 斐波那契(n - 1) + 斐波那契(n - 2)
+}
+函数 除(a, b) {
+    返回 a / b
+}
+打印(斐波那契(15))
+打印(除(7, 2))
+尝试 {
+    除(1, 0)
+} 捕获 (错误) {
+    打印(错误.种类)
+}
+对于 (i, 字) 在 \"你好\" {
+    打印(i + \": \" + 字)
+}
+",
+        "610\n3.5\n\"E0205\"\n\"0: 你\"\n\"1: 好\"\n",
+    );
+}
+
+#[test]
+fn functions_see_top_level_variables() {
+    assert_same_output(
+        "diff_globals",
+        "变量 g = 5
+函数 f() {
+    返回 g
+}
+打印(f())
+让 次数: 数字 = 0
+函数 计数() -> 数字 {
+    次数 = 次数 + 1
+    返回 次数
+}
+计数()
+打印(计数())
+// 代码块中的同名变量不影响全局变量
+如果 真 {
+    让 g = 7
+    打印(g)
+}
+打印(f())
+让 index = [1]
+打印(index)
+",
+        "5\n2\n7\n5\n[1]\n",
+    );
+}
+
+#[test]
+fn locals_changed_in_try_keep_their_values() {
+    // setjmp 之后修改的局部变量，longjmp 回来后仍然是修改后的值
+    assert_same_output(
+        "diff_try_locals",
+        "函数 测试(n: 数字) {
+    让 x = 0
+    让 s: 字符串 = \"\"
+    尝试 {
+        x = 7
+        n = n + 1
+        s = \"改过\"
+        抛出 \"失败\"
+    } 捕获 {
+        打印(x)
+        打印(n)
+    } 最终 {
+        打印(x + n)
+        打印(s)
+    }
+}
+测试(1)
+函数 除(a, b) {
+    返回 a / b
+}
+如果 真 {
+    让 y: 数字 = 0
+    尝试 {
+        y = 3
+        除(1, 0)
+    } 捕获 {
+        打印(y)
+    }
+}
+",
+        "7\n2\n9\n\"改过\"\n3\n",
+    );
+}
+
+#[test]
+fn global_constants_and_redeclarations() {
+    assert_same_output(
+        "diff_global_constants",
+        "常量 上限: 数字 = 10
+常量 N = -1
+常量 开 = 真
+让 x = 1
+打印(x)
+让 x = \"二\"
+函数 f() {
+    返回 上限 + N
+}
+打印(f())
+打印(开)
+打印(x)
+",
+        "1\n9\n真\n\"二\"\n",
+    );
+}
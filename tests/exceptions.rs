@@ -0,0 +1,113 @@
+//! 尝试/捕获/最终/抛出
+
+mod common;
+
+use common::{assert_error, assert_output, run};
+
+#[test]
+fn catch_runtime_error_fields() {
+    assert_output(
+        "exception_fields",
+        "尝试 {
+    打印(1 / 0)
+} 捕获 (错误) {
+    打印(错误)
+    打印(错误.消息)
+    打印(错误.种类)
+    打印(错误.行)
+}
+",
+        "错误[E0205]: 除零错误\n\"除零错误\"\n\"E0205\"\n2\n",
+    );
+}
+
+#[test]
+fn throw_and_catch_value() {
+    assert_output(
+        "exception_throw",
+        "函数 检查(年龄) {
+    如果 年龄 < 0 {
+        抛出 \"年龄不能为负\"
+    }
+    返回 年龄
+}
+尝试 {
+    检查(-1)
+    打印(\"不会执行\")
+} 捕获 e {
+    打印(e.消息 + \" \" + e.种类)
+}
+尝试 {
+    抛出 [1, 2]
+} 捕获 e {
+    打印(e.值)
+}
+",
+        "\"年龄不能为负 E0213\"\n[1, 2]\n",
+    );
+}
+
+#[test]
+fn finally_always_runs() {
+    assert_output(
+        "exception_finally",
+        "尝试 {
+    打印(\"正常\")
+} 最终 {
+    打印(\"最终 1\")
+}
+尝试 {
+    尝试 {
+        抛出 \"失败\"
+    } 最终 {
+        打印(\"最终 2\")
+    }
+} 捕获 {
+    打印(\"外层捕获\")
+}
+对于 x 在 \"ab\" {
+    尝试 {
+        跳出
+    } 最终 {
+        打印(\"最终 3\")
+    }
+}
+函数 f() {
+    尝试 {
+        返回 1
+    } 最终 {
+        打印(\"最终 4\")
+    }
+}
+打印(f())
+",
+        "\"正常\"\n\"最终 1\"\n\"最终 2\"\n\"外层捕获\"\n\"最终 3\"\n\"最终 4\"\n1\n",
+    );
+}
+
+#[test]
+fn rethrow_keeps_original_error() {
+    assert_output(
+        "exception_rethrow",
+        "尝试 {
+    尝试 {
+        打印([1][5])
+    } 捕获 (内) {
+        抛出 内
+    }
+} 捕获 (外) {
+    打印(外.种类)
+}
+",
+        "\"E0206\"\n",
+    );
+}
+
+#[test]
+fn uncaught_throw_is_reported() {
+    let (_, stderr) = run("exception_uncaught", "抛出 \"没人接\"\n");
+    assert!(stderr.contains("错误[E0213]: 没人接"), "{}", stderr);
+    assert!(stderr.contains("没有被 尝试 语句捕获"), "{}", stderr);
+    
+    assert_error("exception_missing_handler", "尝试 {\n    打印(1)\n}\n", "E0101");
+}
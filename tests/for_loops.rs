@@ -0,0 +1,97 @@
+//! 对于 循环：范围、数组、字符串和 (下标, 值)
+
+mod common;
+
+use common::{assert_error, assert_output};
+
+#[test]
+fn ranges() {
+    assert_output(
+        "range_loop",
+        "对于 i 在 1..4 {
+    打印(i)
+}
+对于 i 在 范围(10, 0, -4) {
+    打印(i)
+}
+打印(范围(3))
+打印(范围(0, 1, 0.25))
+打印(5..5)
+让 n = 2
+打印(n - 1..n + 1)
+",
+        "1\n2\n3\n10\n6\n2\n[0, 1, 2]\n[0, 0.25, 0.5, 0.75]\n[]\n[1, 2]\n",
+    );
+}
+
+#[test]
+fn ranges_are_iterated_lazily() {
+    // 遍历范围时不生成整个数组，很大的范围也可以提前跳出
+    assert_output(
+        "range_lazy",
+        "对于 i 在 范围(1000000000000) {
+    如果 i == 2 {
+        跳出
+    }
+    打印(i)
+}
+对于 (下标, 值) 在 0..1000000000000 {
+    如果 下标 == 1 {
+        跳出
+    }
+    打印(值)
+}
+对于 (下标, 值) 在 范围(1, 2, 0.5) {
+    打印(下标 + 值)
+}
+",
+        "0\n1\n0\n1\n2.5\n",
+    );
+}
+
+#[test]
+fn arrays_and_strings() {
+    assert_output(
+        "array_loop",
+        "让 总 = 0
+对于 x 在 [1, 2, 3] {
+    总 = 总 + x
+}
+打印(总)
+对于 (下标, 值) 在 [\"甲\", \"乙\"] {
+    打印(下标)
+    打印(值)
+}
+对于 (i, 字) 在 \"你好\" {
+    打印(i)
+    打印(字)
+}
+",
+        "6\n0\n\"甲\"\n1\n\"乙\"\n0\n\"你\"\n1\n\"好\"\n",
+    );
+}
+
+#[test]
+fn loop_iterates_a_snapshot() {
+    assert_output(
+        "array_loop_snapshot",
+        "变量 列表 = [1, 2]
+对于 x 在 列表 {
+    列表.添加(x * 10)
+}
+打印(列表)
+",
+        "[1, 2, 10, 20]\n",
+    );
+}
+
+#[test]
+fn errors() {
+    assert_error("range_zero_step", "打印(范围(0, 5, 0))\n", "E0204");
+    assert_error("range_not_number", "打印(1..\"5\")\n", "E0204");
+    assert_error("range_arguments", "打印(范围())\n", "E0203");
+    assert_error("range_loop_zero_step", "对于 i 在 范围(0, 5, 0) {\n    打印(i)\n}\n", "E0204");
+    assert_error("range_loop_not_number", "对于 i 在 1..\"5\" {\n    打印(i)\n}\n", "E0204");
+    assert_error("range_missing_end", "打印(1..)\n", "E0102");
+    assert_error("loop_over_number", "对于 x 在 5 {\n}\n", "E0209");
+}
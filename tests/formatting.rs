@@ -0,0 +1,110 @@
+//! `cnlang fmt`：缩进、括号、注释和空行
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use common::{format, run};
+
+#[test]
+fn minimal_parentheses() {
+    assert_eq!(
+        format("parentheses", "让 a = (1 + 2) * 3\n让 b = 1 + (2 * 3)\n让 c = a - (b - 1)\n让 d = (a - b) - 1\n"),
+        "让 a = (1 + 2) * 3\n让 b = 1 + 2 * 3\n让 c = a - (b - 1)\n让 d = a - b - 1\n"
+    );
+    assert_eq!(
+        format("unary", "打印(!(a > 1) 且 (a 或 b))\n打印(-(a + b), -a * b)\n让 r = (0..n + 1)\n"),
+        "打印(非 (a > 1) 且 (a 或 b))\n打印(-(a + b), -a * b)\n让 r = 0..n + 1\n"
+    );
+    assert_eq!(
+        format("postfix", "打印((a + b).长度(), (列表)[0], f(1)(2))\n"),
+        "打印((a + b).长度(), 列表[0], f(1)(2))\n"
+    );
+}
+
+#[test]
+fn blocks_and_else_if_chains() {
+    assert_eq!(
+        format(
+            "blocks",
+            "如果 a { 打印(1) } 否则 { 如果 b { 打印(2) } 否则 { 打印(3) } }\n如果 c { }\n变量 x = 1; 类 点 { x, y }\n"
+        ),
+        "如果 a {\n    打印(1)\n} 否则 如果 b {\n    打印(2)\n} 否则 {\n    打印(3)\n}\n如果 c {}\n让 x = 1\n结构 点 { x, y }\n"
+    );
+    assert_eq!(
+        format("try", "尝试 { 抛出 \"x\" } 捕获 e { 打印(e) } 最终 {}\n"),
+        "尝试 {\n    抛出 \"x\"\n} 捕获 (e) {\n    打印(e)\n} 最终 {}\n"
+    );
+}
+
+#[test]
+fn comments_and_blank_lines() {
+    let source = "// 开头\n\n\n\n让 a = 1   // 行尾\n函数 f() { // 函数\n\n    返回 a\n\n    // 结尾\n\n}\n\n\n打印(f())\n";
+    let expected = "// 开头\n\n让 a = 1 // 行尾\n函数 f() { // 函数\n    返回 a\n\n    // 结尾\n}\n\n打印(f())\n";
+    assert_eq!(format("comments", source), expected);
+    assert_eq!(format("comments_again", expected), expected);
+}
+
+#[test]
+fn line_sensitive_statements() {
+    // 分开的语句格式化之后不能连成一条
+    let source = "函数 f() { 返回; 打印(1) }\n让 a = 1; -a\n";
+    let formatted = format("line_sensitive", source);
+    assert_eq!(formatted, "函数 f() {\n    返回;\n    打印(1)\n}\n让 a = 1\n(-a)\n");
+    assert_eq!(run("line_sensitive", &(formatted + "打印(f())\n")).0, "空\n");
+}
+
+#[test]
+fn dictionaries_in_conditions_keep_parentheses() {
+    // 条件中的 `{` 是代码块的开始，字典字面量所在的整个条件保留括号
+    let source = "对于 (键, 值) 在 ({\"a\": 1}) {\n    打印(键)\n}\n如果 ({}.长度() == 0) {\n    打印(1)\n}\n当 ([{}] == 1) {}\n";
+    let expected = "对于 (键, 值) 在 ({\"a\": 1}) {\n    打印(键)\n}\n如果 ({}.长度() == 0) {\n    打印(1)\n}\n当 [{}] == 1 {}\n";
+    let formatted = format("dict_condition", source);
+    assert_eq!(formatted, expected);
+    assert_eq!(format("dict_condition_again", &formatted), formatted);
+    assert_eq!(run("dict_condition", &formatted), ("\"a\"\n1\n".to_string(), String::new()));
+}
+
+#[test]
+fn check_mode() {
+    let path = std::env::temp_dir().join(format!("cnlang_fmt_check_{}.cn", std::process::id()));
+    let check = |source: &str| {
+        fs::write(&path, source).unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_cnlang")).args(["fmt", "--check"]).arg(&path).output().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), source, "--check 不应修改文件");
+        output
+    };
+    
+    assert_eq!(check("让 a = 1\n").status.code(), Some(0));
+    let output = check("让 a=1\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("需要格式化: "));
+    // 有语法错误的文件不格式化
+    assert_eq!(check("让 = 1\n").status.code(), Some(1));
+    fs::remove_file(&path).unwrap();
+}
+
+/// 格式化不改变示例程序的输出，再次格式化不再改变
+#[test]
+fn samples_keep_their_behaviour() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let samples = [root.to_path_buf(), root.join("示例")]
+        .into_iter()
+        .flat_map(|dir| fs::read_dir(dir).unwrap())
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "cn"));
+    
+    for (i, path) in samples.enumerate() {
+        let source = fs::read_to_string(&path).unwrap();
+        let name = format!("sample_{}", i);
+        let formatted = format(&name, &source);
+        assert_eq!(format(&name, &formatted), formatted, "{} 格式化两次的结果不同", path.display());
+        // 错误的位置可能随格式化改变，只比较输出和是否出错
+        let (before, before_errors) = run(&name, &source);
+        let (after, after_errors) = run(&name, &formatted);
+        assert_eq!(after, before, "{} 格式化后输出不同", path.display());
+        assert_eq!(after_errors.is_empty(), before_errors.is_empty(), "{}", path.display());
+    }
+}
@@ -0,0 +1,83 @@
+//! 匿名函数、闭包和调用任意表达式
+
+mod common;
+
+use common::{assert_error, assert_output};
+
+#[test]
+fn anonymous_function_stored_in_variable() {
+    assert_output(
+        "anon_variable",
+        "变量 双倍 = 函数(x) { 返回 x * 2 }
+打印(双倍(21))
+",
+        "42\n",
+    );
+}
+
+#[test]
+fn functions_are_passed_as_arguments() {
+    assert_output(
+        "anon_argument",
+        "函数 映射(数组, 变换) {
+    变量 结果 = []
+    变量 i = 0
+    循环 (i < 数组.长度()) {
+        结果 = 结果.添加(变换(数组[i]))
+        i = i + 1
+    }
+    返回 结果
+}
+函数 平方(x) {
+    返回 x * x
+}
+打印(映射([1, 2, 3], 平方))
+打印(映射([1, 2, 3], 函数(x) { 返回 x + 100 }))
+",
+        "[1, 4, 9]\n[101, 102, 103]\n",
+    );
+}
+
+#[test]
+fn returned_function_captures_parameters() {
+    assert_output(
+        "anon_returned",
+        "函数 加法器(n) {
+    返回 函数(x) { 返回 x + n }
+}
+变量 加十 = 加法器(10)
+打印(加十(5))
+打印(加法器(1)(1))
+",
+        "15\n2\n",
+    );
+}
+
+#[test]
+fn any_expression_is_callable() {
+    assert_output(
+        "anon_callable",
+        "打印((函数() { 返回 \"立即\" })())
+变量 表 = [函数(x) { 返回 x - 1 }]
+打印(表[0](4))
+",
+        "\"立即\"\n3\n",
+    );
+}
+
+#[test]
+fn call_on_next_line_starts_new_statement() {
+    assert_output(
+        "anon_newline",
+        "变量 x = 1
+(打印(x))
+",
+        "1\n",
+    );
+}
+
+#[test]
+fn calling_a_non_function_is_an_error() {
+    assert_error("anon_not_function", "打印(5(1))\n", "E0202");
+    assert_error("anon_arity", "变量 f = 函数(x) { 返回 x }\nf(1, 2)\n", "E0203");
+}
@@ -0,0 +1,51 @@
+//! 且/或 的短路求值和结果
+
+mod common;
+
+use common::assert_output;
+
+#[test]
+fn and_skips_right_side_when_left_is_false() {
+    assert_output(
+        "and_short_circuit",
+        "变量 x = 0
+如果 x != 0 且 10 / x > 1 {
+    打印(\"不应执行\")
+} 否则 {
+    打印(\"跳过了除法\")
+}
+",
+        "\"跳过了除法\"\n",
+    );
+}
+
+#[test]
+fn or_skips_right_side_when_left_is_true() {
+    assert_output(
+        "or_short_circuit",
+        "变量 调用次数 = 0
+函数 记录() {
+    调用次数 = 调用次数 + 1
+    返回 真
+}
+变量 结果 = 真 或 记录()
+打印(调用次数)
+结果 = 假 或 记录()
+打印(调用次数)
+",
+        "0\n1\n",
+    );
+}
+
+#[test]
+fn logical_operators_return_booleans() {
+    assert_output(
+        "logic_booleans",
+        "打印(1 且 \"非空\")
+打印(0 或 \"\")
+打印(\"\" 且 1)
+打印(假 或 2)
+",
+        "真\n假\n假\n真\n",
+    );
+}
@@ -0,0 +1,290 @@
+//! 语言服务器：用一个简单的客户端通过标准输入输出收发 LSP 消息
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use serde_json::{json, Value};
+
+const URI: &str = "file:///测试.cn";
+
+struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: u64,
+    /// 等待回复时收到的通知
+    notifications: Vec<Value>,
+}
+
+impl Client {
+    /// 启动 `cnlang lsp` 并完成初始化
+    fn start() -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_cnlang"))
+            .arg("lsp")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let mut client = Client {
+            child,
+            stdin,
+            stdout,
+            next_id: 0,
+            notifications: Vec::new(),
+        };
+        let result = client.request("initialize", json!({ "capabilities": {} }));
+        assert_eq!(result["capabilities"]["definitionProvider"], true);
+        client.notify("initialized", json!({}));
+        client
+    }
+    
+    fn send(&mut self, message: Value) {
+        let body = message.to_string();
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        self.stdin.flush().unwrap();
+    }
+    
+    fn receive(&mut self) -> Value {
+        let mut length = 0;
+        loop {
+            let mut header = String::new();
+            assert!(self.stdout.read_line(&mut header).unwrap() > 0, "语言服务器提前结束");
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some(value) = header.strip_prefix("Content-Length:") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; length];
+        self.stdout.read_exact(&mut body).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+    
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+    
+    /// 发送请求并等待回复，返回 `result`；出错时返回整个回复
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        loop {
+            let message = self.receive();
+            if message["id"] == id {
+                return message.get("result").cloned().unwrap_or(message);
+            }
+            self.notifications.push(message);
+        }
+    }
+    
+    /// 下一次发布的诊断信息
+    fn diagnostics(&mut self) -> Vec<Value> {
+        let message = match self.notifications.is_empty() {
+            true => self.receive(),
+            false => self.notifications.remove(0),
+        };
+        assert_eq!(message["method"], "textDocument/publishDiagnostics");
+        assert_eq!(message["params"]["uri"], URI);
+        message["params"]["diagnostics"].as_array().unwrap().clone()
+    }
+    
+    fn open(&mut self, text: &str) -> Vec<Value> {
+        self.notify(
+            "textDocument/didOpen",
+            json!({ "textDocument": { "uri": URI, "languageId": "cnlang", "version": 1, "text": text } }),
+        );
+        self.diagnostics()
+    }
+    
+    /// 光标位置上的请求，行列从 0 开始
+    fn at(&mut self, method: &str, line: u32, character: u32) -> Value {
+        self.request(
+            method,
+            json!({
+                "textDocument": { "uri": URI },
+                "position": { "line": line, "character": character },
+                "context": { "includeDeclaration": true },
+            }),
+        )
+    }
+    
+    fn shutdown(mut self) -> Option<i32> {
+        assert_eq!(self.request("shutdown", Value::Null), Value::Null);
+        self.notify("exit", Value::Null);
+        self.child.wait().unwrap().code()
+    }
+}
+
+/// 位置的 (行, 列)，从 0 开始
+fn start(location: &Value) -> (u64, u64) {
+    let start = &location["range"]["start"];
+    (start["line"].as_u64().unwrap(), start["character"].as_u64().unwrap())
+}
+
+fn labels(items: &Value) -> Vec<&str> {
+    items.as_array().unwrap().iter().map(|item| item["label"].as_str().unwrap()).collect()
+}
+
+#[test]
+fn lifecycle() {
+    let client = Client::start();
+    assert_eq!(client.shutdown(), Some(0));
+    
+    // 没有 shutdown 就 exit 是失败
+    let mut client = Client::start();
+    client.notify("exit", Value::Null);
+    assert_eq!(client.child.wait().unwrap().code(), Some(1));
+    
+    let mut client = Client::start();
+    let response = client.request("textDocument/formatting", json!({}));
+    assert_eq!(response["error"]["code"], -32601);
+    client.shutdown();
+}
+
+#[test]
+fn malformed_message_is_reported_and_skipped() {
+    let mut client = Client::start();
+    let body = "{\"jsonrpc\": \"2.0\", 不是 JSON";
+    write!(client.stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+    client.stdin.flush().unwrap();
+    let response = client.receive();
+    assert_eq!(response["error"]["code"], -32700);
+    assert_eq!(response["id"], Value::Null);
+    
+    // 超过长度上限的消息不读入内存，跳过内容后回复错误
+    let length = 64 * 1024 * 1024 + 1;
+    write!(client.stdin, "Content-Length: {}\r\n\r\n", length).unwrap();
+    let chunk = vec![b' '; 1024 * 1024];
+    for _ in 0..64 {
+        client.stdin.write_all(&chunk).unwrap();
+    }
+    client.stdin.write_all(b" ").unwrap();
+    client.stdin.flush().unwrap();
+    let response = client.receive();
+    assert_eq!(response["error"]["code"], -32600);
+    assert_eq!(response["id"], Value::Null);
+    
+    // 之后的请求照常处理
+    assert!(client.open("让 x = 1\n打印(x)\n").is_empty());
+    assert_eq!(client.shutdown(), Some(0));
+}
+
+#[test]
+fn diagnostics_follow_edits() {
+    let mut client = Client::start();
+    let diagnostics = client.open("让 x = (1 + \n");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["severity"], 1);
+    assert_eq!(diagnostics[0]["source"], "cnlang");
+    
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": URI, "version": 2 },
+            "contentChanges": [{ "text": "常量 上限 = 1\n上限 = 2\n" }],
+        }),
+    );
+    let diagnostics = client.diagnostics();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["code"], "E0210");
+    assert_eq!(start(&diagnostics[0]), (1, 0));
+    
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": URI, "version": 3 },
+            "contentChanges": [{ "text": "常量 上限 = 1\n打印(上限)\n" }],
+        }),
+    );
+    assert!(client.diagnostics().is_empty());
+    client.shutdown();
+}
+
+#[test]
+fn definition_and_references() {
+    let mut client = Client::start();
+    let source = "让 总数 = 0\n函数 加(数) {\n    总数 = 总数 + 数\n}\n加(1)\n打印(总数)\n";
+    assert!(client.open(source).is_empty());
+    
+    // 函数体中的 总数 指向第一行的声明
+    let definition = client.at("textDocument/definition", 2, 11);
+    assert_eq!(definition["uri"], URI);
+    assert_eq!(start(&definition), (0, 2));
+    
+    let references = client.at("textDocument/references", 0, 2);
+    let positions: Vec<_> = references.as_array().unwrap().iter().map(start).collect();
+    assert_eq!(positions, [(0, 2), (2, 4), (2, 9), (5, 3)]);
+    
+    // 参数没有单独的位置，但使用处都指向同一个参数
+    let references = client.at("textDocument/references", 2, 14);
+    assert_eq!(references.as_array().unwrap().len(), 1);
+    
+    assert_eq!(start(&client.at("textDocument/definition", 4, 0)), (1, 3));
+    // 内置函数没有定义的位置
+    assert_eq!(client.at("textDocument/definition", 5, 0), Value::Null);
+    client.shutdown();
+}
+
+#[test]
+fn hover_shows_signatures() {
+    let mut client = Client::start();
+    client.open("函数 加(a, b) {\n    返回 a + b\n}\n打印(加(1, 2))\n");
+    
+    let hover = client.at("textDocument/hover", 3, 3);
+    assert_eq!(hover["contents"]["value"], "```cnlang\n函数 加(a, b)\n```");
+    let hover = client.at("textDocument/hover", 3, 0);
+    assert_eq!(hover["contents"]["value"], "```cnlang\n函数 打印(值)\n```");
+    assert_eq!(client.at("textDocument/hover", 1, 0), Value::Null);
+    
+    // 签名中带类型标注
+    client.open("让 年龄: 数字 = 25\n函数 加(a: 数字, b) -> 数字 {\n    返回 a + b\n}\n打印(加(年龄, 1))\n");
+    let hover = client.at("textDocument/hover", 4, 3);
+    assert_eq!(hover["contents"]["value"], "```cnlang\n函数 加(a: 数字, b) -> 数字\n```");
+    let hover = client.at("textDocument/hover", 4, 5);
+    assert_eq!(hover["contents"]["value"], "```cnlang\n让 年龄: 数字\n```");
+    client.shutdown();
+}
+
+#[test]
+fn completion_uses_scopes() {
+    let mut client = Client::start();
+    let source = "让 外 = 1\n函数 内(参) {\n    让 局部 = 参\n    \n}\n打印(外)\n";
+    client.open(source);
+    
+    let inside = client.at("textDocument/completion", 3, 4);
+    let inside = labels(&inside);
+    for name in ["外", "内", "参", "局部", "打印", "如果", "返回"] {
+        assert!(inside.contains(&name), "函数中应该能补全 {}: {:?}", name, inside);
+    }
+    
+    // 代码块没有记录结尾的位置，函数的作用域到下一条语句为止
+    let outside = client.at("textDocument/completion", 5, 3);
+    let outside = labels(&outside);
+    assert!(outside.contains(&"外") && outside.contains(&"内"), "{:?}", outside);
+    assert!(!outside.contains(&"参") && !outside.contains(&"局部"), "{:?}", outside);
+    
+    // 还没有声明的名字不能补全
+    let before = client.at("textDocument/completion", 0, 0);
+    assert!(!labels(&before).contains(&"外"));
+    client.shutdown();
+}
+
+#[test]
+fn document_symbols() {
+    let mut client = Client::start();
+    let source = "常量 上限 = 10\n结构 点 {\n    x, y\n    函数 长度() {\n        返回 0\n    }\n}\n函数 主() {\n}\n";
+    client.open(source);
+    
+    let symbols = client.request("textDocument/documentSymbol", json!({ "textDocument": { "uri": URI } }));
+    let symbols = symbols.as_array().unwrap();
+    let names: Vec<_> = symbols.iter().map(|symbol| (symbol["name"].as_str().unwrap(), symbol["kind"].as_u64().unwrap())).collect();
+    assert_eq!(names, [("上限", 14), ("点", 23), ("主", 12)]);
+    assert_eq!(symbols[1]["children"][0]["name"], "长度");
+    assert_eq!(start(&json!({ "range": symbols[1]["selectionRange"] })), (1, 3));
+    client.shutdown();
+}
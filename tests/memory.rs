@@ -0,0 +1,128 @@
+//! 压力测试：大量函数调用和闭包之后内存占用保持平稳
+#![cfg(target_os = "linux")]
+
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+/// 运行解释器，轮询 /proc 得到运行期间的最大常驻内存（KB）
+fn peak_rss_kb(args: &[&str], stdin: &str) -> u64 {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_cnlang"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut input = child.stdin.take().unwrap();
+    let stdin = stdin.to_string();
+    let writer = thread::spawn(move || input.write_all(stdin.as_bytes()).unwrap());
+    
+    let mut peak = 0;
+    while child.try_wait().unwrap().is_none() {
+        let status = fs::read_to_string(format!("/proc/{}/status", child.id())).unwrap_or_default();
+        let rss = status
+            .lines()
+            .find_map(|line| line.strip_prefix("VmRSS:"))
+            .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+            .unwrap_or(0);
+        peak = peak.max(rss);
+        thread::sleep(Duration::from_millis(5));
+    }
+    
+    writer.join().unwrap();
+    assert!(child.wait().unwrap().success());
+    peak
+}
+
+fn run_file_peak_rss_kb(name: &str, source: &str) -> u64 {
+    let path = std::env::temp_dir().join(format!("cnlang_memory_{}_{}.cn", name, std::process::id()));
+    fs::write(&path, source).unwrap();
+    let peak = peak_rss_kb(&[path.to_str().unwrap()], "");
+    fs::remove_file(&path).unwrap();
+    peak
+}
+
+/// 循环调用函数，每次调用内部还有一层递归
+fn calls(count: u32) -> String {
+    format!(
+        "函数 求和(n) {{
+    如果 (n <= 0) {{
+        返回 0
+    }}
+    返回 n + 求和(n - 1)
+}}
+变量 i = 0
+循环 (i < {}) {{
+    变量 结果 = 求和(9)
+    i = i + 1
+}}
+打印(i)
+",
+        count / 10
+    )
+}
+
+#[test]
+fn memory_stays_flat_across_a_million_calls() {
+    let baseline = run_file_peak_rss_kb("baseline", &calls(10_000));
+    let stressed = run_file_peak_rss_kb("stressed", &calls(1_000_000));
+    assert!(
+        stressed < baseline + 8 * 1024,
+        "一百万次调用后内存从 {} KB 增长到 {} KB",
+        baseline,
+        stressed
+    );
+}
+
+#[test]
+fn unreachable_closures_are_collected() {
+    // 交互模式中每行输入都是顶层语句，旧的闭包在语句之间变成垃圾
+    let closures = |count: usize| {
+        let mut input = String::from(
+            "函数 计数器() { 变量 n = 0; 函数 下一个() { n = n + 1; 返回 n }; 返回 下一个 }\n变量 c = 计数器()\n",
+        );
+        input.push_str(&"c = 计数器()\nc()\n".repeat(count));
+        input
+    };
+    
+    let baseline = peak_rss_kb(&[], &closures(1_000));
+    let stressed = peak_rss_kb(&[], &closures(100_000));
+    assert!(
+        stressed < baseline + 8 * 1024,
+        "丢弃十万个闭包后内存从 {} KB 增长到 {} KB",
+        baseline,
+        stressed
+    );
+}
+
+#[test]
+fn closures_created_in_a_loop_are_collected() {
+    // 一条顶层语句中的循环不断创建闭包，垃圾回收在循环迭代之间进行，不等语句结束
+    let closures = |count: u32| {
+        format!(
+            "函数 外层() {{
+    函数 内层() {{ 返回 1 }}
+    返回 内层
+}}
+变量 i = 0
+循环 (i < {}) {{
+    外层()
+    i = i + 1
+}}
+打印(i)
+",
+            count
+        )
+    };
+    
+    let baseline = run_file_peak_rss_kb("closures_baseline", &closures(3_000));
+    let stressed = run_file_peak_rss_kb("closures_stressed", &closures(300_000));
+    assert!(
+        stressed < baseline + 8 * 1024,
+        "循环中创建三十万个闭包后内存从 {} KB 增长到 {} KB",
+        baseline,
+        stressed
+    );
+}
@@ -0,0 +1,105 @@
+//! 导入其它文件中公开的声明
+
+mod common;
+
+use common::run_files;
+
+const TOOLS: &str = "公开 常量 圆周率 = 3
+让 计数 = 0
+公开 函数 加(甲, 乙) {
+    计数 = 计数 + 1
+    返回 甲 + 乙
+}
+公开 函数 次数() {
+    返回 计数
+}
+函数 私有() {
+    返回 1
+}
+打印(\"工具 初始化\")
+";
+
+#[test]
+fn import_public_members() {
+    let (stdout, stderr) = run_files(
+        "module_import",
+        &[
+            (
+                "主程序.cn",
+                "导入 工具
+导入 \"库/数学.cn\" 作为 数
+打印(工具.加(1, 2))
+打印(数.平方(4))
+打印(工具.次数())
+打印(工具.圆周率)
+打印(工具)
+",
+            ),
+            ("工具.cn", TOOLS),
+            // 模块中的路径从模块所在的目录开始查找
+            (
+                "库/数学.cn",
+                "导入 \"../工具.cn\" 作为 t
+公开 函数 平方(值) {
+    返回 t.加(值, 0) * 值
+}
+",
+            ),
+        ],
+    );
+    assert_eq!(stderr, "");
+    // 两次导入的是同一个模块，只执行一次，计数也是共享的
+    assert_eq!(stdout, "\"工具 初始化\"\n3\n16\n2\n3\n[模块: 工具]\n");
+}
+
+#[test]
+fn private_and_read_only_members() {
+    let (_, stderr) = run_files("module_private", &[("主程序.cn", "导入 工具\n工具.私有()\n"), ("工具.cn", TOOLS)]);
+    assert!(stderr.contains("错误[E0212]: 模块 工具 没有公开的成员 私有"), "{}", stderr);
+    assert!(stderr.contains("私有 在模块中声明时没有加 公开"), "{}", stderr);
+    
+    let (_, stderr) = run_files("module_assign", &[("主程序.cn", "导入 工具\n工具.圆周率 = 4\n"), ("工具.cn", TOOLS)]);
+    assert!(stderr.contains("[E0210]"), "{}", stderr);
+    
+    let (_, stderr) = run_files("module_rebind", &[("主程序.cn", "导入 工具 作为 t\nt = 1\n"), ("工具.cn", TOOLS)]);
+    assert!(stderr.contains("[E0210]"), "{}", stderr);
+}
+
+#[test]
+fn missing_and_cyclic_imports() {
+    let (_, stderr) = run_files("module_missing", &[("主程序.cn", "导入 没有的模块\n")]);
+    assert!(stderr.contains("错误[E0214]: 找不到模块: 没有的模块"), "{}", stderr);
+    
+    let (stdout, stderr) = run_files(
+        "module_cycle",
+        &[("主程序.cn", "导入 甲\n打印(1)\n"), ("甲.cn", "导入 乙\n"), ("乙.cn", "导入 甲\n")],
+    );
+    assert_eq!(stdout, "", "有循环导入时不执行程序");
+    assert!(stderr.contains("错误[E0215]: 循环导入: 甲"), "{}", stderr);
+    assert!(stderr.contains("甲.cn -> "), "{}", stderr);
+}
+
+#[test]
+fn errors_point_into_the_module() {
+    let (_, stderr) = run_files(
+        "module_runtime_error",
+        &[("主程序.cn", "导入 数学\n数学.除(1, 0)\n"), ("数学.cn", "公开 函数 除(甲, 乙) {\n    返回 甲 / 乙\n}\n")],
+    );
+    assert!(stderr.contains("错误[E0205]: 除零错误"), "{}", stderr);
+    assert!(stderr.contains("数学.cn:2:"), "{}", stderr);
+    assert!(stderr.contains("返回 甲 / 乙"), "{}", stderr);
+    
+    let (_, stderr) = run_files("module_syntax_error", &[("主程序.cn", "导入 坏\n"), ("坏.cn", "让 = 1\n")]);
+    assert!(stderr.contains("[E0101]"), "{}", stderr);
+    assert!(stderr.contains("坏.cn:1:"), "{}", stderr);
+}
+
+#[test]
+fn import_only_at_top_level() {
+    let (_, stderr) = run_files(
+        "module_nested",
+        &[("主程序.cn", "如果 真 {\n    导入 工具\n}\n函数 f() {\n    公开 让 x = 1\n}\n"), ("工具.cn", TOOLS)],
+    );
+    assert!(stderr.contains("错误[E0216]: 导入 只能出现在文件的最外层"), "{}", stderr);
+    assert!(stderr.contains("公开 只能用于文件最外层的声明"), "{}", stderr);
+}
@@ -0,0 +1,48 @@
+//! 交互模式：多行输入、显示表达式的值和 `:` 开头的命令
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// 把 `input` 逐行输入 `cnlang repl`，返回标准输出和标准错误
+fn repl(input: &str) -> (String, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_cnlang"))
+        .arg("repl")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    (String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap())
+}
+
+#[test]
+fn expressions_and_multiline_input() {
+    let (stdout, stderr) = repl("1 + 2\n函数 加(a,\n b) {\n返回 a + b\n}\n加(1, 2)\n[1,\n2]\n:退出\n");
+    assert_eq!(stderr, "");
+    // 括号没有闭合时显示续行提示符，闭合之后一起执行
+    assert!(stdout.contains("中文> 3\n中文> ....> ....> ....> 中文> 3\n"), "{}", stdout);
+    assert!(stdout.contains("中文> ....> [1, 2]\n"), "{}", stdout);
+}
+
+#[test]
+fn commands() {
+    let path = env::temp_dir().join(format!("cnlang_repl_load_{}.cn", std::process::id()));
+    fs::write(&path, "让 工具值 = 5\n函数 三倍(n) {\n    返回 n * 3\n}\n").unwrap();
+    let input = format!(":载入 {}\n三倍(工具值)\n让 x = 1\n:变量\n:重置\nx\n:没有\n", path.display());
+    let (stdout, stderr) = repl(&input);
+    fs::remove_file(&path).unwrap();
+    
+    // 载入的文件中的定义留在交互模式中
+    assert!(stdout.contains("中文> 15\n"), "{}", stdout);
+    assert!(stdout.contains("作用域 #0 (全局)\n"), "{}", stdout);
+    assert!(stdout.contains("  x = 1\n") && stdout.contains("  工具值 = 5\n"), "{}", stdout);
+    // 重置之后之前的变量不再存在
+    assert!(stdout.contains("已重置解释器状态\n"), "{}", stdout);
+    assert!(stderr.contains("错误[E0200]: 未定义的变量: x"), "{}", stderr);
+    assert!(stdout.contains("未知命令: :没有"), "{}", stdout);
+}
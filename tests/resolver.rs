@@ -0,0 +1,192 @@
+//! 名字解析：运行前发现未定义的名字、参数数量错误、放错位置的控制流、没有使用的变量和执行不到的代码
+
+mod common;
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use common::{assert_output, run};
+
+/// 运行一段有静态错误的程序：应该报告 `code`，并且一行都不执行
+fn assert_rejected(name: &str, source: &str, code: &str) -> String {
+    let (stdout, stderr) = run(name, source);
+    assert!(stderr.contains(&format!("错误[{}]", code)), "期望错误 {}，实际输出:\n{}", code, stderr);
+    assert_eq!(stdout, "", "有静态错误的程序不应运行");
+    stderr
+}
+
+/// 运行一段程序，标准错误应该只有警告 `code`，程序照常运行
+fn assert_warning(name: &str, source: &str, code: &str, expected: &str) -> String {
+    let (stdout, stderr) = run(name, source);
+    assert!(stderr.contains(&format!("警告[{}]", code)), "期望警告 {}，实际输出:\n{}", code, stderr);
+    assert!(!stderr.contains("错误["), "{}", stderr);
+    assert_eq!(stdout, expected);
+    stderr
+}
+
+#[test]
+fn undefined_names() {
+    let stderr = assert_rejected("undefined_variable", "打印(\"开始\")\n打印(没有)\n", "E0200");
+    assert!(stderr.contains(":2:4"), "{}", stderr);
+    assert_rejected("undefined_function", "打印(\"开始\")\n没有的函数(1)\n", "E0201");
+    assert_rejected("undefined_assign", "打印(\"开始\")\n没有 = 1\n", "E0200");
+    assert_rejected("self_outside_method", "打印(自己)\n", "E0200");
+    
+    // 函数体在调用时才执行，可以使用后面声明的名字
+    assert_output(
+        "later_global",
+        "函数 显示() {
+    打印(名字)
+}
+让 名字 = \"小明\"
+显示()
+",
+        "\"小明\"\n",
+    );
+    // 函数中按源代码的顺序查找：同名的局部变量声明之前，使用的仍然是全局变量
+    assert_output(
+        "later_local",
+        "让 x: 数字 = 1
+函数 显示() {
+    打印(x + 1)
+    让 x: 字符串 = \"局部\"
+    打印(x)
+}
+显示()
+",
+        "2\n\"局部\"\n",
+    );
+    // 代码块中的声明在块外看不到
+    assert_rejected("block_scope", "如果 (真) {\n    让 里面 = 1\n    打印(里面)\n}\n打印(里面)\n", "E0200");
+}
+
+#[test]
+fn argument_counts() {
+    let stderr = assert_rejected("function_arity", "函数 加(a, b) {\n    返回 a + b\n}\n打印(加(1))\n", "E0203");
+    assert!(stderr.contains("参数数量不匹配: 期望 2, 得到 1"), "{}", stderr);
+    assert!(stderr.contains("在这里声明"), "{}", stderr);
+    assert!(stderr.contains("加(a, b)"), "{}", stderr);
+    
+    assert_rejected("struct_arity", "结构 点 {\n    x, y\n}\n让 p = 点(1, 2, 3)\n打印(p.x)\n", "E0203");
+    assert_rejected("builtin_arity", "打印(1, 2)\n", "E0203");
+    assert_rejected("range_arity", "对于 i 在 范围() {\n    打印(i)\n}\n", "E0203");
+    assert_rejected("lambda_arity", "让 平方 = 函数(x) { 返回 x * x }\n打印(平方())\n", "E0203");
+    
+    // 被重新赋值的名字调用时的值不确定，留到运行时检查
+    assert_output(
+        "reassigned_function",
+        "函数 一() {
+    返回 1
+}
+函数 二(x) {
+    返回 x + 1
+}
+变量 f = 一
+f = 二
+打印(f(1))
+",
+        "2\n",
+    );
+}
+
+#[test]
+fn calls_to_non_functions() {
+    assert_rejected("call_literal", "打印(5(1))\n", "E0202");
+    let stderr = assert_rejected("call_number", "让 数 = 5\n打印(数())\n", "E0202");
+    assert!(stderr.contains("数字"), "{}", stderr);
+    assert_rejected("call_string", "让 名字 = \"小明\"\n名字()\n", "E0202");
+}
+
+#[test]
+fn misplaced_control_flow() {
+    assert_rejected("return_outside", "打印(1)\n返回 1\n", "E0207");
+    assert_rejected("break_outside", "打印(1)\n跳出\n", "E0207");
+    assert_rejected("continue_outside", "如果 (真) {\n    继续\n}\n", "E0207");
+    // 函数不在循环里面，即使它在循环中声明
+    assert_rejected("break_in_function", "当 (真) {\n    函数 停() {\n        跳出\n    }\n    停()\n}\n", "E0207");
+    
+    assert_output(
+        "control_flow_in_place",
+        "函数 第一个非负数(数组) {
+    对于 x 在 数组 {
+        如果 (x < 0) {
+            继续
+        }
+        返回 x
+    }
+    返回 -1
+}
+打印(第一个非负数([-1, -3, 4]))
+",
+        "4\n",
+    );
+}
+
+#[test]
+fn unused_variables() {
+    let stderr = assert_warning("unused", "让 用到的 = 1\n让 没用 = 2\n打印(用到的)\n", "W0102", "1\n");
+    assert!(stderr.contains("变量 没用 声明后没有使用"), "{}", stderr);
+    assert!(stderr.contains(":2:3"), "{}", stderr);
+    assert!(!stderr.contains("用到的 声明后"), "{}", stderr);
+    
+    let stderr = assert_warning("unused_constant", "函数 主() {\n    常量 上限 = 10\n}\n主()\n", "W0102", "");
+    assert!(stderr.contains("常量 上限"), "{}", stderr);
+    
+    // 下划线开头的名字、公开的名字、函数和参数都不报告
+    assert_output(
+        "unused_exempt",
+        "让 _忽略 = 1
+公开 让 导出 = 2
+函数 没调用(参数) {
+}
+对于 i 在 范围(2) {
+    打印(i)
+}
+",
+        "0\n1\n",
+    );
+}
+
+#[test]
+fn unreachable_code() {
+    let stderr = assert_warning(
+        "after_return",
+        "函数 f() {
+    返回 1
+    打印(\"不会执行\")
+    打印(\"也不会\")
+}
+打印(f())
+",
+        "W0103",
+        "1\n",
+    );
+    assert!(stderr.contains("返回 之后的代码不会执行"), "{}", stderr);
+    assert!(stderr.contains(":3:5"), "{}", stderr);
+    // 同一段代码只报告一次
+    assert_eq!(stderr.matches("W0103").count(), 1, "{}", stderr);
+    
+    assert_warning("after_break", "当 (真) {\n    跳出\n    打印(1)\n}\n", "W0103", "");
+    assert_warning("after_throw", "尝试 {\n    抛出 \"错\"\n    打印(1)\n} 捕获 (e) {\n    打印(e)\n}\n", "W0103", "错误[E0213]: 错\n");
+}
+
+#[test]
+fn repl_knows_earlier_lines() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_cnlang"))
+        .arg("repl")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let input = "让 x = 41\n函数 加一(n) {\n返回 n + 1\n}\n打印(加一(x))\n打印(没有)\n加一()\n";
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    
+    assert!(stdout.contains("42"), "{}", stdout);
+    assert!(stderr.contains("[E0200]") && stderr.contains("[E0203]"), "{}", stderr);
+    // 前面输入的变量不会因为这一行没有用到而报告
+    assert!(!stderr.contains("W0102"), "{}", stderr);
+}
@@ -0,0 +1,90 @@
+//! 仓库根目录和 示例 目录中带 `// 期望:` 注释的 .cn 文件，分别用解释器和 C 后端运行，
+//! 两者的输出都要与注释一致
+//!
+//! 每行 `// 期望: 文本` 对应程序打印的一行，按顺序排列；`// 期望错误: E0205` 表示程序
+//! 打印完前面的内容之后以这个错误结束。
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use common::{run, run_compiled, OPT_LEVELS};
+
+struct Expectation {
+    stdout: String,
+    error: Option<String>,
+}
+
+/// 读出文件中的期望注释，没有注释的文件不参与测试
+fn expectation(source: &str) -> Option<Expectation> {
+    let mut stdout = String::new();
+    let mut error = None;
+    let mut annotated = false;
+    for line in source.lines().map(str::trim_start) {
+        if let Some(code) = line.strip_prefix("// 期望错误:") {
+            error = Some(code.trim().to_string());
+            annotated = true;
+        } else if let Some(text) = line.strip_prefix("// 期望:") {
+            stdout.push_str(text.strip_prefix(' ').unwrap_or(text));
+            stdout.push('\n');
+            annotated = true;
+        }
+    }
+    annotated.then_some(Expectation { stdout, error })
+}
+
+fn samples() -> Vec<PathBuf> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut files: Vec<PathBuf> = [root.to_path_buf(), root.join("示例")]
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flatten()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "cn"))
+        .collect();
+    files.sort();
+    files
+}
+
+fn check(sample: &str, stdout: &str, stderr: &str, expected: &Expectation) -> Result<(), String> {
+    if stdout != expected.stdout {
+        return Err(format!("{} 的输出不同\n期望:\n{}实际:\n{}{}", sample, expected.stdout, stdout, stderr));
+    }
+    match &expected.error {
+        Some(code) if !stderr.contains(&format!("[{}]", code)) => {
+            Err(format!("{} 期望错误 {}，实际:\n{}", sample, code, stderr))
+        }
+        None if !stderr.is_empty() => Err(format!("{} 不应报错:\n{}", sample, stderr)),
+        _ => Ok(()),
+    }
+}
+
+#[test]
+fn samples_match_expectations() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut failures = Vec::new();
+    let mut count = 0;
+    
+    for (i, path) in samples().iter().enumerate() {
+        let source = fs::read_to_string(path).unwrap();
+        let Some(expected) = expectation(&source) else {
+            continue;
+        };
+        count += 1;
+        let sample = path.strip_prefix(root).unwrap_or(path).display().to_string();
+        let name = format!("sample_{}", i);
+        
+        let (stdout, stderr) = run(&name, &source);
+        failures.extend(check(&format!("{} (解释器)", sample), &stdout, &stderr, &expected).err());
+        for opt_level in OPT_LEVELS {
+            if let Some((stdout, stderr)) = run_compiled(&name, &source, opt_level) {
+                let label = format!("{} (C -O{})", sample, opt_level.unwrap_or("默认"));
+                failures.extend(check(&label, &stdout, &stderr, &expected).err());
+            }
+        }
+    }
+    
+    assert!(count > 0, "没有找到带 // 期望: 注释的示例");
+    assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+}
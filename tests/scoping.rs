@@ -0,0 +1,197 @@
+//! 作用域规则的一致性测试：闭包、循环和递归
+
+mod common;
+
+use common::{assert_error, assert_output};
+
+#[test]
+fn function_assigns_to_global() {
+    assert_output(
+        "global",
+        "变量 计数 = 0
+函数 增加() {
+    计数 = 计数 + 1
+}
+增加()
+增加()
+打印(计数)
+",
+        "2\n",
+    );
+}
+
+#[test]
+fn block_declaration_shadows_outer() {
+    assert_output(
+        "shadow",
+        "变量 x = 1
+如果 (x == 1) {
+    变量 x = 2
+    打印(x)
+}
+打印(x)
+",
+        "2\n1\n",
+    );
+}
+
+#[test]
+fn block_assignment_updates_outer() {
+    assert_output(
+        "block_assign",
+        "变量 x = 1
+如果 (x == 1) {
+    x = 2
+}
+打印(x)
+",
+        "2\n",
+    );
+}
+
+#[test]
+fn block_bindings_do_not_leak() {
+    assert_error(
+        "if_leak",
+        "如果 (1 == 1) {
+    变量 内部 = 1
+}
+打印(内部)
+",
+        "E0200",
+    );
+    assert_error(
+        "for_leak",
+        "对于 字 在 \"ab\" {
+    变量 y = 字
+}
+打印(y)
+",
+        "E0200",
+    );
+    assert_error(
+        "for_variable_leak",
+        "对于 字 在 \"ab\" {
+}
+打印(字)
+",
+        "E0200",
+    );
+}
+
+#[test]
+fn loop_body_gets_fresh_scope_each_iteration() {
+    assert_output(
+        "loop_fresh",
+        "变量 i = 0
+变量 总和 = 0
+循环 (i < 3) {
+    变量 平方 = i * i
+    总和 = 总和 + 平方
+    i = i + 1
+}
+打印(总和)
+",
+        "5\n",
+    );
+}
+
+#[test]
+fn closure_keeps_its_defining_scope() {
+    assert_output(
+        "closure",
+        "函数 计数器() {
+    变量 n = 0
+    函数 下一个() {
+        n = n + 1
+        返回 n
+    }
+    返回 下一个
+}
+变量 甲 = 计数器()
+变量 乙 = 计数器()
+甲()
+甲()
+打印(甲())
+打印(乙())
+",
+        "3\n1\n",
+    );
+}
+
+#[test]
+fn closure_sees_later_assignments() {
+    assert_output(
+        "closure_late",
+        "变量 问候 = \"你好\"
+函数 说() {
+    返回 问候
+}
+问候 = \"再见\"
+打印(说())
+",
+        "\"再见\"\n",
+    );
+}
+
+#[test]
+fn recursion_has_separate_frames() {
+    assert_output(
+        "recursion",
+        "函数 阶乘(n) {
+    如果 (n <= 1) {
+        返回 1
+    }
+    变量 子 = 阶乘(n - 1)
+    返回 n * 子
+}
+打印(阶乘(5))
+",
+        "120\n",
+    );
+}
+
+#[test]
+fn parameter_shadows_global() {
+    assert_output(
+        "param",
+        "变量 n = 10
+函数 加一(n) {
+    n = n + 1
+    返回 n
+}
+打印(加一(1))
+打印(n)
+",
+        "2\n10\n",
+    );
+}
+
+#[test]
+fn inner_scope_may_shadow_constant() {
+    assert_output(
+        "shadow_const",
+        "常量 上限 = 10
+如果 (上限 > 5) {
+    变量 上限 = 3
+    上限 = 4
+    打印(上限)
+}
+打印(上限)
+",
+        "4\n10\n",
+    );
+}
+
+#[test]
+fn assigning_outer_constant_from_block_is_an_error() {
+    assert_error(
+        "assign_const",
+        "常量 上限 = 10
+如果 (上限 > 5) {
+    上限 = 3
+}
+",
+        "E0210",
+    );
+}
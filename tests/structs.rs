@@ -0,0 +1,91 @@
+//! 结构的声明、构造、字段和方法
+
+mod common;
+
+use common::{assert_error, assert_output};
+
+#[test]
+fn construct_and_print() {
+    assert_output(
+        "struct_print",
+        "结构 学生 { 名字, 分数 }
+让 小明 = 学生(\"小明\", 90)
+打印(小明)
+打印(小明.名字)
+小明.分数 = 95
+打印(小明.分数)
+打印(学生)
+",
+        "学生 { 名字: \"小明\", 分数: 90 }\n\"小明\"\n95\n[结构: 学生]\n",
+    );
+}
+
+#[test]
+fn methods_receive_self() {
+    assert_output(
+        "struct_methods",
+        "结构 计数器 {
+    次数
+    函数 增加(步长) {
+        自己.次数 = 自己.次数 + 步长
+        返回 自己
+    }
+    函数 描述() {
+        返回 \"计数: \" + 自己.次数
+    }
+}
+让 c = 计数器(0)
+c.增加(2).增加(3)
+打印(c.描述())
+",
+        "\"计数: 5\"\n",
+    );
+}
+
+#[test]
+fn class_keyword_and_shared_instances() {
+    assert_output(
+        "struct_class_keyword",
+        "类 节点 { 值, 下一个 }
+让 a = 节点(1, 0)
+让 b = a
+b.下一个 = a
+打印(a)
+打印(a == b)
+打印(节点(1, 0) == 节点(1, 0))
+",
+        "节点 { 值: 1, 下一个: 节点 {...} }\n真\n假\n",
+    );
+}
+
+#[test]
+fn function_fields_are_callable() {
+    assert_output(
+        "struct_function_field",
+        "结构 按钮 { 点击 }
+让 b = 按钮(函数(x) { 返回 x * 2 })
+打印(b.点击(21))
+",
+        "42\n",
+    );
+}
+
+#[test]
+fn builtin_methods_still_work() {
+    assert_output(
+        "struct_builtin_methods",
+        "让 列表 = [1]\n列表.添加(2)\n打印(列表.长度())\n打印({\"a\": 1}.键())\n",
+        "2\n[\"a\"]\n",
+    );
+}
+
+#[test]
+fn errors() {
+    assert_error("struct_unknown_field", "结构 点 { x }\n点(1).y\n", "E0212");
+    assert_error("struct_assign_unknown_field", "结构 点 { x }\n点(1).y = 2\n", "E0212");
+    assert_error("struct_unknown_method", "结构 点 { x }\n点(1).移动()\n", "E0212");
+    assert_error("array_unknown_method", "[1].排序()\n", "E0212");
+    assert_error("struct_wrong_arity", "结构 点 { x, y }\n点(1)\n", "E0203");
+    assert_error("struct_duplicate_member", "结构 点 { x, x }\n", "E0107");
+    assert_error("self_outside_method", "打印(自己)\n", "E0200");
+}
@@ -0,0 +1,78 @@
+//! 查找 C 编译器并编译为可执行文件、动态库和目标文件
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+use common::c_compiler;
+
+const PROGRAM: &str = "打印(\"你好\")\n";
+
+/// 在独立的临时目录中写入 main.cn，用 `args` 编译
+fn compile(name: &str, args: &[&str], envs: &[(&str, &str)]) -> (PathBuf, Output) {
+    let dir = env::temp_dir().join(format!("cnlang_toolchain_{}_{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("main.cn"), PROGRAM).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_cnlang"))
+        .current_dir(&dir)
+        .args(["build", "main.cn"])
+        .args(args)
+        .envs(envs.iter().copied())
+        .output()
+        .unwrap();
+    (dir, output)
+}
+
+#[test]
+fn default_output_names_follow_the_platform() {
+    let Some(compiler) = c_compiler() else {
+        return;
+    };
+    let (dir, output) = compile("names", &["--cc", &compiler], &[]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let exe = dir.join(format!("main{}", env::consts::EXE_SUFFIX));
+    assert_eq!(String::from_utf8(Command::new(&exe).output().unwrap().stdout).unwrap(), "\"你好\"\n");
+    
+    let (_, output) = compile("names", &["--target", "so", "--cc", &compiler, "--debug", "--opt-level", "0"], &[]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(dir.join(format!("main{}", env::consts::DLL_SUFFIX)).is_file());
+    
+    let (_, output) = compile("names", &["--target", "obj", "--cc", &compiler], &[]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(dir.join("main.o").is_file());
+    
+    // 临时的 C 文件不写到当前目录
+    assert!(!dir.join("temp_output.c").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn compiler_from_cc_variable() {
+    let Some(compiler) = c_compiler() else {
+        return;
+    };
+    let (dir, output) = compile("cc_variable", &["--output", "程序"], &[("CC", &compiler)]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(dir.join("程序").is_file());
+    
+    // --cc 优先于 CC
+    let (dir, output) = compile("cc_override", &["--cc", &compiler], &[("CC", "没有的编译器")]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn missing_compiler_is_reported() {
+    let (_, output) = compile("missing_cc", &["--cc", "没有的编译器"], &[]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("错误[E0300]: 找不到 --cc 指定的 C 编译器: 没有的编译器"), "{}", stderr);
+    
+    let (dir, output) = compile("missing_path", &[], &[("PATH", ""), ("CC", "")]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("找不到 C 编译器"), "{}", stderr);
+    assert!(stderr.contains("gcc、clang、tcc"), "{}", stderr);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,178 @@
+//! 类型标注：静态检查能确定的类型错误在运行前报告，其余的在运行时检查；C 后端对确定的类型不装箱
+
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use common::{assert_error, assert_output, assert_same_output, format, run};
+
+/// 运行一段有类型错误的程序：应该在运行前报告 `code`
+fn assert_rejected(name: &str, source: &str, code: &str) -> String {
+    let (stdout, stderr) = run(name, source);
+    assert!(stderr.contains(&format!("错误[{}]", code)), "期望错误 {}，实际输出:\n{}", code, stderr);
+    assert_eq!(stdout, "", "有静态错误的程序不应运行");
+    stderr
+}
+
+/// 用 `cnlang build --target c` 生成 C 源代码
+fn c_source(name: &str, source: &str) -> String {
+    let dir: PathBuf = std::env::temp_dir().join(format!("cnlang_types_{}_{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (source_path, c_path) = (dir.join("main.cn"), dir.join("main.c"));
+    fs::write(&source_path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_cnlang"))
+        .args(["build", "--target", "c", "--output"])
+        .arg(&c_path)
+        .arg(&source_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "生成失败:\n{}", String::from_utf8_lossy(&output.stderr));
+    let code = fs::read_to_string(&c_path).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    code
+}
+
+const TYPED: &str = "让 年龄: 数字 = 25
+函数 加(a: 数字, b: 数字) -> 数字 {
+    返回 a + b
+}
+函数 问候(名字: 字符串) -> 字符串 {
+    如果 名字 == \"小明\" {
+        返回 \"你好, \" + 名字
+    }
+    返回 \"陌生人\"
+}
+让 成年: 布尔值 = 年龄 >= 18 且 真
+让 总和: 数字 = 0
+对于 i 在 范围(5) {
+    总和 = 总和 + i
+}
+打印(加(年龄, 1))
+打印(问候(\"小明\") + 年龄)
+打印(问候(\"小红\"))
+打印(成年)
+打印(总和 / 4)
+打印(-年龄)
+";
+
+#[test]
+fn annotated_programs_run() {
+    let expected = "26\n\"你好, 小明25\"\n\"陌生人\"\n真\n2.5\n-25\n";
+    assert_same_output("typed", TYPED, expected);
+    
+    // 没有标注的参数和变量与以前一样是动态类型
+    assert_same_output(
+        "gradual",
+        "函数 除(a: 数字, b) -> 数字 {
+    返回 a / b
+}
+让 x = \"文本\"
+x = 3
+打印(除(x, 2))
+让 没有值: 空
+打印(没有值)
+",
+        "1.5\n空\n",
+    );
+}
+
+#[test]
+fn static_mismatches() {
+    let stderr = assert_rejected("declaration", "让 年龄: 数字 = \"二十五\"\n打印(年龄)\n", "E0217");
+    assert!(stderr.contains("类型不匹配: 变量 年龄 应该是 数字，得到 字符串"), "{}", stderr);
+    // 初始值有位置时指向初始值，并标出声明的类型
+    let stderr = assert_rejected("declaration_label", "让 年龄: 数字 = \"二十\" + \"五\"\n打印(年龄)\n", "E0217");
+    assert!(stderr.contains("类型在这里声明"), "{}", stderr);
+    
+    let stderr = assert_rejected("assignment", "让 名字: 字符串 = \"小明\"\n名字 = 真\n打印(名字)\n", "E0217");
+    assert!(stderr.contains("变量 名字 应该是 字符串，得到 布尔值"), "{}", stderr);
+    
+    let stderr = assert_rejected(
+        "argument",
+        "函数 加(a: 数字, b: 数字) -> 数字 {\n    返回 a + b\n}\n打印(加(1, \"2\"))\n",
+        "E0217",
+    );
+    assert!(stderr.contains("参数 b 应该是 数字，得到 字符串"), "{}", stderr);
+    assert!(stderr.contains("加 在这里声明"), "{}", stderr);
+    
+    let stderr = assert_rejected("return", "函数 名字() -> 字符串 {\n    返回 1\n}\n打印(名字())\n", "E0217");
+    assert!(stderr.contains("返回值 应该是 字符串，得到 数字"), "{}", stderr);
+    
+    let stderr = assert_rejected(
+        "missing_return",
+        "函数 符号(x: 数字) -> 字符串 {\n    如果 x > 0 {\n        返回 \"正\"\n    }\n}\n打印(符号(1))\n",
+        "E0217",
+    );
+    assert!(stderr.contains("函数执行到结尾时没有返回值"), "{}", stderr);
+    
+    // 确定类型的运算数在运行前检查
+    let stderr = assert_rejected("operator", "让 名字: 字符串 = \"小明\"\n打印(名字 * 2)\n", "E0204");
+    assert!(stderr.contains("无法相乘"), "{}", stderr);
+    
+    // 类型从函数的返回类型推断
+    assert_rejected(
+        "inferred",
+        "函数 一() -> 数字 {\n    返回 1\n}\n让 文本: 字符串 = 一()\n打印(文本)\n",
+        "E0217",
+    );
+}
+
+#[test]
+fn unknown_type_names() {
+    let stderr = assert_rejected("unknown_type", "让 x: 整数 = 1\n打印(x)\n", "E0109");
+    assert!(stderr.contains("未知的类型: 整数"), "{}", stderr);
+    assert_rejected("unknown_return", "函数 f() -> 文本 {\n    返回 \"\"\n}\n", "E0109");
+}
+
+#[test]
+fn runtime_mismatches() {
+    // 没有标注的值在运行时检查
+    let source = "函数 加一(a: 数字) -> 数字 {\n    返回 a + 1\n}\n让 输入值 = \"1\"\n打印(\"开始\")\n打印(加一(输入值))\n";
+    assert_error("runtime_argument", source, "E0217");
+    let (stdout, stderr) = run("runtime_argument_message", source);
+    assert_eq!(stdout, "\"开始\"\n");
+    assert!(stderr.contains("参数 a 应该是 数字，得到 字符串"), "{}", stderr);
+    
+    // 运行时的类型错误可以被捕获
+    assert_same_output(
+        "runtime_caught",
+        "函数 跑(值) {
+    让 文本: 字符串 = \"a\"
+    文本 = 值
+    返回 文本
+}
+打印(跑(\"b\"))
+尝试 {
+    跑(1)
+} 捕获 (错误) {
+    打印(错误)
+}
+",
+        "\"b\"\n错误[E0217]: 类型不匹配: 变量 文本 应该是 字符串，得到 数字\n",
+    );
+}
+
+#[test]
+fn annotations_are_formatted() {
+    let source = "让   年龄:数字=25\n函数 加(a:数字,b)->数字 {\n返回 a+b\n}\n让 f = 函数(x:字符串)->字符串 { 返回 x }\n";
+    let formatted = format("annotations", source);
+    assert!(formatted.contains("让 年龄: 数字 = 25\n"), "{}", formatted);
+    assert!(formatted.contains("函数 加(a: 数字, b) -> 数字 {\n"), "{}", formatted);
+    assert!(formatted.contains("函数(x: 字符串) -> 字符串"), "{}", formatted);
+    assert_eq!(format("annotations_again", &formatted), formatted);
+}
+
+#[test]
+fn proven_types_are_unboxed() {
+    let code = c_source("unboxed", TYPED);
+    assert!(code.contains("double 加(double a, double b)"), "{}", code);
+    assert!(code.contains("char* 问候(char* 名字)"), "{}", code);
+    assert!(code.contains("bool global_成年"), "{}", code);
+    
+    // 没有标注的参数仍然是 Value
+    let code = c_source("boxed", "函数 显示(a: 数字, b) {\n    打印(a + b)\n}\n显示(1, 2)\n");
+    assert!(code.contains("Value 显示(double a, Value b)"), "{}", code);
+    assert_output("boxed_run", "函数 显示(a: 数字, b) {\n    打印(a + b)\n}\n显示(1, 2)\n", "3\n");
+}